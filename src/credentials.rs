@@ -0,0 +1,443 @@
+use anyhow::{anyhow, Result};
+use log::debug;
+use rfd::MessageDialog;
+use std::cell::RefCell;
+use std::ffi::{OsStr, OsString};
+use std::io::{self, IsTerminal};
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use crate::extractors::PasswordError;
+use crate::password_list::{archive_hash, PasswordList};
+
+/// パスワードを尋ね直す最大回数
+const MAX_PROMPTS: usize = 3;
+
+/// 暗号化されたアーカイブのパスワードの入手先
+///
+/// `--password`（または環境変数 `ARCHR_PASSWORD`）で指定されていればそれだけを使う。
+/// 指定がなければ、設定ディレクトリのパスワード一覧を順に試したあと、端末で入力してもらう。
+/// 端末がない場合（ダブルクリックで起動された場合など）はダイアログで入力してもらう。
+pub struct PasswordSource {
+    fixed: Option<String>,
+    list: RefCell<PasswordList>,
+    /// 端末の代わりにパスワードを尋ねるプログラム（環境変数 `ARCHR_ASKPASS`）
+    askpass: Option<OsString>,
+}
+
+impl PasswordSource {
+    pub fn new(fixed: Option<String>) -> Self {
+        // パスワードが指定されている場合は一覧を使わないため、読み込まない
+        let list = if fixed.is_some() {
+            PasswordList::default()
+        } else {
+            PasswordList::load()
+        };
+        Self {
+            fixed,
+            list: RefCell::new(list),
+            askpass: std::env::var_os("ARCHR_ASKPASS").filter(|askpass| !askpass.is_empty()),
+        }
+    }
+
+    /// パスワードが必要になる処理を実行する
+    ///
+    /// `op` が `PasswordError` を返した場合は一覧のパスワードを試し、
+    /// それでも開けなければパスワードを尋ねて、受け取ったパスワードで再試行する。
+    pub fn run<T>(
+        &self,
+        file_path: &Path,
+        mut op: impl FnMut(Option<&str>) -> Result<T>,
+    ) -> Result<T> {
+        let mut error = match op(self.fixed.as_deref()) {
+            Ok(value) => return Ok(value),
+            Err(error) => error,
+        };
+        // 指定されたパスワードが間違っている場合は尋ね直さない（スクリプトから使う場合など）
+        if !error.is::<PasswordError>() || self.fixed.is_some() {
+            return Err(error);
+        }
+
+        let mut list = self.list.borrow_mut();
+        if !list.is_empty() {
+            let archive_hash = archive_hash(file_path)?;
+            let candidates: Vec<String> = list
+                .candidates(&archive_hash)
+                .into_iter()
+                .map(str::to_string)
+                .collect();
+            for password in candidates {
+                match op(Some(&password)) {
+                    Ok(value) => {
+                        list.remember(&archive_hash, &password);
+                        return Ok(value);
+                    }
+                    Err(e) if e.is::<PasswordError>() => error = e,
+                    Err(e) => return Err(e),
+                }
+            }
+        }
+
+        for prompts in 0..MAX_PROMPTS {
+            let retry = prompts > 0 && error.downcast_ref() == Some(&PasswordError::Wrong);
+            let Some(password) = self.prompt_password(file_path, retry)? else {
+                return Err(error);
+            };
+            match op(Some(&password)) {
+                Ok(value) => return Ok(value),
+                Err(e) if e.is::<PasswordError>() => error = e,
+                Err(e) => return Err(e),
+            }
+        }
+        Err(error)
+    }
+
+    /// パスワードを入力してもらう。キャンセルされた場合は `None` を返す
+    ///
+    /// `ARCHR_ASKPASS` が指定されていればそのプログラムで、端末があれば端末で尋ねる。
+    /// どちらもなければダイアログで尋ね、それもキャンセルされたときだけ指定方法を案内する。
+    fn prompt_password(&self, file_path: &Path, retry: bool) -> Result<Option<String>> {
+        if let Some(askpass) = &self.askpass {
+            let text = dialog_text(file_path, retry);
+            return read_askpass(askpass_command(askpass, &text)).map_err(|e| {
+                anyhow!(t!(
+                    "ui.error_askpass_failed",
+                    program = askpass.to_string_lossy(),
+                    error = e
+                )
+                .to_string())
+            });
+        }
+
+        if std::io::stdin().is_terminal() {
+            // 入力プロンプトの直前に表示するため、ログではなく端末に直接出力する
+            if retry {
+                eprintln!("{}", t!("ui.warning_password_wrong"));
+            }
+            let input = rpassword::prompt_password(
+                t!("ui.password_prompt", file = file_path.display()).to_string(),
+            )?;
+            return Ok(Some(input).filter(|input| !input.is_empty()));
+        }
+
+        if let Some(input) = prompt_with_dialog(file_path, retry) {
+            return Ok(Some(input));
+        }
+        // 入力できなかったため、パスワードの指定方法を案内する
+        MessageDialog::new()
+            .set_title(t!("ui.password_dialog_title").to_string())
+            .set_description(t!("ui.password_no_terminal", file = file_path.display()).to_string())
+            .set_level(rfd::MessageLevel::Warning)
+            .show();
+        Ok(None)
+    }
+}
+
+/// ダイアログに表示する文言（間違っていた場合はその旨も添える）
+fn dialog_text(file_path: &Path, retry: bool) -> String {
+    let text = t!("ui.password_dialog_prompt", file = file_path.display());
+    if retry {
+        format!("{}\n{}", t!("ui.warning_password_wrong"), text)
+    } else {
+        text.to_string()
+    }
+}
+
+/// 端末がない場合に、`SSH_ASKPASS` か OS のダイアログでパスワードを入力してもらう
+///
+/// 使えるものが見つからないか、キャンセルされた場合は `None` を返す。
+fn prompt_with_dialog(file_path: &Path, retry: bool) -> Option<String> {
+    let text = dialog_text(file_path, retry);
+    let title = t!("ui.password_dialog_title").to_string();
+
+    let mut commands = Vec::new();
+    if let Some(askpass) = std::env::var_os("SSH_ASKPASS").filter(|askpass| !askpass.is_empty()) {
+        commands.push(askpass_command(&askpass, &text));
+    }
+    commands.extend(dialog_commands(&title, &text));
+
+    for command in commands {
+        match read_askpass(command) {
+            Ok(input) => return input,
+            // 入っていないプログラムは飛ばして次を試す
+            Err(e) => debug!("password dialog unavailable: {e}"),
+        }
+    }
+    None
+}
+
+/// askpass 形式のプログラム（文言を引数に取り、入力されたパスワードを標準出力に書く）
+fn askpass_command(program: &OsStr, text: &str) -> Command {
+    let mut command = Command::new(program);
+    command.arg(text);
+    command
+}
+
+/// OS ごとのパスワード入力ダイアログ（使えそうな順）
+#[cfg(target_os = "macos")]
+fn dialog_commands(title: &str, text: &str) -> Vec<Command> {
+    let mut command = Command::new("osascript");
+    // 文言は引数で渡し、AppleScript の文字列として組み立てない
+    command.args([
+        "-e",
+        "on run argv",
+        "-e",
+        "display dialog (item 1 of argv) with title (item 2 of argv) default answer \"\" with hidden answer",
+        "-e",
+        "text returned of result",
+        "-e",
+        "end run",
+        text,
+        title,
+    ]);
+    vec![command]
+}
+
+/// OS ごとのパスワード入力ダイアログ（使えそうな順）
+#[cfg(windows)]
+fn dialog_commands(title: &str, text: &str) -> Vec<Command> {
+    use std::os::windows::process::CommandExt;
+
+    /// コンソールウィンドウを開かずに起動する
+    const CREATE_NO_WINDOW: u32 = 0x0800_0000;
+    // 文言は環境変数で渡し、スクリプトとして組み立てない
+    const SCRIPT: &str = "\
+        [Console]::OutputEncoding = [Text.Encoding]::UTF8
+        Add-Type -AssemblyName System.Windows.Forms
+        $f = New-Object Windows.Forms.Form
+        $f.Text = $env:ARCHR_DIALOG_TITLE
+        $f.FormBorderStyle = 'FixedDialog'
+        $f.StartPosition = 'CenterScreen'
+        $f.MinimizeBox = $false
+        $f.MaximizeBox = $false
+        $f.TopMost = $true
+        $f.ClientSize = New-Object Drawing.Size(360, 120)
+        $l = New-Object Windows.Forms.Label
+        $l.Text = $env:ARCHR_DIALOG_TEXT
+        $l.SetBounds(10, 10, 340, 40)
+        $t = New-Object Windows.Forms.TextBox
+        $t.UseSystemPasswordChar = $true
+        $t.SetBounds(10, 55, 340, 20)
+        $ok = New-Object Windows.Forms.Button
+        $ok.Text = 'OK'
+        $ok.DialogResult = 'OK'
+        $ok.SetBounds(194, 85, 75, 25)
+        $c = New-Object Windows.Forms.Button
+        $c.Text = 'Cancel'
+        $c.DialogResult = 'Cancel'
+        $c.SetBounds(275, 85, 75, 25)
+        $f.Controls.AddRange(@($l, $t, $ok, $c))
+        $f.AcceptButton = $ok
+        $f.CancelButton = $c
+        if ($f.ShowDialog() -ne 'OK') { exit 1 }
+        [Console]::Out.Write($t.Text)";
+
+    let mut command = Command::new("powershell");
+    command
+        .args(["-NoProfile", "-NonInteractive", "-Command", SCRIPT])
+        .env("ARCHR_DIALOG_TITLE", title)
+        .env("ARCHR_DIALOG_TEXT", text)
+        .creation_flags(CREATE_NO_WINDOW);
+    vec![command]
+}
+
+/// OS ごとのパスワード入力ダイアログ（使えそうな順）
+#[cfg(all(unix, not(target_os = "macos")))]
+fn dialog_commands(title: &str, text: &str) -> Vec<Command> {
+    // 画面がなければダイアログは出せない
+    if std::env::var_os("DISPLAY").is_none() && std::env::var_os("WAYLAND_DISPLAY").is_none() {
+        return Vec::new();
+    }
+
+    let mut zenity = Command::new("zenity");
+    zenity
+        .args(["--entry", "--hide-text", "--title"])
+        .arg(title)
+        .arg("--text")
+        .arg(text);
+    let mut kdialog = Command::new("kdialog");
+    kdialog
+        .arg("--title")
+        .arg(title)
+        .arg("--password")
+        .arg(text);
+    vec![zenity, kdialog]
+}
+
+/// OS ごとのパスワード入力ダイアログ（使えそうな順）
+#[cfg(not(any(unix, windows)))]
+fn dialog_commands(_title: &str, _text: &str) -> Vec<Command> {
+    Vec::new()
+}
+
+/// プログラムにパスワードを尋ねさせ、標準出力から受け取る
+///
+/// キャンセルされた（0 以外で終了した）場合は `None` を、起動できなかった場合はエラーを返す。
+fn read_askpass(mut command: Command) -> io::Result<Option<String>> {
+    let output = command
+        .stdin(Stdio::null())
+        .stderr(Stdio::inherit())
+        .output()?;
+    if !output.status.success() {
+        return Ok(None);
+    }
+    let mut input = String::from_utf8(output.stdout)
+        .map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))?;
+    // 末尾の改行はパスワードに含めない
+    if input.ends_with('\n') {
+        input.pop();
+        if input.ends_with('\r') {
+            input.pop();
+        }
+    }
+    Ok(Some(input).filter(|input| !input.is_empty()))
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::extractors::rar_extractor::RarExtractor;
+    use crate::extractors::test_support::{read_output, Fixture};
+    use crate::extractors::ExtractOptions;
+    use std::fs;
+    use std::os::unix::fs::PermissionsExt;
+    use std::path::PathBuf;
+
+    /// 呼ばれるたびに `answers` を順に返し、受け取った文言を `<script>.log` に書き残す askpass
+    fn askpass_script(dir: &Path, answers: &[&str]) -> PathBuf {
+        let script = dir.join("askpass");
+        let cases: String = answers
+            .iter()
+            .enumerate()
+            .map(|(i, answer)| format!("  {i}) echo '{answer}' ;;\n"))
+            .collect();
+        fs::write(
+            &script,
+            format!(
+                "#!/bin/sh\n\
+                 n=$(cat \"$0.count\" 2>/dev/null || echo 0)\n\
+                 echo $((n + 1)) > \"$0.count\"\n\
+                 printf '%s\\n' \"$1\" >> \"$0.log\"\n\
+                 case $n in\n{cases}  *) exit 1 ;;\nesac\n"
+            ),
+        )
+        .unwrap();
+        fs::set_permissions(&script, fs::Permissions::from_mode(0o755)).unwrap();
+        script
+    }
+
+    fn source_with_askpass(askpass: &Path) -> PasswordSource {
+        PasswordSource {
+            fixed: None,
+            list: RefCell::new(PasswordList::default()),
+            askpass: Some(askpass.into()),
+        }
+    }
+
+    /// "secret" でだけ開けるアーカイブの代わり
+    fn open(password: Option<&str>) -> Result<&'static str> {
+        match password {
+            Some("secret") => Ok("opened"),
+            Some(_) => Err(PasswordError::Wrong.into()),
+            None => Err(PasswordError::Required.into()),
+        }
+    }
+
+    #[test]
+    fn asks_askpass_until_the_password_is_right() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = askpass_script(dir.path(), &["wrong", "secret"]);
+        let source = source_with_askpass(&script);
+
+        let archive = dir.path().join("a.zip");
+        assert_eq!(source.run(&archive, open).unwrap(), "opened");
+
+        // 2回目は間違っていたことも伝える
+        let log = fs::read_to_string(script.with_extension("log")).unwrap();
+        let prompts: Vec<&str> = log.lines().collect();
+        assert_eq!(prompts.len(), 3);
+        assert!(prompts[0].contains("a.zip"));
+        assert_eq!(prompts[1], t!("ui.warning_password_wrong"));
+        assert!(prompts[2].contains("a.zip"));
+    }
+
+    #[test]
+    fn stops_when_askpass_is_cancelled() {
+        let dir = tempfile::tempdir().unwrap();
+        let script = askpass_script(dir.path(), &["wrong"]);
+        let source = source_with_askpass(&script);
+
+        let error = source.run(&dir.path().join("a.zip"), open).unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&PasswordError::Wrong));
+        let count = fs::read_to_string(script.with_extension("count")).unwrap();
+        assert_eq!(count.trim(), "2");
+    }
+
+    #[test]
+    fn reports_missing_askpass() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = source_with_askpass(&dir.path().join("missing"));
+        assert!(source
+            .run(&dir.path().join("a.zip"), open)
+            .is_err_and(|e| !e.is::<PasswordError>()));
+    }
+
+    #[test]
+    fn asks_askpass_for_encrypted_rar() {
+        let cases: [(&str, &[u8], &str); 2] = [
+            (
+                "crypted.rar",
+                include_bytes!("../tests/fixtures/rar/crypted.rar"),
+                "unrar",
+            ),
+            (
+                "comment-hpw-password.rar",
+                include_bytes!("../tests/fixtures/rar/comment-hpw-password.rar"),
+                "password",
+            ),
+        ];
+        for (name, data, password) in cases {
+            let fixture = Fixture::new(name, data);
+            let script = askpass_script(&fixture.root, &["wrong", password]);
+            let source = source_with_askpass(&script);
+
+            source
+                .run(&fixture.archive, |password| {
+                    let options = ExtractOptions {
+                        password: password.map(str::to_string),
+                        ..ExtractOptions::default()
+                    };
+                    fixture.extract(&RarExtractor, &options)
+                })
+                .unwrap();
+            assert_eq!(
+                read_output(&fixture.out, ".gitignore"),
+                Some(b"target\nCargo.lock\n".to_vec()),
+                "{name}"
+            );
+            // 間違ったパスワードで一度失敗してから尋ね直している
+            let count = fs::read_to_string(script.with_extension("count")).unwrap();
+            assert_eq!(count.trim(), "2", "{name}");
+        }
+    }
+
+    #[test]
+    fn reads_askpass_output() {
+        let sh = |script: &str| {
+            let mut command = Command::new("sh");
+            command.args(["-c", script]);
+            read_askpass(command)
+        };
+        assert_eq!(
+            sh("printf 'p a s\\r\\n'").unwrap().as_deref(),
+            Some("p a s")
+        );
+        assert_eq!(sh("printf secret").unwrap().as_deref(), Some("secret"));
+        assert_eq!(sh("echo").unwrap(), None);
+        assert_eq!(sh("echo secret; exit 1").unwrap(), None);
+        assert!(read_askpass(Command::new("/nonexistent/askpass")).is_err());
+    }
+}
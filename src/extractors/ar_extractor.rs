@@ -0,0 +1,498 @@
+use anyhow::Result;
+use indicatif::ProgressBar;
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read};
+use std::path::Path;
+use tar::Archive;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+// Import common decode function
+use super::common::{decode_filename, entry_output_path, new_spinner, unix_datetime};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::limits::LimitTracker;
+use super::tar_extractor::{
+    extract_tar_entries, list_tar_entries, test_tar_entries, TarCompression,
+};
+
+/// ar アーカイブのマジックナンバー
+pub const AR_MAGIC: &[u8] = b"!<arch>\n";
+
+/// Debian パッケージは最初のメンバーが `debian-binary`
+const DEB_MAGIC: &[u8] = b"!<arch>\ndebian-binary";
+
+/// メンバーのヘッダの長さ
+const MEMBER_HEADER_LEN: usize = 60;
+
+/// BSD 形式の長いファイル名の最大の長さ（ヘッダの値のままメモリを確保しないための上限）
+const MAX_NAME_LEN: u64 = 4096;
+/// GNU 形式の長いファイル名の表の最大のサイズ
+const MAX_LONG_NAMES_LEN: u64 = 16 * 1024 * 1024;
+
+/// ar アーカイブの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArFlavor {
+    /// 通常の ar アーカイブ（静的ライブラリなど）
+    Plain,
+    /// Debian パッケージ（`control.tar.*` と `data.tar.*` も展開する）
+    Deb,
+}
+
+/// ar アーカイブのメンバーの情報
+pub struct ArMember {
+    pub name: String,
+    pub size: u64,
+    pub mtime: u64,
+    pub mode: u32,
+}
+
+/// ar アーカイブのメンバーを順に読み込むリーダー
+///
+/// `next_member` で次のメンバーに進み、`Read` でそのメンバーの内容を読み込む。
+/// GNU 形式（`//` の長いファイル名の表）と BSD 形式（`#1/長さ`）の長いファイル名に対応し、
+/// シンボル表のメンバーは読み飛ばす。
+pub struct ArReader<R> {
+    inner: R,
+    /// 現在のメンバーの未読のバイト数
+    remaining: u64,
+    /// 現在のメンバーの後の、2 バイト境界に揃えるための詰め物
+    padding: u64,
+    /// GNU 形式の長いファイル名の表
+    long_names: Vec<u8>,
+}
+
+impl<R: Read> ArReader<R> {
+    pub fn new(mut inner: R) -> io::Result<Self> {
+        let mut magic = [0u8; AR_MAGIC.len()];
+        inner.read_exact(&mut magic)?;
+        if magic != AR_MAGIC {
+            return Err(invalid_data("not an ar archive"));
+        }
+
+        Ok(Self {
+            inner,
+            remaining: 0,
+            padding: 0,
+            long_names: Vec::new(),
+        })
+    }
+
+    /// 次のメンバーに進む（残りの内容は読み飛ばす）。終端に達した場合は `None` を返す
+    pub fn next_member(&mut self) -> io::Result<Option<ArMember>> {
+        loop {
+            let skip = self.remaining + self.padding;
+            io::copy(&mut (&mut self.inner).take(skip), &mut io::sink())?;
+            self.remaining = 0;
+            self.padding = 0;
+
+            let mut header = [0u8; MEMBER_HEADER_LEN];
+            let read = read_full(&mut self.inner, &mut header)?;
+            if read == 0 {
+                return Ok(None);
+            }
+            if read < MEMBER_HEADER_LEN || &header[58..60] != b"`\n" {
+                return Err(invalid_data("corrupt ar member header"));
+            }
+
+            let mut size = parse_number(&header[48..58], 10)?;
+            self.remaining = size;
+            self.padding = size % 2;
+
+            let raw_name = field(&header[0..16]);
+            let name = match raw_name {
+                // シンボル表
+                b"/" | b"/SYM64/" => continue,
+                // GNU 形式の長いファイル名の表
+                b"//" => {
+                    if size > MAX_LONG_NAMES_LEN {
+                        return Err(invalid_data("ar long name table too large"));
+                    }
+                    let mut long_names = vec![0; size as usize];
+                    self.read_exact(&mut long_names)?;
+                    self.long_names = long_names;
+                    continue;
+                }
+                // GNU 形式の長いファイル名（表の中の位置）
+                [b'/', offset @ ..] => {
+                    let offset = parse_number(offset, 10)? as usize;
+                    let names = self.long_names.get(offset..).unwrap_or_default();
+                    let end = names
+                        .windows(2)
+                        .position(|w| w == b"/\n")
+                        .unwrap_or(names.len());
+                    names[..end].to_vec()
+                }
+                // BSD 形式の長いファイル名（内容の先頭に格納される）
+                [b'#', b'1', b'/', length @ ..] => {
+                    let length = parse_number(length, 10)?;
+                    if length > size {
+                        return Err(invalid_data("corrupt ar member header"));
+                    }
+                    if length > MAX_NAME_LEN {
+                        return Err(invalid_data("ar file name too long"));
+                    }
+                    let mut name = vec![0; length as usize];
+                    self.read_exact(&mut name)?;
+                    size -= length;
+                    while name.last() == Some(&0) {
+                        name.pop();
+                    }
+                    name
+                }
+                // GNU 形式ではファイル名の終わりに '/' がつく
+                _ => raw_name.strip_suffix(b"/").unwrap_or(raw_name).to_vec(),
+            };
+
+            // BSD 形式のシンボル表
+            if name.starts_with(b"__.SYMDEF") {
+                continue;
+            }
+
+            return Ok(Some(ArMember {
+                name: decode_filename(&name),
+                size,
+                mtime: parse_number(&header[16..28], 10)?,
+                mode: parse_number(&header[40..48], 8)? as u32,
+            }));
+        }
+    }
+}
+
+impl<R: Read> Read for ArReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// 末尾の空白を除いたヘッダのフィールド
+fn field(bytes: &[u8]) -> &[u8] {
+    let end = bytes.iter().rposition(|&b| b != b' ').map_or(0, |i| i + 1);
+    &bytes[..end]
+}
+
+/// 空白で埋められた数値のフィールドを読む（空の場合は 0）
+fn parse_number(bytes: &[u8], radix: u32) -> io::Result<u64> {
+    let text = std::str::from_utf8(bytes)
+        .map_err(|_| invalid_data("corrupt ar member header"))?
+        .trim();
+    if text.is_empty() {
+        return Ok(0);
+    }
+    u64::from_str_radix(text, radix).map_err(|_| invalid_data("corrupt ar member header"))
+}
+
+/// `buf` が埋まるか終端に達するまで読み込み、読み込んだバイト数を返す
+fn read_full<R: Read>(reader: &mut R, buf: &mut [u8]) -> io::Result<usize> {
+    let mut read = 0;
+    while read < buf.len() {
+        match reader.read(&mut buf[read..])? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+/// .deb の TAR の中のエントリ名を、展開先のディレクトリからのパスにする
+///
+/// 先頭の `./` を取り除き、`./` 自身のエントリは `None` を返す。
+fn nested_name(dir: &str, name: &str) -> Option<String> {
+    let name = name.trim_start_matches("./");
+    (!name.is_empty()).then(|| format!("{}/{}", dir, name))
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct ArExtractor {
+    flavor: ArFlavor,
+}
+
+impl ArExtractor {
+    pub fn new(flavor: ArFlavor) -> Self {
+        Self { flavor }
+    }
+
+    fn open(file_path: &Path) -> Result<ArReader<BufReader<File>>> {
+        let file = File::open(file_path)?;
+        Ok(ArReader::new(BufReader::new(file))?)
+    }
+
+    /// .deb の中で TAR として展開するメンバーの場合、展開先のディレクトリ名と圧縮形式を返す
+    ///
+    /// `control.tar.xz` は `control/`、`data.tar.zst` は `data/` に展開する。
+    fn nested_tar(&self, member: &ArMember) -> Option<(&'static str, TarCompression)> {
+        if self.flavor != ArFlavor::Deb {
+            return None;
+        }
+
+        let dir = ["control", "data"]
+            .into_iter()
+            .find(|dir| member.name.starts_with(&format!("{}.tar", dir)))?;
+        Some((dir, TarCompression::from_file_name(&member.name)?))
+    }
+}
+
+impl Extractor for ArExtractor {
+    fn name(&self) -> &'static str {
+        match self.flavor {
+            ArFlavor::Plain => "AR",
+            ArFlavor::Deb => "DEB",
+        }
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        match self.flavor {
+            ArFlavor::Plain => &["ar"],
+            ArFlavor::Deb => &["deb"],
+        }
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        match self.flavor {
+            ArFlavor::Plain => header.starts_with(AR_MAGIC),
+            ArFlavor::Deb => header.starts_with(DEB_MAGIC),
+        }
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        let mut reader = Self::open(file_path)?;
+        let mut entries = Vec::new();
+
+        while let Some(member) = reader.next_member()? {
+            // .deb の TAR の中身は、展開先のディレクトリの下のエントリとして示す
+            if let Some((dir, compression)) = self.nested_tar(&member) {
+                let decoder = compression.decoder(BufReader::new(&mut reader))?;
+                for entry in list_tar_entries(&mut Archive::new(decoder))? {
+                    if let Some(name) = nested_name(dir, &entry.name) {
+                        entries.push(ArchiveEntry { name, ..entry });
+                    }
+                }
+                continue;
+            }
+
+            entries.push(ArchiveEntry {
+                name: member.name,
+                size: member.size,
+                // ar は圧縮しない
+                compressed_size: Some(member.size),
+                modified: unix_datetime(member.mtime as i64),
+                kind: EntryKind::File,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let mut reader = Self::open(file_path)?;
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+
+        // プログレスバーの設定
+        let message = match self.flavor {
+            ArFlavor::Plain => t!("progress.extracting_ar"),
+            ArFlavor::Deb => t!("progress.extracting_deb"),
+        };
+        let pb = new_spinner(message.to_string());
+
+        let result = self.extract_members(&mut reader, extract_dir, options, &tracker, &pb);
+        tracker.finish(result)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let mut reader = Self::open(file_path)?;
+
+        while let Some(member) = reader.next_member()? {
+            if let Some((dir, compression)) = self.nested_tar(&member) {
+                let decoder = compression.decoder(BufReader::new(&mut reader))?;
+                test_tar_entries(Archive::new(decoder), &mut |name, result| {
+                    if let Some(name) = nested_name(dir, name) {
+                        report(&name, result);
+                    }
+                })?;
+                continue;
+            }
+
+            // ar にはチェックサムがないため、内容を最後まで読めるかだけを確認する
+            let result = io::copy(&mut reader, &mut io::sink());
+            report(&member.name, result.map(|_| ()).map_err(Into::into));
+        }
+        Ok(())
+    }
+}
+
+impl ArExtractor {
+    fn extract_members<R: Read>(
+        &self,
+        reader: &mut ArReader<R>,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+        tracker: &LimitTracker,
+        pb: &ProgressBar,
+    ) -> Result<()> {
+        while let Some(member) = reader.next_member()? {
+            // .deb の control.tar.* と data.tar.* は TAR の処理で展開する
+            if let Some((dir, compression)) = self.nested_tar(&member) {
+                let tar_dir = extract_dir.join(dir);
+                fs::create_dir_all(&tar_dir)?;
+                let decoder = compression.decoder(BufReader::new(&mut *reader))?;
+                extract_tar_entries(&mut Archive::new(decoder), &tar_dir, options, tracker, pb)?;
+                continue;
+            }
+
+            tracker.start_entry()?;
+            pb.set_message(format!(
+                "{}",
+                t!("progress.extracting_file", file = member.name)
+            ));
+
+            // 解凍先の外を指すエントリはスキップ
+            let Some(output_path) = entry_output_path(extract_dir, &member.name, options) else {
+                pb.inc(1);
+                continue;
+            };
+
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut output_file = File::create(&output_path)?;
+            io::copy(&mut tracker.reader(&mut *reader), &mut output_file)?;
+
+            // ファイル権限を設定 (Unix系のみ)
+            #[cfg(unix)]
+            {
+                use std::os::unix::fs::PermissionsExt;
+                let mode = member.mode & 0o7777;
+                if mode != 0 {
+                    fs::set_permissions(&output_path, fs::Permissions::from_mode(mode))?;
+                }
+            }
+
+            pb.inc(1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{read_output, Fixture, TarBuilder};
+
+    fn member(name: &str, size: &str, data: &[u8]) -> Vec<u8> {
+        let header = format!(
+            "{:<16}{:<12}{:<6}{:<6}{:<8}{:<10}`\n",
+            name, "0", "0", "0", "100644", size
+        );
+        let mut member = header.into_bytes();
+        member.extend_from_slice(data);
+        if data.len() % 2 == 1 {
+            member.push(b'\n');
+        }
+        member
+    }
+
+    fn file(name: &str, data: &[u8]) -> Vec<u8> {
+        member(name, &data.len().to_string(), data)
+    }
+
+    fn archive(members: &[Vec<u8>]) -> Vec<u8> {
+        [AR_MAGIC.to_vec(), members.concat()].concat()
+    }
+
+    fn read_members(data: &[u8]) -> io::Result<Vec<(String, Vec<u8>)>> {
+        let mut reader = ArReader::new(data)?;
+        let mut members = Vec::new();
+        while let Some(member) = reader.next_member()? {
+            let mut contents = Vec::new();
+            reader.read_to_end(&mut contents)?;
+            members.push((member.name, contents));
+        }
+        Ok(members)
+    }
+
+    #[test]
+    fn reads_gnu_and_bsd_names() {
+        let long_names = b"a_very_long_object_file_name.o/\nanother_long_name.o/\n";
+        let data = archive(&[
+            file("/", b"\0\0\0\0"),
+            file("//", long_names),
+            file("short.o/", b"odd"),
+            file("/32", b"gnu long"),
+            file("#1/20", b"bsd_long_name_here.obsd long"),
+            file("__.SYMDEF", b"symbols"),
+        ]);
+
+        assert_eq!(
+            read_members(&data).unwrap(),
+            vec![
+                ("short.o".to_string(), b"odd".to_vec()),
+                ("another_long_name.o".to_string(), b"gnu long".to_vec()),
+                ("bsd_long_name_here.o".to_string(), b"bsd long".to_vec()),
+            ]
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_names_before_allocating() {
+        let huge = archive(&[member("//", "9999999999", b"")]);
+        let error = read_members(&huge).unwrap_err();
+        assert!(error.to_string().contains("too large"), "{error}");
+
+        let huge = archive(&[member("#1/4000000000", "9999999999", b"")]);
+        let error = read_members(&huge).unwrap_err();
+        assert!(error.to_string().contains("too long"), "{error}");
+    }
+
+    #[test]
+    fn rejects_corrupt_headers() {
+        assert!(read_members(b"!<arch>\nshort").is_err());
+        assert!(read_members(&archive(&[member("a.o", "12x", b"")])).is_err());
+        assert!(read_members(&archive(&[member("a.o", "100", b"truncated")])).is_err());
+        assert!(ArReader::new(&b"!<arch>"[..]).is_err());
+    }
+
+    #[test]
+    fn extracts_deb_members() {
+        let data_tar = TarBuilder::new()
+            .file("./usr/bin/tool", b"#!/bin/sh\n")
+            .build();
+        let deb = archive(&[file("debian-binary", b"2.0\n"), file("data.tar", &data_tar)]);
+        let fixture = Fixture::new("tool.deb", &deb);
+        fixture
+            .extract(&ArExtractor::new(ArFlavor::Deb), &ExtractOptions::default())
+            .unwrap();
+
+        assert_eq!(
+            read_output(&fixture.out, "debian-binary").unwrap(),
+            b"2.0\n"
+        );
+        assert_eq!(
+            read_output(&fixture.out, "data/usr/bin/tool").unwrap(),
+            b"#!/bin/sh\n"
+        );
+    }
+}
@@ -0,0 +1,1032 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use flate2::{Decompress, FlushDecompress};
+use indicatif::ProgressBar;
+use log::debug;
+use lzxd::{Lzxd, WindowSize};
+use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// Import the i18n macro
+use rust_i18n::t;
+
+// Import common decode function
+use super::common::{decode_filename, dos_datetime, entry_output_path, new_progress_bar};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::limits::LimitTracker;
+use super::volumes::{missing_volume_error, VolumeSet};
+
+/// キャビネットファイルのマジックナンバー
+pub const CAB_MAGIC: &[u8] = b"MSCF";
+
+/// CFHEADER の固定長部分のサイズ
+const HEADER_LEN: usize = 36;
+/// CFFOLDER の固定長部分のサイズ
+const FOLDER_LEN: usize = 8;
+/// CFFILE の固定長部分のサイズ
+const FILE_LEN: usize = 16;
+/// CFDATA の固定長部分のサイズ
+const DATA_LEN: usize = 8;
+
+/// CFHEADER のフラグ
+const FLAG_PREV_CABINET: u16 = 0x0001;
+const FLAG_NEXT_CABINET: u16 = 0x0002;
+const FLAG_RESERVE_PRESENT: u16 = 0x0004;
+
+/// CFFILE の属性のうち、ファイル名が UTF-8 であることを示すビット
+const ATTRIB_NAME_IS_UTF8: u16 = 0x80;
+
+/// CFFILE のフォルダ番号のうち、前後のキャビネットにまたがるファイルを示す特別な値
+const FOLDER_CONTINUED_FROM_PREV: u16 = 0xfffd;
+const FOLDER_CONTINUED_TO_NEXT: u16 = 0xfffe;
+const FOLDER_CONTINUED_PREV_AND_NEXT: u16 = 0xffff;
+
+/// フォルダの圧縮方式
+const COMPRESSION_MASK: u16 = 0x000f;
+const COMPRESSION_NONE: u16 = 0;
+const COMPRESSION_MSZIP: u16 = 1;
+const COMPRESSION_QUANTUM: u16 = 2;
+const COMPRESSION_LZX: u16 = 3;
+
+/// 1つのデータブロックを展開した後の最大サイズ
+const MAX_BLOCK_SIZE: usize = 32768;
+/// MSZIP の各データブロックの先頭のシグネチャ
+const MSZIP_SIGNATURE: &[u8] = b"CK";
+/// Deflate の辞書（直前のブロックから参照できる範囲）のサイズ
+const DEFLATE_WINDOW_SIZE: usize = 32768;
+/// 前後のキャビネット名などの文字列の最大長
+const MAX_NAME_LEN: usize = 256;
+
+/// キャビネットのヘッダに記録された前後のキャビネットのファイル名
+pub struct CabinetLinks {
+    pub prev: Option<String>,
+    pub next: Option<String>,
+}
+
+/// `file_path` のキャビネットのヘッダから前後のキャビネットのファイル名を読む
+///
+/// キャビネットファイルでない場合は `None` を返す。
+pub fn read_cabinet_links(file_path: &Path) -> io::Result<Option<CabinetLinks>> {
+    let mut reader = BufReader::new(File::open(file_path)?);
+    let mut magic = [0u8; 4];
+    if reader.read_exact(&mut magic).is_err() || magic != CAB_MAGIC {
+        return Ok(None);
+    }
+    reader.seek(SeekFrom::Start(0))?;
+
+    let header = CabinetHeader::read(&mut reader)?;
+    Ok(Some(CabinetLinks {
+        prev: header.prev,
+        next: header.next,
+    }))
+}
+
+/// キャビネットファイル1つ分のヘッダ
+struct CabinetHeader {
+    /// 各データブロックの予約領域のサイズ
+    data_reserve: usize,
+    prev: Option<String>,
+    next: Option<String>,
+    folders: Vec<FolderHeader>,
+    files: Vec<FileHeader>,
+}
+
+/// CFFOLDER の内容
+struct FolderHeader {
+    data_offset: u64,
+    block_count: u16,
+    compression: u16,
+}
+
+/// CFFILE の内容
+struct FileHeader {
+    name: String,
+    size: u32,
+    /// フォルダを展開した内容の中での位置
+    offset: u32,
+    folder: u16,
+    modified: Option<NaiveDateTime>,
+}
+
+impl CabinetHeader {
+    fn read<R: Read + Seek>(reader: &mut R) -> io::Result<Self> {
+        let mut header = [0u8; HEADER_LEN];
+        reader.read_exact(&mut header)?;
+        if !header.starts_with(CAB_MAGIC) {
+            return Err(invalid_data("cab: bad magic number"));
+        }
+        let files_offset = u32_at(&header, 16);
+        let folder_count = u16_at(&header, 26);
+        let file_count = u16_at(&header, 28);
+        let flags = u16_at(&header, 30);
+
+        // 予約領域（署名などに使われる）のサイズ
+        let mut folder_reserve = 0;
+        let mut data_reserve = 0;
+        if flags & FLAG_RESERVE_PRESENT != 0 {
+            let mut sizes = [0u8; 4];
+            reader.read_exact(&mut sizes)?;
+            let header_reserve = u16_at(&sizes, 0);
+            folder_reserve = usize::from(sizes[2]);
+            data_reserve = usize::from(sizes[3]);
+            reader.seek(SeekFrom::Current(i64::from(header_reserve)))?;
+        }
+
+        // 前後のキャビネットの名前と、それが入っているディスクの名前
+        let mut prev = None;
+        let mut next = None;
+        if flags & FLAG_PREV_CABINET != 0 {
+            prev = Some(decode_filename(&read_cstring(reader)?));
+            read_cstring(reader)?;
+        }
+        if flags & FLAG_NEXT_CABINET != 0 {
+            next = Some(decode_filename(&read_cstring(reader)?));
+            read_cstring(reader)?;
+        }
+
+        let mut folders = Vec::with_capacity(usize::from(folder_count));
+        for _ in 0..folder_count {
+            let mut folder = [0u8; FOLDER_LEN];
+            reader.read_exact(&mut folder)?;
+            reader.seek(SeekFrom::Current(folder_reserve as i64))?;
+            folders.push(FolderHeader {
+                data_offset: u64::from(u32_at(&folder, 0)),
+                block_count: u16_at(&folder, 4),
+                compression: u16_at(&folder, 6),
+            });
+        }
+
+        reader.seek(SeekFrom::Start(u64::from(files_offset)))?;
+        let mut files = Vec::with_capacity(usize::from(file_count));
+        for _ in 0..file_count {
+            let mut file = [0u8; FILE_LEN];
+            reader.read_exact(&mut file)?;
+            let date = u16_at(&file, 10);
+            let time = u16_at(&file, 12);
+            let attribs = u16_at(&file, 14);
+
+            // UTF-8 のフラグがなければ作成した環境のコードページ（日本語環境では CP932）
+            let raw_name = read_cstring(reader)?;
+            let name = if attribs & ATTRIB_NAME_IS_UTF8 != 0 {
+                String::from_utf8_lossy(&raw_name).into_owned()
+            } else {
+                decode_filename(&raw_name)
+            };
+
+            files.push(FileHeader {
+                // 区切り文字はデコード後に置き換える（CP932 の2バイト目の 0x5C を壊さないため）
+                name: name.replace('\\', "/"),
+                size: u32_at(&file, 0),
+                offset: u32_at(&file, 4),
+                folder: u16_at(&file, 8),
+                modified: dos_datetime((u32::from(date) << 16) | u32::from(time)),
+            });
+        }
+
+        Ok(Self {
+            data_reserve,
+            prev,
+            next,
+            folders,
+            files,
+        })
+    }
+
+    /// 最後のフォルダが次のキャビネットに続いているかどうか
+    fn continues_to_next(&self) -> bool {
+        self.files.iter().any(|file| {
+            matches!(
+                file.folder,
+                FOLDER_CONTINUED_TO_NEXT | FOLDER_CONTINUED_PREV_AND_NEXT
+            )
+        })
+    }
+
+    /// 最初のフォルダが前のキャビネットから続いているかどうか
+    fn continued_from_prev(&self) -> bool {
+        self.files.iter().any(|file| {
+            matches!(
+                file.folder,
+                FOLDER_CONTINUED_FROM_PREV | FOLDER_CONTINUED_PREV_AND_NEXT
+            )
+        })
+    }
+}
+
+/// キャビネットファイルと、そのデータブロックの予約領域のサイズ
+struct Cabinet {
+    reader: BufReader<File>,
+    data_reserve: usize,
+}
+
+/// フォルダのデータブロックのうち、1つのキャビネットに格納されている部分
+struct FolderPart {
+    cabinet: usize,
+    data_offset: u64,
+    block_count: u16,
+}
+
+/// 圧縮の単位となるフォルダ（複数のキャビネットにまたがる場合は1つにまとめる）
+struct Folder {
+    compression: u16,
+    parts: Vec<FolderPart>,
+}
+
+/// キャビネット内のファイル
+struct CabEntry {
+    name: String,
+    size: u64,
+    offset: u64,
+    folder: usize,
+    modified: Option<NaiveDateTime>,
+}
+
+/// 分割されたキャビネットの組全体（分割されていない場合はキャビネット1つ）
+struct CabinetSet {
+    cabinets: Vec<Cabinet>,
+    folders: Vec<Folder>,
+    entries: Vec<CabEntry>,
+}
+
+impl CabinetSet {
+    fn open(file_path: &Path) -> Result<Self> {
+        let paths = match VolumeSet::find(file_path)? {
+            Some(volume_set) => volume_set.volumes().to_vec(),
+            None => vec![file_path.to_path_buf()],
+        };
+
+        let mut set = Self {
+            cabinets: Vec::with_capacity(paths.len()),
+            folders: Vec::new(),
+            entries: Vec::new(),
+        };
+        let mut prev_continues = false;
+        let mut last_next: Option<(PathBuf, String)> = None;
+        for (index, path) in paths.iter().enumerate() {
+            let mut reader = BufReader::new(File::open(path)?);
+            let header = CabinetHeader::read(&mut reader)?;
+            let merge = index > 0 && prev_continues && header.continued_from_prev();
+            set.add_cabinet(index, &header, merge)?;
+
+            prev_continues = header.continues_to_next();
+            last_next = header.next.map(|next| (path.clone(), next));
+            set.cabinets.push(Cabinet {
+                reader,
+                data_reserve: header.data_reserve,
+            });
+        }
+
+        // 最後に見つかったキャビネットの次のキャビネットが欠けている
+        if let Some((path, next)) = last_next {
+            let dir = path.parent().unwrap_or(Path::new(""));
+            return Err(missing_volume_error(&dir.join(next)));
+        }
+        Ok(set)
+    }
+
+    /// キャビネット1つ分のフォルダとファイルを追加する
+    ///
+    /// `merge` の場合、最初のフォルダは前のキャビネットの最後のフォルダの続き。
+    fn add_cabinet(&mut self, index: usize, header: &CabinetHeader, merge: bool) -> io::Result<()> {
+        let first_folder = if merge {
+            self.folders.len() - 1
+        } else {
+            self.folders.len()
+        };
+        for (i, folder) in header.folders.iter().enumerate() {
+            let part = FolderPart {
+                cabinet: index,
+                data_offset: folder.data_offset,
+                block_count: folder.block_count,
+            };
+            match self.folders.last_mut() {
+                Some(last) if merge && i == 0 => last.parts.push(part),
+                _ => self.folders.push(Folder {
+                    compression: folder.compression,
+                    parts: vec![part],
+                }),
+            }
+        }
+
+        for file in &header.files {
+            let from_prev = matches!(
+                file.folder,
+                FOLDER_CONTINUED_FROM_PREV | FOLDER_CONTINUED_PREV_AND_NEXT
+            );
+            // 前のキャビネットがない場合、先頭部分を持たないファイルは解凍できない
+            if from_prev && !merge {
+                debug!(
+                    "Skipped file continued from a missing cabinet: {}",
+                    file.name
+                );
+                continue;
+            }
+
+            let local = match file.folder {
+                FOLDER_CONTINUED_FROM_PREV | FOLDER_CONTINUED_PREV_AND_NEXT => 0,
+                FOLDER_CONTINUED_TO_NEXT => header.folders.len().saturating_sub(1),
+                folder => usize::from(folder),
+            };
+            if local >= header.folders.len() {
+                return Err(invalid_data("cab: invalid folder index"));
+            }
+            let folder = first_folder + local;
+            let offset = u64::from(file.offset);
+
+            // 前後のキャビネットにまたがるファイルは両方に記録されているため、1度だけ扱う
+            if from_prev
+                && self.entries.iter().any(|entry| {
+                    entry.folder == folder && entry.offset == offset && entry.name == file.name
+                })
+            {
+                continue;
+            }
+
+            self.entries.push(CabEntry {
+                name: file.name.clone(),
+                size: u64::from(file.size),
+                offset,
+                folder,
+                modified: file.modified,
+            });
+        }
+        Ok(())
+    }
+
+    /// フォルダごとに、フォルダ内の位置の順に並べたエントリの番号
+    fn entries_by_folder(&self) -> Vec<Vec<usize>> {
+        let mut by_folder = vec![Vec::new(); self.folders.len()];
+        for (i, entry) in self.entries.iter().enumerate() {
+            by_folder[entry.folder].push(i);
+        }
+        for indices in &mut by_folder {
+            indices.sort_by_key(|&i| self.entries[i].offset);
+        }
+        by_folder
+    }
+}
+
+/// データブロックの展開処理
+enum Decoder {
+    Stored,
+    /// Deflate のストリームをブロックごとに区切ったもの（辞書は前のブロックから引き継ぐ）
+    MsZip {
+        inflater: Decompress,
+        dictionary: Vec<u8>,
+    },
+    Lzx(Box<Lzxd>),
+}
+
+impl Decoder {
+    fn new(compression: u16) -> Result<Self> {
+        match compression & COMPRESSION_MASK {
+            COMPRESSION_NONE => Ok(Decoder::Stored),
+            COMPRESSION_MSZIP => Ok(Decoder::MsZip {
+                inflater: Decompress::new(false),
+                dictionary: Vec::with_capacity(DEFLATE_WINDOW_SIZE),
+            }),
+            COMPRESSION_QUANTUM => Err(anyhow!(t!("ui.error_cab_quantum_unsupported").to_string())),
+            COMPRESSION_LZX => {
+                // 上位バイトに辞書サイズ（2 の累乗の指数）が入っている
+                let window_size = match (compression >> 8) & 0x1f {
+                    15 => WindowSize::KB32,
+                    16 => WindowSize::KB64,
+                    17 => WindowSize::KB128,
+                    18 => WindowSize::KB256,
+                    19 => WindowSize::KB512,
+                    20 => WindowSize::MB1,
+                    21 => WindowSize::MB2,
+                    _ => return Err(invalid_data("cab: invalid LZX window size").into()),
+                };
+                Ok(Decoder::Lzx(Box::new(Lzxd::new(window_size))))
+            }
+            _ => Err(invalid_data("cab: unknown compression method").into()),
+        }
+    }
+
+    /// データブロック1つを展開する
+    fn decompress(&mut self, data: &[u8], size: usize) -> io::Result<Vec<u8>> {
+        let output = match self {
+            Decoder::Stored => data.to_vec(),
+            Decoder::MsZip {
+                inflater,
+                dictionary,
+            } => {
+                let data = data
+                    .strip_prefix(MSZIP_SIGNATURE)
+                    .ok_or_else(|| invalid_data("cab: bad MSZIP block signature"))?;
+
+                // 直前のブロックの末尾を非圧縮ブロックとして先に渡し、辞書として参照できるようにする
+                inflater.reset(false);
+                if !dictionary.is_empty() {
+                    let len = dictionary.len() as u16;
+                    let mut stored = vec![0];
+                    stored.extend_from_slice(&len.to_le_bytes());
+                    stored.extend_from_slice(&(!len).to_le_bytes());
+                    stored.extend_from_slice(dictionary);
+                    let mut discard = Vec::with_capacity(dictionary.len());
+                    inflater
+                        .decompress_vec(&stored, &mut discard, FlushDecompress::Sync)
+                        .map_err(io::Error::other)?;
+                }
+
+                let mut output = Vec::with_capacity(size);
+                inflater
+                    .decompress_vec(data, &mut output, FlushDecompress::Finish)
+                    .map_err(|_| invalid_data("cab: corrupt MSZIP data"))?;
+
+                let keep = dictionary
+                    .len()
+                    .min(DEFLATE_WINDOW_SIZE.saturating_sub(output.len()));
+                dictionary.drain(..dictionary.len() - keep);
+                dictionary
+                    .extend_from_slice(&output[output.len().saturating_sub(DEFLATE_WINDOW_SIZE)..]);
+                output
+            }
+            Decoder::Lzx(lzxd) => lzxd
+                .decompress_next(data, size)
+                .map_err(|_| invalid_data("cab: corrupt LZX data"))?
+                .to_vec(),
+        };
+
+        if output.len() != size {
+            return Err(invalid_data("cab: data block size mismatch"));
+        }
+        Ok(output)
+    }
+}
+
+/// フォルダを展開した内容を先頭から読むリーダー
+struct FolderReader<'a> {
+    cabinets: &'a mut [Cabinet],
+    folder: &'a Folder,
+    /// まだ読み始めていない部分
+    parts: &'a [FolderPart],
+    /// 読んでいる部分のキャビネットと、その残りのデータブロック数
+    cabinet: usize,
+    blocks_left: u16,
+    decoder: Decoder,
+    block: Vec<u8>,
+    block_pos: usize,
+    /// フォルダを展開した内容の中での現在位置
+    pos: u64,
+}
+
+impl<'a> FolderReader<'a> {
+    fn new(cabinets: &'a mut [Cabinet], folder: &'a Folder) -> Result<Self> {
+        Ok(Self {
+            cabinets,
+            folder,
+            parts: &folder.parts,
+            cabinet: 0,
+            blocks_left: 0,
+            decoder: Decoder::new(folder.compression)?,
+            block: Vec::new(),
+            block_pos: 0,
+            pos: 0,
+        })
+    }
+
+    /// フォルダの先頭から読み直す
+    fn rewind(&mut self) -> Result<()> {
+        self.parts = &self.folder.parts;
+        self.blocks_left = 0;
+        self.decoder = Decoder::new(self.folder.compression)?;
+        self.block.clear();
+        self.block_pos = 0;
+        self.pos = 0;
+        Ok(())
+    }
+
+    /// フォルダの `offset` の位置まで読み進める（現在位置より前なら先頭から読み直す）
+    fn skip_to(&mut self, offset: u64) -> Result<()> {
+        if offset < self.pos {
+            self.rewind()?;
+        }
+        let len = offset - self.pos;
+        if io::copy(&mut self.by_ref().take(len), &mut io::sink())? != len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(())
+    }
+
+    /// 次のデータブロックを展開する（フォルダの終わりなら `false`）
+    fn next_block(&mut self) -> io::Result<bool> {
+        let mut data = Vec::new();
+        loop {
+            while self.blocks_left == 0 {
+                let Some((part, rest)) = self.parts.split_first() else {
+                    if data.is_empty() {
+                        return Ok(false);
+                    }
+                    return Err(invalid_data(
+                        "cab: data block continues past the last cabinet",
+                    ));
+                };
+                self.cabinets[part.cabinet]
+                    .reader
+                    .seek(SeekFrom::Start(part.data_offset))?;
+                self.cabinet = part.cabinet;
+                self.blocks_left = part.block_count;
+                self.parts = rest;
+            }
+
+            let (block_data, size) = read_data_block(&mut self.cabinets[self.cabinet])?;
+            self.blocks_left -= 1;
+            data.extend_from_slice(&block_data);
+
+            // 展開後のサイズが 0 のブロックは、次のキャビネットの最初のブロックに続く
+            if size != 0 {
+                self.block = self.decoder.decompress(&data, size)?;
+                self.block_pos = 0;
+                return Ok(true);
+            }
+        }
+    }
+}
+
+impl Read for FolderReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.block_pos >= self.block.len() {
+            if buf.is_empty() || !self.next_block()? {
+                return Ok(0);
+            }
+        }
+        let len = buf.len().min(self.block.len() - self.block_pos);
+        buf[..len].copy_from_slice(&self.block[self.block_pos..self.block_pos + len]);
+        self.block_pos += len;
+        self.pos += len as u64;
+        Ok(len)
+    }
+}
+
+/// CFDATA を1つ読み、チェックサムを検証して (圧縮データ, 展開後のサイズ) を返す
+fn read_data_block(cabinet: &mut Cabinet) -> io::Result<(Vec<u8>, usize)> {
+    let mut header = [0u8; DATA_LEN];
+    cabinet.reader.read_exact(&mut header)?;
+    let checksum = u32_at(&header, 0);
+    let compressed_size = usize::from(u16_at(&header, 4));
+    let size = usize::from(u16_at(&header, 6));
+    if size > MAX_BLOCK_SIZE {
+        return Err(invalid_data("cab: data block too large"));
+    }
+
+    // チェックサムは予約領域と圧縮データ、続いてサイズのフィールドに対して計算する
+    let mut data = vec![0; cabinet.data_reserve + compressed_size];
+    cabinet.reader.read_exact(&mut data)?;
+    if checksum != 0 && block_checksum(&data) ^ u32_at(&header, 4) != checksum {
+        return Err(invalid_data("cab: data block checksum mismatch"));
+    }
+    data.drain(..cabinet.data_reserve);
+    Ok((data, size))
+}
+
+/// データブロックのチェックサム（4 バイトごとの XOR、端数は逆順に詰める）
+fn block_checksum(data: &[u8]) -> u32 {
+    let mut chunks = data.chunks_exact(4);
+    let checksum = chunks
+        .by_ref()
+        .fold(0, |checksum, chunk| checksum ^ u32_at(chunk, 0));
+    let rest = chunks
+        .remainder()
+        .iter()
+        .fold(0, |value, &byte| (value << 8) | u32::from(byte));
+    checksum ^ rest
+}
+
+/// NUL 終端の文字列を読む
+fn read_cstring<R: Read>(reader: &mut R) -> io::Result<Vec<u8>> {
+    let mut bytes = Vec::new();
+    let mut byte = [0u8];
+    loop {
+        reader.read_exact(&mut byte)?;
+        if byte[0] == 0 {
+            return Ok(bytes);
+        }
+        if bytes.len() >= MAX_NAME_LEN {
+            return Err(invalid_data("cab: name too long"));
+        }
+        bytes.push(byte[0]);
+    }
+}
+
+fn u16_at(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn u32_at(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct CabExtractor;
+
+impl Extractor for CabExtractor {
+    fn name(&self) -> &'static str {
+        "CAB"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["cab"]
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        header.starts_with(CAB_MAGIC)
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        let set = CabinetSet::open(file_path)?;
+
+        Ok(set
+            .entries
+            .iter()
+            .map(|entry| ArchiveEntry {
+                name: entry.name.clone(),
+                size: entry.size,
+                // フォルダ単位で圧縮されるため、ファイルごとの圧縮後のサイズは分からない
+                compressed_size: (set.folders[entry.folder].compression & COMPRESSION_MASK
+                    == COMPRESSION_NONE)
+                    .then_some(entry.size),
+                modified: entry.modified,
+                kind: EntryKind::File,
+            })
+            .collect())
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let mut set = CabinetSet::open(file_path)?;
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+
+        // プログレスバーの設定
+        let pb = new_progress_bar(
+            set.entries.len() as u64,
+            format!("{}", t!("progress.extracting_cab")),
+        );
+
+        let result = extract_cab_entries(&mut set, extract_dir, options, &tracker, &pb);
+        tracker.finish(result)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let mut set = CabinetSet::open(file_path)?;
+
+        // フォルダを先頭から展開し、各データブロックのチェックサムとサイズを検証する
+        let by_folder = set.entries_by_folder();
+        for (folder, indices) in set.folders.iter().zip(by_folder) {
+            let mut reader = FolderReader::new(&mut set.cabinets, folder)?;
+            for i in indices {
+                let entry = &set.entries[i];
+                let result = copy_entry(&mut reader, entry, &mut io::sink());
+                // 読めなかったエントリの後は、フォルダの先頭から読み直す
+                if result.is_err() {
+                    reader.rewind()?;
+                }
+                report(&entry.name, result);
+            }
+        }
+        Ok(())
+    }
+}
+
+/// エントリの内容を `output` に書き出す
+fn copy_entry<W: io::Write>(
+    reader: &mut FolderReader,
+    entry: &CabEntry,
+    output: &mut W,
+) -> Result<()> {
+    reader.skip_to(entry.offset)?;
+    if io::copy(&mut reader.by_ref().take(entry.size), output)? != entry.size {
+        return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+    }
+    Ok(())
+}
+
+// キャビネットのエントリの解凍処理
+fn extract_cab_entries(
+    set: &mut CabinetSet,
+    extract_dir: &Path,
+    options: &ExtractOptions,
+    tracker: &LimitTracker,
+    pb: &ProgressBar,
+) -> Result<()> {
+    // フォルダごとに、先頭から順に展開しながら書き出す
+    let by_folder = set.entries_by_folder();
+    for (folder, indices) in set.folders.iter().zip(by_folder) {
+        let mut reader = FolderReader::new(&mut set.cabinets, folder)?;
+        for i in indices {
+            tracker.start_entry()?;
+            let entry = &set.entries[i];
+
+            // 解凍先の外を指すエントリはスキップ
+            let Some(output_path) = entry_output_path(extract_dir, &entry.name, options) else {
+                pb.inc(1);
+                continue;
+            };
+
+            // プログレスバーのメッセージを更新
+            if let Some(file_name) = output_path.file_name().and_then(|s| s.to_str()) {
+                pb.set_message(format!(
+                    "{}",
+                    t!("progress.extracting_file", file = file_name)
+                ));
+            }
+
+            if let Some(parent) = output_path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            let mut output_file = File::create(&output_path)?;
+            reader.skip_to(entry.offset)?;
+            let copied = io::copy(
+                &mut tracker.reader(reader.by_ref().take(entry.size)),
+                &mut output_file,
+            )?;
+            if copied != entry.size {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            pb.inc(1);
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{read_output, Fixture};
+    use flate2::{Compress, Compression, FlushCompress};
+
+    /// (圧縮データ, 展開後のサイズ)
+    type Block = (Vec<u8>, usize);
+
+    /// フォルダの (圧縮方式, データブロック) とファイルの (名前, サイズ, 位置, フォルダ番号) から
+    /// キャビネットを組み立てる
+    fn cabinet(
+        prev: Option<&str>,
+        next: Option<&str>,
+        folders: &[(u16, Vec<Block>)],
+        files: &[(&str, usize, usize, u16)],
+    ) -> Vec<u8> {
+        let mut links = Vec::new();
+        let mut flags = 0;
+        if let Some(prev) = prev {
+            flags |= FLAG_PREV_CABINET;
+            links.extend_from_slice(format!("{prev}\0disk\0").as_bytes());
+        }
+        if let Some(next) = next {
+            flags |= FLAG_NEXT_CABINET;
+            links.extend_from_slice(format!("{next}\0disk\0").as_bytes());
+        }
+
+        let files_offset = HEADER_LEN + links.len() + FOLDER_LEN * folders.len();
+        let mut file_table = Vec::new();
+        for &(name, size, offset, folder) in files {
+            file_table.extend_from_slice(&(size as u32).to_le_bytes());
+            file_table.extend_from_slice(&(offset as u32).to_le_bytes());
+            file_table.extend_from_slice(&folder.to_le_bytes());
+            // 2024-01-02 03:04:06
+            file_table.extend_from_slice(&0x5822u16.to_le_bytes());
+            file_table.extend_from_slice(&0x1883u16.to_le_bytes());
+            file_table.extend_from_slice(&0u16.to_le_bytes());
+            file_table.extend_from_slice(name.as_bytes());
+            file_table.push(0);
+        }
+
+        let mut folder_table = Vec::new();
+        let mut data = Vec::new();
+        let data_start = files_offset + file_table.len();
+        for (compression, blocks) in folders {
+            folder_table.extend_from_slice(&((data_start + data.len()) as u32).to_le_bytes());
+            folder_table.extend_from_slice(&(blocks.len() as u16).to_le_bytes());
+            folder_table.extend_from_slice(&compression.to_le_bytes());
+            for (block, size) in blocks {
+                let mut sizes = [0u8; 4];
+                sizes[..2].copy_from_slice(&(block.len() as u16).to_le_bytes());
+                sizes[2..].copy_from_slice(&(*size as u16).to_le_bytes());
+                let checksum = block_checksum(block) ^ u32::from_le_bytes(sizes);
+                data.extend_from_slice(&checksum.to_le_bytes());
+                data.extend_from_slice(&sizes);
+                data.extend_from_slice(block);
+            }
+        }
+
+        let mut header = vec![0u8; HEADER_LEN];
+        header[..4].copy_from_slice(CAB_MAGIC);
+        let total = data_start + data.len();
+        header[8..12].copy_from_slice(&(total as u32).to_le_bytes());
+        header[16..20].copy_from_slice(&(files_offset as u32).to_le_bytes());
+        header[24] = 3;
+        header[25] = 1;
+        header[26..28].copy_from_slice(&(folders.len() as u16).to_le_bytes());
+        header[28..30].copy_from_slice(&(files.len() as u16).to_le_bytes());
+        header[30..32].copy_from_slice(&flags.to_le_bytes());
+        [header, links, folder_table, file_table, data].concat()
+    }
+
+    fn stored_blocks(data: &[u8]) -> Vec<Block> {
+        data.chunks(MAX_BLOCK_SIZE)
+            .map(|chunk| (chunk.to_vec(), chunk.len()))
+            .collect()
+    }
+
+    /// 1つの Deflate ストリームをブロックごとに区切り、MSZIP のデータブロックにする
+    ///
+    /// 後のブロックは前のブロックを辞書として参照するため、辞書の引き継ぎも検証できる。
+    fn mszip_blocks(data: &[u8]) -> Vec<Block> {
+        let mut deflater = Compress::new(Compression::default(), false);
+        data.chunks(MAX_BLOCK_SIZE)
+            .map(|chunk| {
+                let mut block = MSZIP_SIGNATURE.to_vec();
+                block.reserve(chunk.len() * 2 + 64);
+                let before = deflater.total_in();
+                deflater
+                    .compress_vec(chunk, &mut block, FlushCompress::Sync)
+                    .unwrap();
+                assert_eq!(deflater.total_in() - before, chunk.len() as u64);
+                // 各ブロックは最終ブロックの印が付いた空の非圧縮ブロックで終える
+                block.extend_from_slice(&[0x01, 0x00, 0x00, 0xff, 0xff]);
+                (block, chunk.len())
+            })
+            .collect()
+    }
+
+    fn sample(len: usize) -> Vec<u8> {
+        (0..len)
+            .map(|i| b"cabinet sample text "[i % 20].wrapping_add((i / 997 % 7) as u8))
+            .collect()
+    }
+
+    #[test]
+    fn extracts_stored_and_mszip_folders() {
+        let a = sample(50_000);
+        let b = sample(70_000);
+        let data = cabinet(
+            None,
+            None,
+            &[
+                (COMPRESSION_NONE, stored_blocks(&a)),
+                (
+                    COMPRESSION_MSZIP,
+                    mszip_blocks(&[b.as_slice(), b"tail"].concat()),
+                ),
+            ],
+            &[
+                ("dir\\a.txt", a.len(), 0, 0),
+                ("b.bin", b.len(), 0, 1),
+                ("c.txt", 4, b.len(), 1),
+            ],
+        );
+        let fixture = Fixture::new("test.cab", &data);
+
+        let entries = CabExtractor.list(&fixture.archive, None).unwrap();
+        let names: Vec<_> = entries.iter().map(|entry| entry.name.as_str()).collect();
+        assert_eq!(names, ["dir/a.txt", "b.bin", "c.txt"]);
+        assert_eq!(entries[0].compressed_size, Some(a.len() as u64));
+        assert_eq!(entries[1].compressed_size, None);
+        assert!(entries[0].modified.is_some());
+
+        fixture
+            .extract(&CabExtractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(read_output(&fixture.out, "dir/a.txt"), Some(a));
+        assert_eq!(read_output(&fixture.out, "b.bin"), Some(b));
+        assert_eq!(read_output(&fixture.out, "c.txt"), Some(b"tail".to_vec()));
+
+        let results = fixture.test(&CabExtractor).unwrap();
+        assert!(results.iter().all(|(_, ok)| *ok), "{results:?}");
+    }
+
+    #[test]
+    fn detects_checksum_mismatch() {
+        let mut data = cabinet(
+            None,
+            None,
+            &[(COMPRESSION_NONE, stored_blocks(b"hello, cabinet"))],
+            &[("hello.txt", 14, 0, 0)],
+        );
+        let last = data.len() - 1;
+        data[last] ^= 0x01;
+        let fixture = Fixture::new("test.cab", &data);
+
+        assert_eq!(
+            fixture.test(&CabExtractor).unwrap(),
+            vec![("hello.txt".to_string(), false)]
+        );
+        let error = fixture
+            .extract(&CabExtractor, &ExtractOptions::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("checksum"), "{error}");
+    }
+
+    #[test]
+    fn joins_split_cabinets() {
+        // 1つ目のキャビネットの最後のブロックは、次のキャビネットの最初のブロックに続く
+        let first = cabinet(
+            None,
+            Some("b.cab"),
+            &[(COMPRESSION_NONE, vec![(b"hello ".to_vec(), 0)])],
+            &[("greeting.txt", 12, 0, FOLDER_CONTINUED_TO_NEXT)],
+        );
+        let second = cabinet(
+            Some("a.cab"),
+            None,
+            &[
+                (COMPRESSION_NONE, vec![(b"world!".to_vec(), 12)]),
+                (COMPRESSION_NONE, stored_blocks(b"tail")),
+            ],
+            &[
+                ("greeting.txt", 12, 0, FOLDER_CONTINUED_FROM_PREV),
+                ("tail.txt", 4, 0, 1),
+            ],
+        );
+        let fixture = Fixture::new("a.cab", &first);
+        fs::write(fixture.root.join("b.cab"), &second).unwrap();
+
+        fixture
+            .extract(&CabExtractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(
+            read_output(&fixture.out, "greeting.txt"),
+            Some(b"hello world!".to_vec())
+        );
+        assert_eq!(
+            read_output(&fixture.out, "tail.txt"),
+            Some(b"tail".to_vec())
+        );
+
+        fs::remove_file(fixture.root.join("b.cab")).unwrap();
+        assert!(CabExtractor.list(&fixture.archive, None).is_err());
+    }
+
+    #[test]
+    fn rejects_corrupt_cabinets() {
+        let data = cabinet(
+            None,
+            None,
+            &[(COMPRESSION_NONE, stored_blocks(b"data"))],
+            &[("bad.txt", 4, 0, 3)],
+        );
+        let fixture = Fixture::new("test.cab", &data);
+        let error = CabExtractor.list(&fixture.archive, None).unwrap_err();
+        assert!(error.to_string().contains("folder index"), "{error}");
+
+        let data = cabinet(
+            None,
+            None,
+            &[(COMPRESSION_NONE, stored_blocks(b"data"))],
+            &[("short.txt", 4, 0, 0)],
+        );
+        let fixture = Fixture::new("test.cab", &data[..data.len() - 2]);
+        assert_eq!(
+            fixture.test(&CabExtractor).unwrap(),
+            vec![("short.txt".to_string(), false)]
+        );
+
+        let fixture = Fixture::new("test.cab", &data[..HEADER_LEN - 1]);
+        assert!(CabExtractor.list(&fixture.archive, None).is_err());
+    }
+
+    #[test]
+    fn rejects_corrupt_blocks() {
+        let mut decoder = Decoder::new(COMPRESSION_MSZIP).unwrap();
+        let error = decoder.decompress(b"XX\x03\x00", 0).unwrap_err();
+        assert!(error.to_string().contains("signature"), "{error}");
+
+        let mut decoder = Decoder::new(COMPRESSION_MSZIP).unwrap();
+        assert!(decoder.decompress(b"CK\xff\xff\xff\xff", 10).is_err());
+
+        let mut decoder = Decoder::new(COMPRESSION_NONE).unwrap();
+        let error = decoder.decompress(b"12345", 6).unwrap_err();
+        assert!(error.to_string().contains("size mismatch"), "{error}");
+
+        let mut decoder = Decoder::new(COMPRESSION_LZX | (15 << 8)).unwrap();
+        assert!(decoder.decompress(&[0xff; 16], 1024).is_err());
+    }
+
+    #[test]
+    fn rejects_unsupported_compression() {
+        assert!(Decoder::new(COMPRESSION_QUANTUM).is_err());
+        assert!(Decoder::new(COMPRESSION_LZX | (14 << 8)).is_err());
+        assert!(Decoder::new(COMPRESSION_LZX | (22 << 8)).is_err());
+        assert!(Decoder::new(COMPRESSION_LZX | (21 << 8)).is_ok());
+        assert!(Decoder::new(0x000f).is_err());
+    }
+}
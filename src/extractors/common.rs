@@ -1,5 +1,19 @@
+use anyhow::Result;
+use chrono::{DateTime, Local, NaiveDate, NaiveDateTime};
 use encoding_rs::SHIFT_JIS;
-use std::path::PathBuf;
+use indicatif::{ProgressBar, ProgressStyle};
+use log::{debug, warn};
+use std::fs::File;
+use std::io::{BufReader, Read};
+use std::path::{Path, PathBuf};
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use super::extractor::{
+    ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport, MAGIC_HEADER_LEN,
+};
+use super::limits::LimitTracker;
 
 /// ファイル名のデコード（日本語対応）
 /// 生のバイト配列からファイル名を適切にデコードする
@@ -25,7 +39,413 @@ pub fn decode_filename(raw_bytes: &[u8]) -> String {
     }
 }
 
-/// ファイル名をデコードしてPathBufとして返す
-pub fn decode_filename_as_pathbuf(raw_bytes: &[u8]) -> PathBuf {
-    PathBuf::from(decode_filename(raw_bytes))
+/// `sanitize_entry_path` の結果
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SanitizedPath {
+    /// そのまま使える相対パス
+    Clean(PathBuf),
+    /// 絶対パス・ドライブレター・UNCプレフィックスを取り除いた（Windows では `:` も置き換えた）相対パス
+    Rewritten(PathBuf),
+    /// `..` を含むなど、解凍先の外を指すため拒否したエントリ
+    Rejected,
+}
+
+/// アーカイブ内のエントリ名を、解凍先ディレクトリからの安全な相対パスに変換する（Zip Slip 対策）
+///
+/// Windows で作られたアーカイブを考慮し、`/` と `\` のどちらも区切り文字として扱う。
+pub fn sanitize_entry_path(name: &str) -> SanitizedPath {
+    let mut components = name.split(['/', '\\']);
+    let mut rewritten = false;
+
+    // \\server\share\... や \\?\C:\... のようなUNCプレフィックス
+    // （先頭の空要素2つとサーバー名・共有名の4要素を読み飛ばす）
+    if name.starts_with("\\\\") || name.starts_with("//") {
+        components.nth(3);
+        rewritten = true;
+    }
+
+    let mut path = PathBuf::new();
+    for (i, component) in components.enumerate() {
+        // 先頭のドライブレター（"C:" や "C:foo" のようなドライブ相対パス）
+        let component = match component.as_bytes() {
+            [drive, b':', ..] if i == 0 && drive.is_ascii_alphabetic() => {
+                rewritten = true;
+                &component[2..]
+            }
+            _ => component,
+        };
+
+        match component {
+            // 先頭の区切り文字（絶対パス）や連続した区切り文字
+            "" => rewritten |= i == 0,
+            "." => {}
+            ".." => return SanitizedPath::Rejected,
+            // NTFS の代替データストリームとして解釈されないよう、Windows では ':' を置き換える
+            // （Unix や macOS では `12:30 notes.txt` のような普通のファイル名）
+            _ if cfg!(windows) && component.contains(':') => {
+                rewritten = true;
+                path.push(component.replace(':', "_"));
+            }
+            _ => path.push(component),
+        }
+    }
+
+    if path.as_os_str().is_empty() {
+        SanitizedPath::Rejected
+    } else if rewritten {
+        SanitizedPath::Rewritten(path)
+    } else {
+        SanitizedPath::Clean(path)
+    }
+}
+
+/// エントリの書き出し先パスを決定する
+///
+/// 書き換えたエントリ・拒否したエントリはそれぞれ警告を出し、
+/// 拒否した場合は `None` を返す（呼び出し側はそのエントリをスキップする）。
+///
+/// 解凍済みのシンボリックリンクを経由して書き込むことになるエントリも拒否する。
+/// `options.keep_junk` が `false` の場合、OS が作成する不要なファイルも黙ってスキップする。
+pub fn entry_output_path(
+    extract_dir: &Path,
+    name: &str,
+    options: &ExtractOptions,
+) -> Option<PathBuf> {
+    if !options.keep_junk && is_junk_entry(name) {
+        debug!("Skipped junk entry: {}", name);
+        return None;
+    }
+
+    let output_path = match sanitize_entry_path(name) {
+        SanitizedPath::Clean(path) => extract_dir.join(path),
+        SanitizedPath::Rewritten(path) => {
+            warn!(
+                "{}",
+                t!(
+                    "ui.warning_entry_rewritten",
+                    entry = name,
+                    path = path.display()
+                )
+            );
+            extract_dir.join(path)
+        }
+        SanitizedPath::Rejected => {
+            warn!("{}", t!("ui.warning_entry_rejected", entry = name));
+            return None;
+        }
+    };
+
+    if passes_through_symlink(extract_dir, &output_path) {
+        warn!("{}", t!("ui.warning_entry_through_link", entry = name));
+        return None;
+    }
+    Some(output_path)
+}
+
+/// macOS や Windows が自動的に作成する、アーカイブに含める必要のないエントリかどうか
+///
+/// - `__MACOSX/` 以下（macOS の Finder が ZIP に追加するリソースフォーク）
+/// - `._*`（AppleDouble 形式のメタデータ）
+/// - `.DS_Store`（Finder の表示設定）
+/// - `Thumbs.db`（Windows エクスプローラーのサムネイルキャッシュ）
+pub fn is_junk_entry(name: &str) -> bool {
+    let mut components = name.split(['/', '\\']).filter(|part| !part.is_empty());
+    let Some(file_name) = components.clone().next_back() else {
+        return false;
+    };
+
+    components.any(|part| part == "__MACOSX")
+        || (file_name.starts_with("._") && file_name.len() > 2)
+        || file_name == ".DS_Store"
+        || file_name.eq_ignore_ascii_case("Thumbs.db")
+}
+
+/// `extract_dir` から `path` までの途中（`path` 自身を含む）にシンボリックリンクがあるかどうか
+pub fn passes_through_symlink(extract_dir: &Path, path: &Path) -> bool {
+    let Ok(relative) = path.strip_prefix(extract_dir) else {
+        return true;
+    };
+
+    let mut current = extract_dir.to_path_buf();
+    relative.components().any(|component| {
+        current.push(component);
+        current
+            .symlink_metadata()
+            .map(|m| m.file_type().is_symlink())
+            .unwrap_or(false)
+    })
+}
+
+/// 形式判定用にファイル先頭の最大 `MAGIC_HEADER_LEN` バイトを読み込む
+pub fn read_magic_header(file_path: &Path) -> std::io::Result<Vec<u8>> {
+    read_header_from(File::open(file_path)?)
+}
+
+/// リーダーの先頭から最大 `MAGIC_HEADER_LEN` バイトを読み込む（圧縮TARの中身の判定用）
+pub fn read_header_from<R: Read>(reader: R) -> std::io::Result<Vec<u8>> {
+    let mut header = Vec::with_capacity(MAGIC_HEADER_LEN);
+    reader
+        .take(MAGIC_HEADER_LEN as u64)
+        .read_to_end(&mut header)?;
+    Ok(header)
+}
+
+/// スピナー形式のプログレスバーを作成する（事前にエントリ数が分からない形式用）
+pub fn new_spinner(message: String) -> ProgressBar {
+    let pb = ProgressBar::new_spinner();
+    pb.set_style(
+        ProgressStyle::default_spinner()
+            .template("{spinner:.green} {elapsed_precise} {msg}")
+            .unwrap(),
+    );
+    pb.set_message(message);
+    pb
+}
+
+/// バー形式のプログレスバーを作成する（エントリ数が分かる形式用）
+pub fn new_progress_bar(len: u64, message: String) -> ProgressBar {
+    let pb = ProgressBar::new(len);
+    pb.set_style(
+        ProgressStyle::default_bar()
+            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
+            .unwrap()
+            .progress_chars("#>-"),
+    );
+    pb.set_message(message);
+    pb
+}
+
+/// 単一ファイル圧縮（.gz, .xz, .bz2 など）の展開後のファイル名
+/// 元のファイル名から最後の拡張子を取り除いたもの
+pub fn single_file_output_name(file_path: &Path) -> String {
+    file_path
+        .file_stem()
+        .and_then(|s| s.to_str())
+        .unwrap_or("extracted")
+        .to_string()
+}
+
+/// 単一ファイル圧縮のデコーダーの出力を `extract_dir` に書き出す
+pub fn decompress_single_file<R: Read>(
+    decoder: R,
+    file_path: &Path,
+    extract_dir: &Path,
+    options: &ExtractOptions,
+) -> Result<()> {
+    let tracker = LimitTracker::new(options.limits, file_path)?;
+    let output_path = extract_dir.join(single_file_output_name(file_path));
+
+    let result = (|| {
+        tracker.start_entry()?;
+        let mut output_file = File::create(output_path)?;
+        std::io::copy(&mut tracker.reader(decoder), &mut output_file)?;
+        Ok(())
+    })();
+    tracker.finish(result)
+}
+
+/// 単一ファイル圧縮の一覧（展開後のサイズは実際にデコードして求める）
+pub fn list_single_file<R: Read>(mut decoder: R, file_path: &Path) -> Result<Vec<ArchiveEntry>> {
+    let size = std::io::copy(&mut decoder, &mut std::io::sink())?;
+    let metadata = file_path.metadata()?;
+
+    Ok(vec![ArchiveEntry {
+        name: single_file_output_name(file_path),
+        size,
+        compressed_size: Some(metadata.len()),
+        modified: metadata
+            .modified()
+            .ok()
+            .map(|time| DateTime::<Local>::from(time).naive_local()),
+        kind: EntryKind::File,
+    }])
+}
+
+/// 単一ファイル圧縮の検査（ストリーム全体を1つのエントリとして報告する）
+pub fn test_single_file<R: Read>(mut decoder: R, file_path: &Path, report: &mut TestReport) {
+    let result = std::io::copy(&mut decoder, &mut std::io::sink());
+    report(
+        &single_file_output_name(file_path),
+        result.map(|_| ()).map_err(Into::into),
+    );
+}
+
+/// 単一ファイル圧縮のファイルから、展開後のデータを読むリーダーを作成する関数
+pub type DecoderFactory = fn(BufReader<File>) -> Result<Box<dyn Read>>;
+
+/// 1つのファイルを圧縮しただけの形式の Extractor
+///
+/// 形式ごとに異なるのは名前・拡張子・判定方法・デコーダーだけなので、
+/// それらを受け取って一覧・解凍・検査を共通の処理で行う。
+/// チェックサムを持つ形式は、デコーダーが末尾まで読んだ時点で検証する。
+pub struct SingleFileExtractor {
+    pub name: &'static str,
+    pub extensions: &'static [&'static str],
+    /// ファイル先頭のヘッダが、この形式かどうか
+    pub detect: fn(&[u8]) -> bool,
+    pub decoder: DecoderFactory,
+    /// 解凍中のプログレスバーに表示するメッセージのキー
+    pub progress_key: &'static str,
+}
+
+impl SingleFileExtractor {
+    fn open(&self, file_path: &Path) -> Result<Box<dyn Read>> {
+        let file = File::open(file_path)?;
+        (self.decoder)(BufReader::new(file))
+    }
+}
+
+impl Extractor for SingleFileExtractor {
+    fn name(&self) -> &'static str {
+        self.name
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        self.extensions
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        (self.detect)(header)
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        list_single_file(self.open(file_path)?, file_path)
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let decoder = self.open(file_path)?;
+
+        // プログレスバーの設定
+        let _pb = new_spinner(format!("{}", t!(self.progress_key)));
+
+        decompress_single_file(decoder, file_path, extract_dir, options)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        test_single_file(self.open(file_path)?, file_path, report);
+        Ok(())
+    }
+}
+
+/// MS-DOS 形式の日時（上位16ビットが日付、下位16ビットが時刻）を変換する
+pub fn dos_datetime(value: u32) -> Option<NaiveDateTime> {
+    let (date, time) = (value >> 16, value & 0xffff);
+    NaiveDate::from_ymd_opt(1980 + (date >> 9) as i32, (date >> 5) & 0x0f, date & 0x1f)?
+        .and_hms_opt(time >> 11, (time >> 5) & 0x3f, (time & 0x1f) * 2)
+}
+
+/// Unix 時刻（秒）をローカル時刻に変換する
+pub fn unix_datetime(seconds: i64) -> Option<NaiveDateTime> {
+    DateTime::from_timestamp(seconds, 0).map(|time| time.with_timezone(&Local).naive_local())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn rewritten(path: &str) -> SanitizedPath {
+        SanitizedPath::Rewritten(PathBuf::from(path))
+    }
+
+    #[test]
+    fn sanitize_keeps_relative_paths() {
+        assert_eq!(
+            sanitize_entry_path("a/./b//c.txt"),
+            SanitizedPath::Clean(PathBuf::from("a/b/c.txt"))
+        );
+        assert_eq!(
+            sanitize_entry_path("dir\\file.txt"),
+            SanitizedPath::Clean(PathBuf::from("dir/file.txt"))
+        );
+    }
+
+    #[test]
+    fn sanitize_rejects_parent_components() {
+        assert_eq!(sanitize_entry_path("../x"), SanitizedPath::Rejected);
+        assert_eq!(sanitize_entry_path("a/../../x"), SanitizedPath::Rejected);
+        assert_eq!(sanitize_entry_path("a/.."), SanitizedPath::Rejected);
+        assert_eq!(sanitize_entry_path("..\\..\\x"), SanitizedPath::Rejected);
+        assert_eq!(
+            sanitize_entry_path("/../etc/passwd"),
+            SanitizedPath::Rejected
+        );
+    }
+
+    #[test]
+    fn sanitize_rejects_empty_names() {
+        assert_eq!(sanitize_entry_path(""), SanitizedPath::Rejected);
+        assert_eq!(sanitize_entry_path("."), SanitizedPath::Rejected);
+        assert_eq!(sanitize_entry_path("/"), SanitizedPath::Rejected);
+        assert_eq!(sanitize_entry_path("C:"), SanitizedPath::Rejected);
+    }
+
+    #[test]
+    fn sanitize_strips_absolute_paths() {
+        assert_eq!(sanitize_entry_path("/etc/passwd"), rewritten("etc/passwd"));
+        assert_eq!(
+            sanitize_entry_path("\\Windows\\win.ini"),
+            rewritten("Windows/win.ini")
+        );
+    }
+
+    #[test]
+    fn sanitize_strips_drive_letters() {
+        assert_eq!(sanitize_entry_path("C:foo"), rewritten("foo"));
+        assert_eq!(sanitize_entry_path("C:\\x\\y"), rewritten("x/y"));
+        assert_eq!(sanitize_entry_path("c:/x/y"), rewritten("x/y"));
+    }
+
+    #[test]
+    fn sanitize_strips_unc_prefixes() {
+        assert_eq!(
+            sanitize_entry_path("\\\\server\\share\\dir\\f"),
+            rewritten("dir/f")
+        );
+        assert_eq!(sanitize_entry_path("//server/share/f"), rewritten("f"));
+        assert_eq!(sanitize_entry_path("\\\\?\\C:\\dir\\f"), rewritten("dir/f"));
+        assert_eq!(
+            sanitize_entry_path("\\\\?\\C:\\..\\f"),
+            SanitizedPath::Rejected
+        );
+        assert_eq!(
+            sanitize_entry_path("\\\\server\\share"),
+            SanitizedPath::Rejected
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn sanitize_keeps_colons_outside_windows() {
+        assert_eq!(
+            sanitize_entry_path("12:30 notes.txt"),
+            SanitizedPath::Clean(PathBuf::from("12:30 notes.txt"))
+        );
+        assert_eq!(
+            sanitize_entry_path("logs/foo:bar.log"),
+            SanitizedPath::Clean(PathBuf::from("logs/foo:bar.log"))
+        );
+    }
+
+    #[cfg(windows)]
+    #[test]
+    fn sanitize_rewrites_colons_on_windows() {
+        assert_eq!(
+            sanitize_entry_path("12:30 notes.txt"),
+            rewritten("12_30 notes.txt")
+        );
+        assert_eq!(
+            sanitize_entry_path("file.txt:stream"),
+            rewritten("file.txt_stream")
+        );
+    }
 }
@@ -0,0 +1,376 @@
+use std::io::{self, Read};
+
+use super::common::SingleFileExtractor;
+
+/// compress (.Z) のマジックナンバー
+pub const COMPRESS_MAGIC: &[u8] = &[0x1f, 0x9d];
+
+/// 符号の最小ビット数
+const INIT_BITS: u32 = 9;
+/// ブロックモードで辞書を初期化する符号
+const CLEAR: usize = 256;
+
+/// compress (.Z) 形式の LZW ストリームを展開するリーダー
+///
+/// ncompress と同じく、符号のビット数が変わるときや辞書を初期化するときは
+/// 現在のビット数 × 8 ビット単位の区切りまで入力を読み飛ばす。
+pub struct LzwDecoder<R: Read> {
+    reader: R,
+    /// 読み込み済みで未使用のビット（下位から順に使う）
+    bit_buffer: u64,
+    bit_count: u32,
+    /// 直前の区切りから読んだビット数
+    group_bits: u64,
+    block_mode: bool,
+    max_bits: u32,
+    n_bits: u32,
+    max_code: usize,
+    free_ent: usize,
+    prefix: Vec<u16>,
+    suffix: Vec<u8>,
+    old_code: Option<usize>,
+    fin_char: u8,
+    /// 展開済みで未出力のデータ
+    pending: Vec<u8>,
+    pending_pos: usize,
+}
+
+impl<R: Read> LzwDecoder<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let mut header = [0; 3];
+        reader.read_exact(&mut header)?;
+        if !header.starts_with(COMPRESS_MAGIC) {
+            return Err(invalid_data("compress: bad magic number"));
+        }
+        let max_bits = u32::from(header[2] & 0x1f);
+        if !(INIT_BITS..=16).contains(&max_bits) {
+            return Err(invalid_data("compress: unsupported number of bits"));
+        }
+        let block_mode = header[2] & 0x80 != 0;
+
+        Ok(Self::raw(reader, max_bits, block_mode))
+    }
+
+    /// .Z のヘッダがない LZW ストリームを展開するリーダーを作る
+    ///
+    /// ARC の crunch / squash 形式など、符号のビット数の上限を別に知っている場合に使う。
+    pub fn raw(reader: R, max_bits: u32, block_mode: bool) -> Self {
+        let table_size = 1 << max_bits;
+        let mut suffix = vec![0; table_size];
+        for (code, byte) in suffix.iter_mut().enumerate().take(256) {
+            *byte = code as u8;
+        }
+
+        Self {
+            reader,
+            bit_buffer: 0,
+            bit_count: 0,
+            group_bits: 0,
+            block_mode,
+            max_bits,
+            n_bits: INIT_BITS,
+            max_code: (1 << INIT_BITS) - 1,
+            free_ent: if block_mode { CLEAR + 1 } else { CLEAR },
+            prefix: vec![0; table_size],
+            suffix,
+            old_code: None,
+            fin_char: 0,
+            pending: Vec::new(),
+            pending_pos: 0,
+        }
+    }
+
+    /// 次の符号を読む（入力の終わりに達した場合は `None`）
+    fn read_code(&mut self) -> io::Result<Option<usize>> {
+        while self.bit_count < self.n_bits {
+            let mut byte = [0];
+            if self.reader.read(&mut byte)? == 0 {
+                return Ok(None);
+            }
+            self.bit_buffer |= u64::from(byte[0]) << self.bit_count;
+            self.bit_count += 8;
+        }
+
+        let code = (self.bit_buffer & ((1 << self.n_bits) - 1)) as usize;
+        self.bit_buffer >>= self.n_bits;
+        self.bit_count -= self.n_bits;
+        self.group_bits += u64::from(self.n_bits);
+        Ok(Some(code))
+    }
+
+    /// 現在のビット数 × 8 ビット単位の区切りまで入力を読み飛ばす
+    fn skip_to_group_end(&mut self) -> io::Result<()> {
+        let group = u64::from(self.n_bits) * 8;
+        let mut skip = (group - self.group_bits % group) % group;
+        self.group_bits = 0;
+
+        let buffered = skip.min(u64::from(self.bit_count)) as u32;
+        self.bit_buffer = self.bit_buffer.checked_shr(buffered).unwrap_or(0);
+        self.bit_count -= buffered;
+        skip -= u64::from(buffered);
+
+        // 区切りはバイト境界にあるため、残りはバイト単位で読み飛ばせる
+        io::copy(&mut (&mut self.reader).take(skip / 8), &mut io::sink())?;
+        Ok(())
+    }
+
+    /// 符号を1つ展開して `pending` に追加する（入力の終わりに達した場合は `false`）
+    fn decode_next(&mut self) -> io::Result<bool> {
+        if self.free_ent > self.max_code {
+            self.skip_to_group_end()?;
+            self.n_bits += 1;
+            self.max_code = if self.n_bits == self.max_bits {
+                1 << self.max_bits
+            } else {
+                (1 << self.n_bits) - 1
+            };
+        }
+
+        let Some(code) = self.read_code()? else {
+            return Ok(false);
+        };
+        let Some(old_code) = self.old_code else {
+            if code >= 256 {
+                return Err(invalid_data("compress: corrupt input"));
+            }
+            self.fin_char = code as u8;
+            self.pending.push(self.fin_char);
+            self.old_code = Some(code);
+            return Ok(true);
+        };
+
+        if code == CLEAR && self.block_mode {
+            // 直後の符号で CLEAR の位置に不要な項目が追加されるため、1つ手前から数える
+            self.free_ent = CLEAR;
+            self.skip_to_group_end()?;
+            self.n_bits = INIT_BITS;
+            self.max_code = (1 << INIT_BITS) - 1;
+            return Ok(true);
+        }
+
+        // 辞書をたどると逆順に得られるため、追加してから反転する
+        let mut current = code;
+        if current >= self.free_ent {
+            if current > self.free_ent {
+                return Err(invalid_data("compress: corrupt input"));
+            }
+            // KwKwK: まだ辞書にない、直前の文字列 + その先頭文字
+            self.pending.push(self.fin_char);
+            current = old_code;
+        }
+        while current >= 256 {
+            self.pending.push(self.suffix[current]);
+            current = usize::from(self.prefix[current]);
+        }
+        self.fin_char = current as u8;
+        self.pending.push(self.fin_char);
+        self.pending.reverse();
+
+        if self.free_ent < 1 << self.max_bits {
+            self.prefix[self.free_ent] = old_code as u16;
+            self.suffix[self.free_ent] = self.fin_char;
+            self.free_ent += 1;
+        }
+        self.old_code = Some(code);
+        Ok(true)
+    }
+}
+
+impl<R: Read> Read for LzwDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.pending_pos >= self.pending.len() {
+            self.pending.clear();
+            self.pending_pos = 0;
+            if !self.decode_next()? {
+                return Ok(0);
+            }
+        }
+
+        let len = buf.len().min(self.pending.len() - self.pending_pos);
+        buf[..len].copy_from_slice(&self.pending[self.pending_pos..self.pending_pos + len]);
+        self.pending_pos += len;
+        Ok(len)
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// Unix の compress 形式（.Z）
+///
+/// compress にはチェックサムがないため、検査では最後までデコードできるかだけを確認する。
+pub const COMPRESS_EXTRACTOR: SingleFileExtractor = SingleFileExtractor {
+    name: "Z",
+    // 拡張子は小文字にして比較される（.Z）
+    extensions: &["z"],
+    detect: |header| header.starts_with(COMPRESS_MAGIC),
+    decoder: |reader| Ok(Box::new(LzwDecoder::new(reader)?)),
+    progress_key: "progress.extracting_z",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{read_output, Fixture};
+    use crate::extractors::ExtractOptions;
+    use std::collections::HashMap;
+
+    /// ncompress と同じ出力をする LZW のエンコーダー（辞書が一杯になったら初期化する）
+    struct LzwEncoder {
+        output: Vec<u8>,
+        /// 現在の区切りの先頭の位置と、区切りの中で書いたビット数
+        group_start: usize,
+        group_bits: usize,
+        n_bits: u32,
+        max_bits: u32,
+        max_code: usize,
+        free_ent: usize,
+    }
+
+    impl LzwEncoder {
+        fn put(&mut self, code: usize, clear: bool) {
+            for bit in 0..self.n_bits {
+                let pos = self.group_start * 8 + self.group_bits;
+                if pos / 8 == self.output.len() {
+                    self.output.push(0);
+                }
+                self.output[pos / 8] |= (((code >> bit) & 1) as u8) << (pos % 8);
+                self.group_bits += 1;
+            }
+            if self.group_bits == self.n_bits as usize * 8 {
+                self.group_start = self.output.len();
+                self.group_bits = 0;
+            }
+
+            if self.free_ent > self.max_code || clear {
+                // 区切りの残りを埋めてからビット数を変える
+                if self.group_bits > 0 {
+                    self.output
+                        .resize(self.group_start + self.n_bits as usize, 0);
+                    self.group_start = self.output.len();
+                    self.group_bits = 0;
+                }
+                if clear {
+                    self.n_bits = INIT_BITS;
+                    self.max_code = (1 << INIT_BITS) - 1;
+                } else {
+                    self.n_bits += 1;
+                    self.max_code = if self.n_bits == self.max_bits {
+                        1 << self.max_bits
+                    } else {
+                        (1 << self.n_bits) - 1
+                    };
+                }
+            }
+        }
+    }
+
+    fn compress(data: &[u8], max_bits: u32) -> Vec<u8> {
+        let mut encoder = LzwEncoder {
+            output: vec![0x1f, 0x9d, 0x80 | max_bits as u8],
+            group_start: 3,
+            group_bits: 0,
+            n_bits: INIT_BITS,
+            max_bits,
+            max_code: (1 << INIT_BITS) - 1,
+            free_ent: CLEAR + 1,
+        };
+        let mut dictionary = HashMap::new();
+        let Some((&first, rest)) = data.split_first() else {
+            return encoder.output;
+        };
+
+        let mut ent = usize::from(first);
+        for &byte in rest {
+            if let Some(&code) = dictionary.get(&(ent, byte)) {
+                ent = code;
+                continue;
+            }
+            encoder.put(ent, false);
+            if encoder.free_ent < 1 << max_bits {
+                dictionary.insert((ent, byte), encoder.free_ent);
+                encoder.free_ent += 1;
+            } else {
+                dictionary.clear();
+                encoder.free_ent = CLEAR + 1;
+                encoder.put(CLEAR, true);
+            }
+            ent = usize::from(byte);
+        }
+        encoder.put(ent, false);
+        encoder.output
+    }
+
+    /// 辞書の初期化が何度も起きるよう、繰り返しの少ないデータを作る
+    fn sample(len: usize) -> Vec<u8> {
+        let mut state = 0x2545_f491_u32;
+        (0..len)
+            .map(|i| {
+                state ^= state << 13;
+                state ^= state >> 17;
+                state ^= state << 5;
+                if i % 3 == 0 {
+                    b'a' + (state % 26) as u8
+                } else {
+                    (state >> 24) as u8 & 0x0f
+                }
+            })
+            .collect()
+    }
+
+    fn decode(data: &[u8]) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        LzwDecoder::new(data)?.read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    #[test]
+    fn round_trips_every_code_width() {
+        for max_bits in [9, 12, 16] {
+            for data in [
+                Vec::new(),
+                b"a".to_vec(),
+                b"TOBEORNOTTOBEORTOBEORNOT".to_vec(),
+                sample(300_000),
+            ] {
+                assert_eq!(
+                    decode(&compress(&data, max_bits)).unwrap(),
+                    data,
+                    "{max_bits} bits"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_bad_headers() {
+        assert!(decode(b"\x1f\x8b\x90").is_err());
+        assert!(decode(b"\x1f\x9d\x88").is_err());
+        assert!(decode(b"\x1f\x9d\x91").is_err());
+        assert!(decode(b"\x1f\x9d").is_err());
+    }
+
+    #[test]
+    fn rejects_codes_not_in_dictionary() {
+        // 最初の符号の直後に、まだ登録されていない符号（300）が現れる
+        let mut data = b"\x1f\x9d\x90".to_vec();
+        let codes = u32::from(b'a') | (300 << 9);
+        data.extend_from_slice(&codes.to_le_bytes()[..3]);
+        assert!(decode(&data).is_err());
+    }
+
+    #[test]
+    fn extracts_and_tests_files() {
+        let data = sample(50_000);
+        let fixture = Fixture::new("sample.bin.Z", &compress(&data, 16));
+        fixture
+            .extract(&COMPRESS_EXTRACTOR, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(read_output(&fixture.out, "sample.bin").unwrap(), data);
+        assert_eq!(
+            fixture.test(&COMPRESS_EXTRACTOR).unwrap(),
+            vec![("sample.bin".to_string(), true)]
+        );
+    }
+}
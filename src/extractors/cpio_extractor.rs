@@ -0,0 +1,810 @@
+use anyhow::Result;
+use flate2::bufread::GzDecoder;
+use indicatif::ProgressBar;
+use log::debug;
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use xz2::bufread::XzDecoder;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+// Import common decode function
+use super::common::{
+    decode_filename, entry_output_path, new_spinner, read_header_from, unix_datetime,
+};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::gzip_extractor::GZIP_MAGIC;
+use super::limits::LimitTracker;
+use super::links::{DeferredLinks, LinkKind};
+use super::xz_extractor::XZ_MAGIC;
+use super::zstd_extractor::{zstd_decoder, ZSTD_MAGIC};
+
+/// SVR4 形式（newc）のマジックナンバー（070702 は CRC つき）
+const NEWC_MAGIC: &[u8] = b"070701";
+const NEWC_CRC_MAGIC: &[u8] = b"070702";
+/// POSIX.1 のポータブル形式（odc）のマジックナンバー
+const ODC_MAGIC: &[u8] = b"070707";
+/// 古いバイナリ形式のマジックナンバー（書き込んだ環境のバイトオーダーで格納される）
+const BINARY_MAGIC: u16 = 0o070707;
+
+/// 各形式のヘッダの長さ
+const NEWC_HEADER_LEN: usize = 110;
+const ODC_HEADER_LEN: usize = 76;
+const BINARY_HEADER_LEN: usize = 26;
+
+/// アーカイブの終端を表すエントリ名
+const TRAILER_NAME: &[u8] = b"TRAILER!!!";
+
+/// ファイル名の最大の長さ（ヘッダの値のままメモリを確保しないための上限）
+const MAX_NAME_LEN: u64 = 4096;
+
+/// シンボリックリンクのリンク先として読み込む最大の長さ
+const MAX_LINK_TARGET_LEN: u64 = 64 * 1024;
+
+/// Unix のファイルモードの種別部分と、ディレクトリ・通常のファイル・シンボリックリンクの値
+const S_IFMT: u32 = 0o170000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// ファイルの先頭が cpio のヘッダかどうか
+pub fn is_cpio_header(header: &[u8]) -> bool {
+    [NEWC_MAGIC, NEWC_CRC_MAGIC, ODC_MAGIC]
+        .iter()
+        .any(|magic| header.starts_with(magic))
+        || header.starts_with(&BINARY_MAGIC.to_le_bytes())
+        || header.starts_with(&BINARY_MAGIC.to_be_bytes())
+}
+
+/// cpio アーカイブの外側の圧縮形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CpioCompression {
+    None,
+    Gzip,
+    Xz,
+    Zstd,
+}
+
+impl CpioCompression {
+    /// 先頭のマジックナンバーから圧縮形式を判定する（initramfs の途中から圧縮が始まる場合など）
+    fn from_magic(header: &[u8]) -> Option<Self> {
+        [
+            (GZIP_MAGIC, CpioCompression::Gzip),
+            (XZ_MAGIC, CpioCompression::Xz),
+            (ZSTD_MAGIC, CpioCompression::Zstd),
+        ]
+        .into_iter()
+        .find(|(magic, _)| header.starts_with(magic))
+        .map(|(_, compression)| compression)
+    }
+
+    fn decoder<'a, R: BufRead + 'a>(self, reader: R) -> Result<Box<dyn BufRead + 'a>> {
+        let decoder: Box<dyn BufRead + 'a> = match self {
+            CpioCompression::None => Box::new(reader),
+            CpioCompression::Gzip => Box::new(BufReader::new(GzDecoder::new(reader))),
+            CpioCompression::Xz => Box::new(BufReader::new(XzDecoder::new(reader))),
+            CpioCompression::Zstd => Box::new(BufReader::new(zstd_decoder(reader)?)),
+        };
+        Ok(decoder)
+    }
+}
+
+/// cpio のエントリの情報
+pub struct CpioEntry {
+    pub name: String,
+    pub mode: u32,
+    /// ハードリンクを判定するためのデバイス番号と inode 番号
+    pub inode: (u64, u64),
+    pub nlink: u64,
+    pub mtime: u64,
+    pub size: u64,
+}
+
+impl CpioEntry {
+    fn file_type(&self) -> u32 {
+        self.mode & S_IFMT
+    }
+
+    /// 内容を別のエントリに持つハードリンクかどうか
+    ///
+    /// newc 形式では、同じ inode のエントリのうち最後のものにだけ内容が格納される。
+    fn is_hardlink(&self) -> bool {
+        self.file_type() == S_IFREG && self.nlink > 1 && self.size == 0
+    }
+
+    fn kind(&self) -> EntryKind {
+        match self.file_type() {
+            S_IFDIR => EntryKind::Directory,
+            S_IFLNK => EntryKind::Symlink,
+            _ if self.is_hardlink() => EntryKind::Hardlink,
+            _ => EntryKind::File,
+        }
+    }
+}
+
+/// cpio アーカイブのエントリを順に読み込むリーダー
+///
+/// `next_entry` で次のエントリに進み、`Read` でそのエントリの内容を読み込む。
+/// initramfs のように複数のアーカイブが連結されている場合（途中から圧縮されている場合を含む）は、
+/// 続くアーカイブのエントリも続けて返す。
+pub struct CpioReader<'a> {
+    inner: Box<dyn BufRead + 'a>,
+    /// 現在のエントリの未読のバイト数
+    remaining: u64,
+    /// 現在のエントリの後の、境界に揃えるための詰め物
+    padding: u64,
+    /// CRC つきの newc 形式の場合、ヘッダのチェックサムとここまでの内容の合計
+    checksum: Option<(u32, u32)>,
+}
+
+impl<'a> CpioReader<'a> {
+    pub fn new<R: BufRead + 'a>(reader: R, compression: CpioCompression) -> Result<Self> {
+        Ok(Self {
+            inner: compression.decoder(reader)?,
+            remaining: 0,
+            padding: 0,
+            checksum: None,
+        })
+    }
+
+    /// 次のエントリに進む（残りの内容は読み飛ばす）。終端に達した場合は `None` を返す
+    pub fn next_entry(&mut self) -> Result<Option<CpioEntry>> {
+        let skip = self.remaining + self.padding;
+        io::copy(&mut (&mut self.inner).take(skip), &mut io::sink())?;
+        self.remaining = 0;
+        self.padding = 0;
+        self.checksum = None;
+
+        let mut at_start = true;
+        loop {
+            // 終端のエントリの後は、ブロック境界までの 0 埋めの後に次のアーカイブが続くことがある
+            let mut magic = [0u8; 6];
+            if !at_start {
+                skip_zeros(&mut self.inner)?;
+                let buf = self.inner.fill_buf()?;
+                if let Some(compression) = CpioCompression::from_magic(buf) {
+                    let inner = std::mem::replace(&mut self.inner, Box::new(io::empty()));
+                    self.inner = compression.decoder(inner)?;
+                } else if !is_cpio_header(buf) {
+                    return Ok(None);
+                }
+            }
+            at_start = false;
+
+            // ヘッダの長さと、ファイル名・内容の後を揃える境界は形式ごとに異なる
+            self.inner.read_exact(&mut magic[..2])?;
+            let (entry, name_len, checksum, header_len, align) = if magic[..2]
+                == BINARY_MAGIC.to_le_bytes()
+                || magic[..2] == BINARY_MAGIC.to_be_bytes()
+            {
+                let big_endian = magic[..2] == BINARY_MAGIC.to_be_bytes();
+                let (entry, name_len) = self.read_binary_header(big_endian)?;
+                (entry, name_len, None, BINARY_HEADER_LEN, 2)
+            } else {
+                self.inner.read_exact(&mut magic[2..])?;
+                match &magic[..] {
+                    NEWC_MAGIC | NEWC_CRC_MAGIC => {
+                        let (entry, name_len, checksum) = self.read_newc_header()?;
+                        let checksum = (magic[..] == *NEWC_CRC_MAGIC).then_some(checksum);
+                        (entry, name_len, checksum, NEWC_HEADER_LEN, 4)
+                    }
+                    ODC_MAGIC => {
+                        let (entry, name_len) = self.read_odc_header()?;
+                        (entry, name_len, None, ODC_HEADER_LEN, 1)
+                    }
+                    _ => return Err(invalid_data("corrupt cpio header").into()),
+                }
+            };
+
+            // ファイル名（終端の NUL を含む）の後は、ヘッダの先頭から境界に揃うように詰め物が入る
+            if name_len > MAX_NAME_LEN {
+                return Err(invalid_data("cpio file name too long").into());
+            }
+            let mut name = vec![0; name_len as usize];
+            self.inner.read_exact(&mut name)?;
+            let name_padding = padding(header_len as u64 + name_len, align);
+            io::copy(&mut (&mut self.inner).take(name_padding), &mut io::sink())?;
+            while name.last() == Some(&0) {
+                name.pop();
+            }
+
+            self.remaining = entry.size;
+            self.padding = padding(entry.size, align);
+
+            if name == TRAILER_NAME {
+                io::copy(&mut (&mut self.inner).take(self.remaining), &mut io::sink())?;
+                self.remaining = 0;
+                continue;
+            }
+
+            self.checksum = checksum.map(|checksum| (checksum, 0));
+            return Ok(Some(CpioEntry {
+                name: decode_filename(&name),
+                ..entry
+            }));
+        }
+    }
+
+    /// newc 形式のヘッダ（マジックナンバーの後の 8 桁の 16 進数 13 個）
+    fn read_newc_header(&mut self) -> Result<(CpioEntry, u64, u32)> {
+        let mut fields = [0u64; 13];
+        for field in &mut fields {
+            *field = self.read_number(8, 16)?;
+        }
+        let [ino, mode, _uid, _gid, nlink, mtime, size, dev_major, dev_minor, _, _, name_len, checksum] =
+            fields;
+
+        let entry = CpioEntry {
+            name: String::new(),
+            mode: mode as u32,
+            inode: ((dev_major << 32) | dev_minor, ino),
+            nlink,
+            mtime,
+            size,
+        };
+        Ok((entry, name_len, checksum as u32))
+    }
+
+    /// odc 形式のヘッダ（マジックナンバーの後の 8 進数）
+    fn read_odc_header(&mut self) -> Result<(CpioEntry, u64)> {
+        let dev = self.read_number(6, 8)?;
+        let ino = self.read_number(6, 8)?;
+        let mode = self.read_number(6, 8)?;
+        let _uid = self.read_number(6, 8)?;
+        let _gid = self.read_number(6, 8)?;
+        let nlink = self.read_number(6, 8)?;
+        let _rdev = self.read_number(6, 8)?;
+        let mtime = self.read_number(11, 8)?;
+        let name_len = self.read_number(6, 8)?;
+        let size = self.read_number(11, 8)?;
+
+        let entry = CpioEntry {
+            name: String::new(),
+            mode: mode as u32,
+            inode: (dev, ino),
+            nlink,
+            mtime,
+            size,
+        };
+        Ok((entry, name_len))
+    }
+
+    /// バイナリ形式のヘッダ（マジックナンバーの後の 16 ビット整数 12 個）
+    ///
+    /// 32 ビットの値（更新日時とサイズ）は上位 16 ビットが先に格納される。
+    fn read_binary_header(&mut self, big_endian: bool) -> Result<(CpioEntry, u64)> {
+        let mut bytes = [0u8; BINARY_HEADER_LEN - 2];
+        self.inner.read_exact(&mut bytes)?;
+        let words: Vec<u64> = bytes
+            .chunks_exact(2)
+            .map(|word| {
+                let word = [word[0], word[1]];
+                u64::from(if big_endian {
+                    u16::from_be_bytes(word)
+                } else {
+                    u16::from_le_bytes(word)
+                })
+            })
+            .collect();
+
+        let entry = CpioEntry {
+            name: String::new(),
+            mode: words[2] as u32,
+            inode: (words[0], words[1]),
+            nlink: words[5],
+            mtime: (words[7] << 16) | words[8],
+            size: (words[10] << 16) | words[11],
+        };
+        Ok((entry, words[9]))
+    }
+
+    /// 固定長の ASCII の数値のフィールドを読む
+    fn read_number(&mut self, len: usize, radix: u32) -> Result<u64> {
+        let mut bytes = vec![0u8; len];
+        self.inner.read_exact(&mut bytes)?;
+        std::str::from_utf8(&bytes)
+            .ok()
+            .and_then(|text| u64::from_str_radix(text, radix).ok())
+            .ok_or_else(|| invalid_data("corrupt cpio header").into())
+    }
+}
+
+impl Read for CpioReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let len = buf
+            .len()
+            .min(self.remaining.try_into().unwrap_or(usize::MAX));
+        if len == 0 {
+            return Ok(0);
+        }
+
+        let n = self.inner.read(&mut buf[..len])?;
+        if n == 0 {
+            return Err(io::ErrorKind::UnexpectedEof.into());
+        }
+        self.remaining -= n as u64;
+
+        // CRC つきの newc 形式のチェックサムは内容の各バイトの単純な合計
+        if let Some((expected, sum)) = &mut self.checksum {
+            *sum = buf[..n]
+                .iter()
+                .fold(*sum, |sum, &b| sum.wrapping_add(u32::from(b)));
+            if self.remaining == 0 && sum != expected {
+                return Err(invalid_data("cpio checksum mismatch"));
+            }
+        }
+        Ok(n)
+    }
+}
+
+/// `len` バイトの後を `align` バイト境界に揃えるための詰め物の長さ
+fn padding(len: u64, align: u64) -> u64 {
+    (align - len % align) % align
+}
+
+/// 0 のバイトを読み飛ばす
+fn skip_zeros(reader: &mut dyn BufRead) -> io::Result<()> {
+    loop {
+        let buf = reader.fill_buf()?;
+        if buf.is_empty() {
+            return Ok(());
+        }
+        let zeros = buf.iter().take_while(|&&b| b == 0).count();
+        let done = zeros < buf.len();
+        reader.consume(zeros);
+        if done {
+            return Ok(());
+        }
+    }
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct CpioExtractor {
+    compression: CpioCompression,
+}
+
+impl CpioExtractor {
+    pub fn new(compression: CpioCompression) -> Self {
+        Self { compression }
+    }
+
+    fn open(&self, file_path: &Path) -> Result<CpioReader<'static>> {
+        let file = File::open(file_path)?;
+        CpioReader::new(BufReader::new(file), self.compression)
+    }
+
+    /// 圧縮を解いた先頭が cpio かどうか（単一ファイルの圧縮と区別する）
+    fn is_cpio(&self, file_path: &Path) -> bool {
+        File::open(file_path)
+            .map_err(anyhow::Error::from)
+            .and_then(|file| self.compression.decoder(BufReader::new(file)))
+            .and_then(|decoder| Ok(read_header_from(decoder)?))
+            .map(|header| is_cpio_header(&header))
+            .unwrap_or(false)
+    }
+}
+
+impl Extractor for CpioExtractor {
+    fn name(&self) -> &'static str {
+        match self.compression {
+            CpioCompression::None => "CPIO",
+            CpioCompression::Gzip => "CPIO.GZ",
+            CpioCompression::Xz => "CPIO.XZ",
+            CpioCompression::Zstd => "CPIO.ZST",
+        }
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        match self.compression {
+            CpioCompression::None => &["cpio"],
+            CpioCompression::Gzip => &["cpio.gz"],
+            CpioCompression::Xz => &["cpio.xz"],
+            CpioCompression::Zstd => &["cpio.zst"],
+        }
+    }
+
+    fn detect(&self, file_path: &Path, header: &[u8]) -> bool {
+        let outer_magic = match self.compression {
+            CpioCompression::None => return is_cpio_header(header),
+            CpioCompression::Gzip => GZIP_MAGIC,
+            CpioCompression::Xz => XZ_MAGIC,
+            CpioCompression::Zstd => ZSTD_MAGIC,
+        };
+        header.starts_with(outer_magic) && self.is_cpio(file_path)
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        let mut reader = self.open(file_path)?;
+        let mut entries = Vec::new();
+
+        while let Some(entry) = reader.next_entry()? {
+            // ルートディレクトリ自身を表すエントリ
+            if entry.name == "." {
+                continue;
+            }
+            entries.push(ArchiveEntry {
+                size: entry.size,
+                // cpio 全体を圧縮するため、エントリごとの圧縮後のサイズはない
+                compressed_size: None,
+                modified: unix_datetime(entry.mtime as i64),
+                kind: entry.kind(),
+                name: entry.name,
+            });
+        }
+        Ok(entries)
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let mut reader = self.open(file_path)?;
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+
+        // プログレスバーの設定（cpio は事前にエントリ数が分からないのでスピナー形式）
+        let message = match self.compression {
+            CpioCompression::None => t!("progress.extracting_cpio"),
+            CpioCompression::Gzip => t!("progress.extracting_cpio_gz"),
+            CpioCompression::Xz => t!("progress.extracting_cpio_xz"),
+            CpioCompression::Zstd => t!("progress.extracting_cpio_zst"),
+        };
+        let pb = new_spinner(message.to_string());
+
+        let result = extract_cpio_entries(&mut reader, extract_dir, options, &tracker, &pb);
+        tracker.finish(result)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let mut reader = self.open(file_path)?;
+
+        // CRC つきの newc 形式はエントリを読み込む際にチェックサムを検証する
+        while let Some(entry) = reader.next_entry()? {
+            if entry.name == "." {
+                continue;
+            }
+            let result = io::copy(&mut reader, &mut io::sink());
+
+            // 外側の圧縮ストリームが壊れている場合は以降のエントリを読めない
+            let failed = result
+                .as_ref()
+                .is_err_and(|e| e.kind() != io::ErrorKind::InvalidData);
+            report(&entry.name, result.map(|_| ()).map_err(Into::into));
+            if failed {
+                return Ok(());
+            }
+        }
+        Ok(())
+    }
+}
+
+/// 同じ inode のエントリ（ハードリンク）の解凍状況
+#[derive(Default)]
+struct HardlinkGroup {
+    /// 内容を書き出したエントリ名
+    source: Option<String>,
+    /// 内容を書き出すエントリを待っているエントリ名と書き出し先
+    pending: Vec<(String, PathBuf)>,
+}
+
+// cpio のエントリの解凍処理
+fn extract_cpio_entries(
+    reader: &mut CpioReader,
+    extract_dir: &Path,
+    options: &ExtractOptions,
+    tracker: &LimitTracker,
+    pb: &ProgressBar,
+) -> Result<()> {
+    let mut links = DeferredLinks::new(extract_dir, options.link_policy);
+    let mut hardlinks: HashMap<(u64, u64), HardlinkGroup> = HashMap::new();
+
+    while let Some(entry) = reader.next_entry()? {
+        if entry.name == "." {
+            continue;
+        }
+        tracker.start_entry()?;
+
+        // 解凍先の外を指すエントリはスキップ
+        let Some(output_path) = entry_output_path(extract_dir, &entry.name, options) else {
+            pb.inc(1);
+            continue;
+        };
+
+        // プログレスバーのメッセージを更新
+        if let Some(file_name) = output_path.file_name().and_then(|s| s.to_str()) {
+            pb.set_message(format!(
+                "{}",
+                t!("progress.extracting_file", file = file_name)
+            ));
+        }
+
+        match entry.file_type() {
+            S_IFDIR => fs::create_dir_all(&output_path)?,
+            // シンボリックリンクはリンク先のパスが内容として格納されている
+            S_IFLNK => {
+                let mut target = Vec::new();
+                tracker
+                    .reader(&mut *reader)
+                    .take(MAX_LINK_TARGET_LEN)
+                    .read_to_end(&mut target)?;
+                links.add(
+                    LinkKind::Symbolic,
+                    &entry.name,
+                    output_path,
+                    &decode_filename(&target),
+                );
+            }
+            S_IFREG => {
+                let group = (entry.nlink > 1).then(|| hardlinks.entry(entry.inode).or_default());
+
+                // 内容は同じ inode の別のエントリに格納されている
+                if let (true, Some(group)) = (entry.is_hardlink(), group) {
+                    match &group.source {
+                        Some(source) => links.add(LinkKind::Hard, &entry.name, output_path, source),
+                        None => group.pending.push((entry.name, output_path)),
+                    }
+                    pb.inc(1);
+                    continue;
+                }
+
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut output_file = File::create(&output_path)?;
+                io::copy(&mut tracker.reader(&mut *reader), &mut output_file)?;
+
+                // ファイル権限を設定 (Unix系のみ)
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    fs::set_permissions(
+                        &output_path,
+                        fs::Permissions::from_mode(entry.mode & 0o7777),
+                    )?;
+                }
+
+                if let Some(group) = hardlinks.get_mut(&entry.inode).filter(|_| entry.nlink > 1) {
+                    for (name, path) in group.pending.drain(..) {
+                        links.add(LinkKind::Hard, &name, path, &entry.name);
+                    }
+                    group.source = Some(entry.name);
+                }
+            }
+            // デバイスファイル・FIFO・ソケットは作成しない
+            _ => debug!("Skipped special file: {}", entry.name),
+        }
+        pb.inc(1);
+    }
+
+    // 内容を持つエントリがなかったハードリンクは、空のファイルとして作成する
+    for group in hardlinks.into_values() {
+        let mut pending = group.pending.into_iter();
+        if let Some((source, path)) = pending.next() {
+            if let Some(parent) = path.parent() {
+                fs::create_dir_all(parent)?;
+            }
+            File::create(&path)?;
+            for (name, path) in pending {
+                links.add(LinkKind::Hard, &name, path, &source);
+            }
+        }
+    }
+
+    links.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{read_output, Fixture};
+
+    /// newc 形式のエントリ（`magic` が 070702 の場合はチェックサムも書き込む）
+    fn newc_entry(
+        magic: &[u8],
+        name: &str,
+        mode: u32,
+        ino: u64,
+        nlink: u64,
+        data: &[u8],
+    ) -> Vec<u8> {
+        let checksum = data
+            .iter()
+            .fold(0u32, |sum, &b| sum.wrapping_add(u32::from(b)));
+        let name_len = name.len() + 1;
+        let fields = [
+            ino,
+            u64::from(mode),
+            0,
+            0,
+            nlink,
+            0,
+            data.len() as u64,
+            0,
+            0,
+            0,
+            0,
+        ];
+        let mut entry = magic.to_vec();
+        for field in fields {
+            entry.extend_from_slice(format!("{:08X}", field).as_bytes());
+        }
+        entry.extend_from_slice(format!("{:08X}{:08X}", name_len, checksum).as_bytes());
+        entry.extend_from_slice(name.as_bytes());
+        entry.push(0);
+        entry.resize(
+            entry.len() + padding((NEWC_HEADER_LEN + name_len) as u64, 4) as usize,
+            0,
+        );
+        entry.extend_from_slice(data);
+        entry.resize(entry.len() + padding(data.len() as u64, 4) as usize, 0);
+        entry
+    }
+
+    fn newc_archive(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut archive = entries.concat();
+        archive.extend(newc_entry(NEWC_MAGIC, "TRAILER!!!", 0, 0, 1, b""));
+        archive
+    }
+
+    fn extract(data: &[u8]) -> Result<Fixture> {
+        let fixture = Fixture::new("test.cpio", data);
+        fixture.extract(
+            &CpioExtractor::new(CpioCompression::None),
+            &ExtractOptions::default(),
+        )?;
+        Ok(fixture)
+    }
+
+    #[test]
+    fn extracts_newc_entries() {
+        let fixture = extract(&newc_archive(&[
+            newc_entry(NEWC_MAGIC, ".", S_IFDIR | 0o755, 1, 2, b""),
+            newc_entry(NEWC_MAGIC, "dir", S_IFDIR | 0o755, 2, 2, b""),
+            newc_entry(NEWC_MAGIC, "dir/a.txt", S_IFREG | 0o644, 3, 1, b"hello"),
+            // 同じ inode のハードリンクは最後のエントリにだけ内容がある
+            newc_entry(NEWC_MAGIC, "b.txt", S_IFREG | 0o644, 4, 2, b""),
+            newc_entry(NEWC_MAGIC, "c.txt", S_IFREG | 0o644, 4, 2, b"linked"),
+            newc_entry(NEWC_MAGIC, "link", S_IFLNK | 0o777, 5, 1, b"dir/a.txt"),
+        ]))
+        .unwrap();
+
+        assert_eq!(read_output(&fixture.out, "dir/a.txt").unwrap(), b"hello");
+        assert_eq!(read_output(&fixture.out, "b.txt").unwrap(), b"linked");
+        assert_eq!(read_output(&fixture.out, "c.txt").unwrap(), b"linked");
+        assert_eq!(read_output(&fixture.out, "link").unwrap(), b"hello");
+    }
+
+    fn odc_entry(name: &str, mode: u32, data: &[u8]) -> Vec<u8> {
+        let mut entry = ODC_MAGIC.to_vec();
+        for field in [0, 1, mode, 0, 0, 1, 0] {
+            entry.extend_from_slice(format!("{:06o}", field).as_bytes());
+        }
+        entry.extend_from_slice(
+            format!("{:011o}{:06o}{:011o}", 0, name.len() + 1, data.len()).as_bytes(),
+        );
+        entry.extend_from_slice(name.as_bytes());
+        entry.push(0);
+        entry.extend_from_slice(data);
+        entry
+    }
+
+    #[test]
+    fn extracts_odc_entries() {
+        let odc = [
+            odc_entry("a.txt", S_IFREG | 0o644, b"odc"),
+            odc_entry("TRAILER!!!", 0, b""),
+        ]
+        .concat();
+        let fixture = extract(&odc).unwrap();
+        assert_eq!(read_output(&fixture.out, "a.txt").unwrap(), b"odc");
+
+        // 終端のエントリがないアーカイブは途中で切れている
+        assert!(extract(&odc_entry("a.txt", S_IFREG | 0o644, b"odc")).is_err());
+    }
+
+    #[test]
+    fn extracts_binary_entries_in_both_byte_orders() {
+        for big_endian in [false, true] {
+            let word = |value: u32| {
+                if big_endian {
+                    (value as u16).to_be_bytes()
+                } else {
+                    (value as u16).to_le_bytes()
+                }
+            };
+            let entry = |name: &[u8], data: &[u8]| {
+                let words = [
+                    BINARY_MAGIC.into(),
+                    0,
+                    2,
+                    S_IFREG | 0o644,
+                    0,
+                    0,
+                    1,
+                    0,
+                    0,
+                    0,
+                    name.len() as u32,
+                    0,
+                    data.len() as u32,
+                ];
+                let mut entry: Vec<u8> = words.into_iter().flat_map(word).collect();
+                // ファイル名と内容の後は 2 バイト境界に揃える
+                entry.extend_from_slice(name);
+                entry.resize(entry.len() + name.len() % 2, 0);
+                entry.extend_from_slice(data);
+                entry.resize(entry.len() + data.len() % 2, 0);
+                entry
+            };
+
+            let binary = [
+                entry(b"a.txt\0", b"odd"),
+                entry(b"b.txt\0", b"binary"),
+                entry(b"TRAILER!!!\0", b""),
+            ]
+            .concat();
+            let fixture = extract(&binary).unwrap();
+            assert_eq!(read_output(&fixture.out, "a.txt").unwrap(), b"odd");
+            assert_eq!(read_output(&fixture.out, "b.txt").unwrap(), b"binary");
+        }
+    }
+
+    #[test]
+    fn verifies_crc_checksums() {
+        let good = newc_archive(&[newc_entry(
+            NEWC_CRC_MAGIC,
+            "a.txt",
+            S_IFREG | 0o644,
+            1,
+            1,
+            b"data",
+        )]);
+        let fixture = Fixture::new("good.cpio", &good);
+        let extractor = CpioExtractor::new(CpioCompression::None);
+        assert_eq!(
+            fixture.test(&extractor).unwrap(),
+            vec![("a.txt".to_string(), true)]
+        );
+
+        let mut bad = good.clone();
+        let pos = bad.windows(4).position(|w| w == b"data").unwrap();
+        bad[pos] = b'D';
+        let fixture = Fixture::new("bad.cpio", &bad);
+        assert_eq!(
+            fixture.test(&extractor).unwrap(),
+            vec![("a.txt".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn rejects_oversized_names_before_allocating() {
+        let mut entry = newc_entry(NEWC_MAGIC, "a.txt", S_IFREG | 0o644, 1, 1, b"");
+        // ファイル名の長さ（12 番目のフィールド）を最大値にする
+        let pos = NEWC_MAGIC.len() + 11 * 8;
+        entry[pos..pos + 8].copy_from_slice(b"FFFFFFFF");
+        let error = extract(&entry).err().unwrap();
+        assert!(error.to_string().contains("too long"), "{error}");
+    }
+
+    #[test]
+    fn rejects_corrupt_headers() {
+        let mut entry = newc_entry(NEWC_MAGIC, "a.txt", S_IFREG | 0o644, 1, 1, b"data");
+        entry[10] = b'x';
+        assert!(extract(&entry).is_err());
+
+        let entry = newc_entry(NEWC_MAGIC, "a.txt", S_IFREG | 0o644, 1, 1, b"data");
+        assert!(extract(&entry[..entry.len() - 6]).is_err());
+    }
+}
@@ -0,0 +1,137 @@
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::path::Path;
+
+use super::limits::ExtractLimits;
+
+/// 形式判定のために読み込むファイル先頭のバイト数（TARの ustar マジックを含む）
+pub const MAGIC_HEADER_LEN: usize = 512;
+
+/// シンボリックリンク・ハードリンクのエントリの扱い
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, clap::ValueEnum)]
+pub enum LinkPolicy {
+    /// リンクとして作成する
+    #[default]
+    Recreate,
+    /// リンクのエントリを無視する
+    Skip,
+    /// リンク先の内容をコピーした通常のファイル・ディレクトリとして作成する
+    Copy,
+}
+
+/// 全形式で共通の解凍オプション
+#[derive(Debug, Clone, Default)]
+pub struct ExtractOptions {
+    pub link_policy: LinkPolicy,
+    pub limits: ExtractLimits,
+    /// `__MACOSX/` や `.DS_Store` などの不要なファイルも解凍する
+    pub keep_junk: bool,
+    /// 暗号化されたアーカイブのパスワード
+    pub password: Option<String>,
+}
+
+/// エントリの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum EntryKind {
+    File,
+    Directory,
+    Symlink,
+    Hardlink,
+}
+
+impl EntryKind {
+    /// 一覧表示・JSON 出力で使う名前
+    pub fn as_str(self) -> &'static str {
+        match self {
+            EntryKind::File => "file",
+            EntryKind::Directory => "directory",
+            EntryKind::Symlink => "symlink",
+            EntryKind::Hardlink => "hardlink",
+        }
+    }
+}
+
+/// アーカイブ内のエントリ情報（一覧表示用）
+#[derive(Debug, Clone, Serialize)]
+pub struct ArchiveEntry {
+    /// デコード済みのエントリ名
+    pub name: String,
+    /// 展開後のサイズ
+    pub size: u64,
+    /// 圧縮後のサイズ（ソリッド圧縮などでエントリごとに分からない形式は `None`）
+    pub compressed_size: Option<u64>,
+    /// 最終更新日時（ローカル時刻）
+    pub modified: Option<NaiveDateTime>,
+    pub kind: EntryKind,
+}
+
+/// 検査モードでエントリ名と検査結果を受け取るコールバック
+pub type TestReport<'a> = dyn FnMut(&str, Result<()>) + 'a;
+
+/// 各アーカイブ形式が実装する共通インターフェース
+///
+/// 新しい形式を追加する場合はこのトレイトを実装し、
+/// `Registry::with_builtin` に登録する。
+pub trait Extractor: Send + Sync {
+    /// 形式の表示名（例: "ZIP"）
+    fn name(&self) -> &'static str;
+
+    /// 対応する拡張子（小文字・先頭のドットなし、例: "tar.gz"）
+    fn extensions(&self) -> &'static [&'static str];
+
+    /// ファイルの先頭バイト（マジックナンバー）からこの形式かどうかを判定する
+    ///
+    /// `header` はファイル先頭の最大 `MAGIC_HEADER_LEN` バイト。
+    /// 判定できない形式は `false` を返し、拡張子による判定に任せる。
+    fn detect(&self, file_path: &Path, header: &[u8]) -> bool {
+        let _ = (file_path, header);
+        false
+    }
+
+    /// 拡張子がこの形式のものかどうかを判定する
+    fn matches_extension(&self, file_path: &Path) -> bool {
+        self.matched_extension(file_path).is_some()
+    }
+
+    /// ファイル名に一致するこの形式の拡張子（`tar.zst` と `tzst` のように複数ある場合は長い方）
+    fn matched_extension(&self, file_path: &Path) -> Option<&'static str> {
+        let file_name = file_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+
+        self.extensions()
+            .iter()
+            .copied()
+            .filter(|ext| has_extension(file_name, ext))
+            .max_by_key(|ext| ext.len())
+    }
+
+    /// エントリの一覧を取得する
+    ///
+    /// 暗号化されていてパスワードが足りない・間違っている場合は `PasswordError` を返す。
+    fn list(&self, file_path: &Path, password: Option<&str>) -> Result<Vec<ArchiveEntry>>;
+
+    /// `extract_dir` にすべてのエントリを解凍する（`extract_dir` は作成済み）
+    ///
+    /// 暗号化されていてパスワードが足りない・間違っている場合は `PasswordError` を返す。
+    fn extract(&self, file_path: &Path, extract_dir: &Path, options: &ExtractOptions)
+        -> Result<()>;
+
+    /// ファイルを書き出さずにすべてのエントリをデコードし、破損がないか検査する
+    ///
+    /// エントリごとの結果（CRC の不一致など）は `report` に渡す。
+    /// アーカイブ自体が読めない場合や、ストリーム全体のチェックサムが一致しない場合は `Err` を返す。
+    fn test(&self, file_path: &Path, password: Option<&str>, report: &mut TestReport)
+        -> Result<()>;
+}
+
+/// ファイル名が `.{ext}` で終わるかどうか（大文字・小文字は区別しない）
+///
+/// 一致した場合、末尾の `ext.len() + 1` バイトは ASCII なので、そこで切り分けられる。
+pub fn has_extension(file_name: &str, ext: &str) -> bool {
+    let Some(start) = file_name.len().checked_sub(ext.len() + 1) else {
+        return false;
+    };
+    let suffix = &file_name.as_bytes()[start..];
+    suffix[0] == b'.' && suffix[1..].eq_ignore_ascii_case(ext.as_bytes())
+}
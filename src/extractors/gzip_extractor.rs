@@ -1,39 +1,66 @@
 use anyhow::Result;
 use flate2::read::GzDecoder;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::fs::{self, File};
-use std::io::BufReader;
+use std::fs::File;
+use std::io::{BufReader, Read};
 use std::path::Path;
 
 // Import the i18n macro
 use rust_i18n::t;
 
-pub fn extract_gz(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let mut decoder = GzDecoder::new(reader);
+use super::common::{decompress_single_file, list_single_file, new_spinner, test_single_file};
+use super::extractor::{ArchiveEntry, ExtractOptions, Extractor, TestReport};
 
-    fs::create_dir_all(extract_dir)?;
+/// gzip ストリームのマジックナンバー
+pub const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
 
-    // プログレスバーの設定
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_gz")));
+pub struct GzExtractor;
 
-    // .gz ファイルの元のファイル名を取得
-    let output_name = file_path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("extracted");
+impl GzExtractor {
+    fn open(file_path: &Path) -> Result<impl Read> {
+        let file = File::open(file_path)?;
+        Ok(GzDecoder::new(BufReader::new(file)))
+    }
+}
+
+impl Extractor for GzExtractor {
+    fn name(&self) -> &'static str {
+        "GZIP"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["gz"]
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        header.starts_with(GZIP_MAGIC)
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        list_single_file(Self::open(file_path)?, file_path)
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let decoder = Self::open(file_path)?;
 
-    let output_path = extract_dir.join(output_name);
-    let mut output_file = File::create(output_path)?;
+        // プログレスバーの設定
+        let _pb = new_spinner(format!("{}", t!("progress.extracting_gz")));
 
-    std::io::copy(&mut decoder, &mut output_file)?;
+        decompress_single_file(decoder, file_path, extract_dir, options)
+    }
 
-    Ok(())
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        // 末尾まで読むとgzip のトレーラーの CRC32が検証される
+        test_single_file(Self::open(file_path)?, file_path, report);
+        Ok(())
+    }
 }
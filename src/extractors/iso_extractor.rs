@@ -0,0 +1,931 @@
+use anyhow::{anyhow, Result};
+use chrono::{Duration, Local, NaiveDate, NaiveDateTime};
+use indicatif::ProgressBar;
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+// Import common decode function
+use super::common::{decode_filename, entry_output_path, new_progress_bar};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::limits::LimitTracker;
+use super::links::{is_symlink_mode, DeferredLinks, LinkKind};
+
+/// ボリューム記述子などの単位となるセクタのサイズ
+const SECTOR_SIZE: u64 = 2048;
+/// 最初のボリューム記述子のセクタ（それより前はシステム領域）
+const FIRST_DESCRIPTOR_SECTOR: u64 = 16;
+/// 読み込むボリューム記述子の最大数
+const MAX_DESCRIPTORS: u64 = 64;
+
+/// ISO 9660 のボリューム記述子の識別子
+const ISO_MAGIC: &[u8] = b"CD001";
+/// UDF のボリューム認識シーケンスの識別子
+const UDF_MAGICS: [&[u8]; 3] = [b"BEA01", b"NSR02", b"NSR03"];
+
+/// ボリューム記述子の種類
+const DESCRIPTOR_PRIMARY: u8 = 1;
+const DESCRIPTOR_SUPPLEMENTARY: u8 = 2;
+
+/// Joliet の補助ボリューム記述子を示すエスケープシーケンス（UCS-2 レベル 1〜3）
+const JOLIET_ESCAPES: [&[u8]; 3] = [b"%/@", b"%/C", b"%/E"];
+
+/// ディレクトリレコードのフラグ
+const FLAG_DIRECTORY: u8 = 0x02;
+const FLAG_MULTI_EXTENT: u8 = 0x80;
+
+/// ディレクトリの階層の上限（循環したディレクトリを含む壊れたイメージ対策）
+const MAX_DEPTH: usize = 64;
+/// Rock Ridge の CE エントリで続きの領域をたどる回数の上限
+const MAX_CONTINUATIONS: usize = 64;
+/// 1つのディレクトリの大きさの上限
+const MAX_DIRECTORY_SIZE: u64 = 64 * 1024 * 1024;
+
+/// ディレクトリツリーのファイル名の形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum TreeKind {
+    /// ISO 9660 の 8.3 形式の名前
+    Primary,
+    /// Rock Ridge 拡張の長い名前・パーミッション・シンボリックリンク
+    RockRidge,
+    /// Joliet 拡張の UCS-2 の名前
+    Joliet,
+}
+
+/// ディスクイメージ内のエントリ
+struct IsoEntry {
+    name: String,
+    kind: EntryKind,
+    /// 内容が格納されている (論理ブロック番号, バイト数) の並び（マルチエクステントの場合は複数）
+    extents: Vec<(u64, u64)>,
+    modified: Option<NaiveDateTime>,
+    /// Rock Ridge の POSIX ファイルモード
+    mode: Option<u32>,
+    /// Rock Ridge のシンボリックリンクのリンク先
+    link_target: Option<String>,
+}
+
+impl IsoEntry {
+    fn size(&self) -> u64 {
+        self.extents.iter().map(|(_, len)| len).sum()
+    }
+}
+
+/// ディレクトリレコードの内容
+struct DirectoryRecord {
+    extent: u64,
+    data_len: u64,
+    flags: u8,
+    name: Vec<u8>,
+    modified: Option<NaiveDateTime>,
+    system_use: Vec<u8>,
+}
+
+impl DirectoryRecord {
+    fn parse(record: &[u8]) -> Option<Self> {
+        let name_len = *record.get(32)? as usize;
+        let name = record.get(33..33 + name_len)?.to_vec();
+        // ファイル名の長さが偶数の場合は詰め物の 1 バイトが入る
+        let system_use_start = 33 + name_len + (1 - name_len % 2);
+
+        Some(Self {
+            extent: u64::from(u32::from_le_bytes(record[2..6].try_into().ok()?)),
+            data_len: u64::from(u32::from_le_bytes(record[10..14].try_into().ok()?)),
+            flags: record[25],
+            name,
+            modified: recording_datetime(&record[18..25]),
+            system_use: record.get(system_use_start..).unwrap_or_default().to_vec(),
+        })
+    }
+
+    fn is_directory(&self) -> bool {
+        self.flags & FLAG_DIRECTORY != 0
+    }
+
+    /// `.`（0x00）と `..`（0x01）のレコードかどうか
+    fn is_self_or_parent(&self) -> bool {
+        matches!(self.name[..], [0] | [1])
+    }
+}
+
+/// ディレクトリレコードの日時（1900年からの年・月・日・時・分・秒・15分単位の時差）を変換する
+fn recording_datetime(bytes: &[u8]) -> Option<NaiveDateTime> {
+    if bytes.iter().all(|&b| b == 0) {
+        return None;
+    }
+    let datetime =
+        NaiveDate::from_ymd_opt(1900 + i32::from(bytes[0]), bytes[1].into(), bytes[2].into())?
+            .and_hms_opt(bytes[3].into(), bytes[4].into(), bytes[5].into())?;
+    let utc = datetime - Duration::minutes(i64::from(bytes[6] as i8) * 15);
+    Some(utc.and_utc().with_timezone(&Local).naive_local())
+}
+
+/// Rock Ridge のシステム使用領域から読み取った情報
+#[derive(Default)]
+struct RockRidge {
+    /// NM エントリの名前（`.` と `..` を表す場合は `None`）
+    name: Option<Vec<u8>>,
+    /// PX エントリのファイルモード
+    mode: Option<u32>,
+    /// SL エントリのリンク先
+    link_target: Option<String>,
+    /// CL エントリ（移動されたディレクトリの実際の位置）
+    child_link: Option<u64>,
+    /// RE エントリ（移動されたディレクトリ自身。CL のエントリから辿るため一覧では読み飛ばす）
+    relocated: bool,
+}
+
+/// ディスクイメージ
+struct IsoImage {
+    file: File,
+    block_size: u64,
+    entries: Vec<IsoEntry>,
+}
+
+impl IsoImage {
+    fn open(file_path: &Path) -> Result<Self> {
+        let mut file = File::open(file_path)?;
+
+        // ボリューム記述子から基本ボリューム記述子と Joliet の補助ボリューム記述子を探す
+        let mut primary = None;
+        let mut joliet = None;
+        let mut udf = false;
+        for sector in FIRST_DESCRIPTOR_SECTOR..FIRST_DESCRIPTOR_SECTOR + MAX_DESCRIPTORS {
+            let mut descriptor = vec![0u8; SECTOR_SIZE as usize];
+            file.seek(SeekFrom::Start(sector * SECTOR_SIZE))?;
+            if file.read_exact(&mut descriptor).is_err() {
+                break;
+            }
+
+            let identifier = &descriptor[1..6];
+            if UDF_MAGICS.contains(&identifier) {
+                udf = true;
+                continue;
+            }
+            if identifier != ISO_MAGIC {
+                // UDF のボリューム認識シーケンスは ISO 9660 の終端記述子の後に続くことがある
+                if udf || primary.is_some() {
+                    break;
+                }
+                continue;
+            }
+
+            match descriptor[0] {
+                DESCRIPTOR_PRIMARY if primary.is_none() => primary = Some(descriptor),
+                DESCRIPTOR_SUPPLEMENTARY
+                    if JOLIET_ESCAPES
+                        .iter()
+                        .any(|escape| descriptor[88..].starts_with(escape)) =>
+                {
+                    joliet = Some(descriptor)
+                }
+                _ => {}
+            }
+        }
+
+        let Some(primary) = primary else {
+            // UDF だけで書き込まれた DVD などのイメージ
+            if udf {
+                return Err(anyhow!(t!("ui.error_udf_unsupported").to_string()));
+            }
+            return Err(invalid_data("no ISO 9660 volume descriptor").into());
+        };
+
+        let block_size = u64::from(u16::from_le_bytes([primary[128], primary[129]]));
+        let mut image = Self {
+            file,
+            block_size: if block_size == 0 {
+                SECTOR_SIZE
+            } else {
+                block_size
+            },
+            entries: Vec::new(),
+        };
+
+        // 長いファイル名を使えるツリーを優先する（Rock Ridge → Joliet → ISO 9660 の名前）
+        let primary_root = root_record(&primary)?;
+        let (tree, root) = if image.has_rock_ridge(&primary_root)? {
+            (TreeKind::RockRidge, primary_root)
+        } else if let Some(joliet) = &joliet {
+            (TreeKind::Joliet, root_record(joliet)?)
+        } else {
+            (TreeKind::Primary, primary_root)
+        };
+
+        let mut visited = HashSet::new();
+        image.read_directory(tree, root.extent, root.data_len, "", 0, &mut visited)?;
+        Ok(image)
+    }
+
+    /// ルートディレクトリの `.` のレコードに SUSP の SP エントリがあれば Rock Ridge とみなす
+    fn has_rock_ridge(&mut self, root: &DirectoryRecord) -> Result<bool> {
+        let data = self.read_extent(root.extent, root.data_len.min(self.block_size))?;
+        Ok(first_record(&data).is_some_and(|record| {
+            record
+                .system_use
+                .get(..6)
+                .is_some_and(|sp| sp[..2] == *b"SP" && sp[4..] == [0xbe, 0xef])
+        }))
+    }
+
+    /// 指定した論理ブロックから `len` バイトを読み込む
+    fn read_extent(&mut self, extent: u64, len: u64) -> Result<Vec<u8>> {
+        let mut data = Vec::new();
+        self.file.seek(SeekFrom::Start(extent * self.block_size))?;
+        (&mut self.file).take(len).read_to_end(&mut data)?;
+        if (data.len() as u64) < len {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+        }
+        Ok(data)
+    }
+
+    /// ディレクトリのレコードを読み込み、エントリを追加する（サブディレクトリは再帰的に読む）
+    ///
+    /// 移動されたディレクトリだけを含むディレクトリ（`rr_moved`）の場合は `true` を返す。
+    fn read_directory(
+        &mut self,
+        tree: TreeKind,
+        extent: u64,
+        data_len: u64,
+        prefix: &str,
+        depth: usize,
+        visited: &mut HashSet<u64>,
+    ) -> Result<bool> {
+        if depth > MAX_DEPTH || data_len > MAX_DIRECTORY_SIZE || !visited.insert(extent) {
+            return Err(invalid_data("corrupt ISO 9660 directory tree").into());
+        }
+
+        let data = self.read_extent(extent, data_len)?;
+        let mut pos = 0;
+        let mut multi_extent = false;
+        let mut relocated = false;
+        let first_entry = self.entries.len();
+        while pos < data.len() {
+            // レコードはセクタをまたがず、セクタの残りは 0 で埋められる
+            let record_len = data[pos] as usize;
+            if record_len == 0 {
+                pos = (pos / SECTOR_SIZE as usize + 1) * SECTOR_SIZE as usize;
+                continue;
+            }
+            let record = data
+                .get(pos..pos + record_len)
+                .and_then(DirectoryRecord::parse)
+                .ok_or_else(|| invalid_data("corrupt ISO 9660 directory record"))?;
+            pos += record_len;
+
+            if record.is_self_or_parent() {
+                continue;
+            }
+
+            let rock_ridge = match tree {
+                TreeKind::RockRidge => self.read_rock_ridge(&record.system_use)?,
+                _ => RockRidge::default(),
+            };
+            if rock_ridge.relocated {
+                relocated = true;
+                continue;
+            }
+
+            let name = match (&rock_ridge.name, tree) {
+                (Some(name), _) => decode_filename(name),
+                (None, TreeKind::Joliet) => decode_ucs2(&record.name),
+                (None, _) => iso_name(&record.name),
+            };
+            let path = format!("{}{}", prefix, name);
+
+            // マルチエクステントのファイルは、同じ名前のレコードが続けて記録される
+            let continues =
+                std::mem::replace(&mut multi_extent, record.flags & FLAG_MULTI_EXTENT != 0);
+            if let Some(previous) = self
+                .entries
+                .last_mut()
+                .filter(|entry| continues && entry.name == path)
+            {
+                previous.extents.push((record.extent, record.data_len));
+                continue;
+            }
+
+            let (kind, extents) = if let Some(child) = rock_ridge.child_link {
+                // 階層が深いため移動されたディレクトリ（実際の内容は CL の位置にある）
+                let data = self.read_extent(child, self.block_size)?;
+                let child_record = first_record(&data)
+                    .ok_or_else(|| invalid_data("corrupt ISO 9660 directory record"))?;
+                (EntryKind::Directory, vec![(child, child_record.data_len)])
+            } else if record.is_directory() {
+                (EntryKind::Directory, vec![(record.extent, record.data_len)])
+            } else if rock_ridge.link_target.is_some()
+                || rock_ridge.mode.is_some_and(is_symlink_mode)
+            {
+                (EntryKind::Symlink, Vec::new())
+            } else {
+                (EntryKind::File, vec![(record.extent, record.data_len)])
+            };
+
+            let is_directory = kind == EntryKind::Directory;
+            let (dir_extent, dir_len) = extents.first().copied().unwrap_or_default();
+            self.entries.push(IsoEntry {
+                name: path.clone(),
+                kind,
+                extents: if is_directory { Vec::new() } else { extents },
+                modified: record.modified,
+                mode: rock_ridge.mode,
+                link_target: rock_ridge.link_target,
+            });
+
+            if is_directory {
+                let prefix = format!("{}/", path);
+                if self.read_directory(tree, dir_extent, dir_len, &prefix, depth + 1, visited)? {
+                    self.entries.pop();
+                }
+            }
+        }
+        Ok(relocated && self.entries.len() == first_entry)
+    }
+
+    /// システム使用領域の SUSP エントリから Rock Ridge の情報を読み取る
+    fn read_rock_ridge(&mut self, system_use: &[u8]) -> Result<RockRidge> {
+        let mut rock_ridge = RockRidge::default();
+        let mut name = Vec::new();
+        let mut has_name = false;
+        let mut link = String::new();
+        let mut link_continues = false;
+        let mut has_link = false;
+
+        let mut area = system_use.to_vec();
+        // CE エントリで続きの領域が示される（壊れたイメージで無限に続かないよう回数を制限する）
+        for _ in 0..MAX_CONTINUATIONS {
+            let mut continuation = None;
+            let mut pos = 0;
+            while pos + 4 <= area.len() {
+                let len = area[pos + 2] as usize;
+                if len < 4 || pos + len > area.len() {
+                    break;
+                }
+                let data = &area[pos + 4..pos + len];
+                match &area[pos..pos + 2] {
+                    b"CE" if data.len() >= 24 => {
+                        let block = u32::from_le_bytes(data[0..4].try_into()?);
+                        let offset = u32::from_le_bytes(data[8..12].try_into()?);
+                        let size = u32::from_le_bytes(data[16..20].try_into()?);
+                        continuation = Some((u64::from(block), u64::from(offset), u64::from(size)));
+                    }
+                    // フラグの 0x02 はカレントディレクトリ、0x04 は親ディレクトリを表す
+                    b"NM" if data.first().is_some_and(|flags| flags & 0x06 == 0) => {
+                        name.extend_from_slice(&data[1..]);
+                        has_name = true;
+                    }
+                    b"PX" if data.len() >= 4 => {
+                        rock_ridge.mode = Some(u32::from_le_bytes(data[0..4].try_into()?));
+                    }
+                    b"SL" if !data.is_empty() => {
+                        has_link = true;
+                        append_symlink_components(&mut link, &mut link_continues, &data[1..]);
+                    }
+                    b"CL" if data.len() >= 4 => {
+                        rock_ridge.child_link =
+                            Some(u64::from(u32::from_le_bytes(data[0..4].try_into()?)));
+                    }
+                    b"RE" => rock_ridge.relocated = true,
+                    b"ST" => break,
+                    _ => {}
+                }
+                pos += len;
+            }
+
+            let Some((block, offset, size)) = continuation else {
+                break;
+            };
+            let mut data = Vec::new();
+            self.file
+                .seek(SeekFrom::Start(block * self.block_size + offset))?;
+            (&mut self.file).take(size).read_to_end(&mut data)?;
+            area = data;
+        }
+
+        if has_name {
+            rock_ridge.name = Some(name);
+        }
+        if has_link {
+            rock_ridge.link_target = Some(link);
+        }
+        Ok(rock_ridge)
+    }
+}
+
+/// SL エントリのパスの構成要素をリンク先に追加する
+///
+/// 構成要素のフラグは 0x01: 次の構成要素に続く、0x02: `.`、0x04: `..`、0x08: ルート。
+fn append_symlink_components(link: &mut String, continues: &mut bool, mut components: &[u8]) {
+    while components.len() >= 2 {
+        let (flags, len) = (components[0], components[1] as usize);
+        let Some(content) = components.get(2..2 + len) else {
+            break;
+        };
+
+        if !*continues && !link.is_empty() && !link.ends_with('/') {
+            link.push('/');
+        }
+        match flags & 0x0e {
+            0x02 => link.push('.'),
+            0x04 => link.push_str(".."),
+            0x08 => link.push('/'),
+            _ => link.push_str(&decode_filename(content)),
+        }
+        *continues = flags & 0x01 != 0;
+        components = &components[2 + len..];
+    }
+}
+
+/// ディレクトリの先頭のレコード（`.` のレコード）
+fn first_record(data: &[u8]) -> Option<DirectoryRecord> {
+    data.get(..*data.first()? as usize)
+        .and_then(DirectoryRecord::parse)
+}
+
+/// ボリューム記述子のルートディレクトリのレコード（オフセット 156 の 34 バイト）
+fn root_record(descriptor: &[u8]) -> Result<DirectoryRecord> {
+    DirectoryRecord::parse(&descriptor[156..190])
+        .ok_or_else(|| invalid_data("corrupt ISO 9660 volume descriptor").into())
+}
+
+/// ISO 9660 のファイル名からバージョン番号（`;1`）と拡張子のない場合の末尾の `.` を取り除く
+fn iso_name(name: &[u8]) -> String {
+    let name = decode_filename(name);
+    let name = name.split_once(';').map_or(name.as_str(), |(name, _)| name);
+    name.strip_suffix('.').unwrap_or(name).to_string()
+}
+
+/// Joliet のファイル名（UCS-2 ビッグエンディアン）をデコードする
+fn decode_ucs2(name: &[u8]) -> String {
+    let units: Vec<u16> = name
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+        .collect();
+    let name = String::from_utf16_lossy(&units);
+    name.split_once(';')
+        .map_or(name.clone(), |(name, _)| name.to_string())
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct IsoExtractor;
+
+impl Extractor for IsoExtractor {
+    fn name(&self) -> &'static str {
+        "ISO"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["iso"]
+    }
+
+    fn detect(&self, file_path: &Path, _header: &[u8]) -> bool {
+        // 識別子はシステム領域（32 KiB）の後の最初のボリューム記述子にある
+        let mut identifier = [0u8; 5];
+        File::open(file_path)
+            .and_then(|mut file| {
+                file.seek(SeekFrom::Start(FIRST_DESCRIPTOR_SECTOR * SECTOR_SIZE + 1))?;
+                file.read_exact(&mut identifier)
+            })
+            .is_ok_and(|()| identifier == ISO_MAGIC || UDF_MAGICS.contains(&&identifier[..]))
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        let image = IsoImage::open(file_path)?;
+
+        Ok(image
+            .entries
+            .iter()
+            .map(|entry| ArchiveEntry {
+                name: entry.name.clone(),
+                size: entry.size(),
+                // ディスクイメージは圧縮しない
+                compressed_size: (entry.kind == EntryKind::File).then(|| entry.size()),
+                modified: entry.modified,
+                kind: entry.kind,
+            })
+            .collect())
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let mut image = IsoImage::open(file_path)?;
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+
+        // プログレスバーの設定
+        let pb = new_progress_bar(
+            image.entries.len() as u64,
+            format!("{}", t!("progress.extracting_iso")),
+        );
+
+        let result = extract_iso_entries(&mut image, extract_dir, options, &tracker, &pb);
+        tracker.finish(result)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let mut image = IsoImage::open(file_path)?;
+        let entries = std::mem::take(&mut image.entries);
+
+        // ISO 9660 にはチェックサムがないため、内容を最後まで読めるかだけを確認する
+        for entry in entries.iter().filter(|entry| entry.kind == EntryKind::File) {
+            let result = io::copy(&mut ExtentReader::new(&mut image, entry), &mut io::sink());
+            report(&entry.name, result.map(|_| ()).map_err(Into::into));
+        }
+        Ok(())
+    }
+}
+
+/// エントリの内容（マルチエクステントの場合は各エクステントを連結したもの）を読み込むリーダー
+struct ExtentReader<'a> {
+    file: &'a mut File,
+    block_size: u64,
+    extents: &'a [(u64, u64)],
+    /// 現在のエクステントの未読のバイト数（`None` はまだシークしていない）
+    remaining: Option<u64>,
+}
+
+impl<'a> ExtentReader<'a> {
+    fn new(image: &'a mut IsoImage, entry: &'a IsoEntry) -> Self {
+        Self {
+            file: &mut image.file,
+            block_size: image.block_size,
+            extents: &entry.extents,
+            remaining: None,
+        }
+    }
+}
+
+impl Read for ExtentReader<'_> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let remaining = match self.remaining {
+                Some(remaining) if remaining > 0 => remaining,
+                _ => {
+                    let Some((&(extent, len), rest)) = self.extents.split_first() else {
+                        return Ok(0);
+                    };
+                    self.file.seek(SeekFrom::Start(extent * self.block_size))?;
+                    self.extents = rest;
+                    self.remaining = Some(len);
+                    continue;
+                }
+            };
+
+            let len = buf.len().min(remaining.try_into().unwrap_or(usize::MAX));
+            let n = self.file.read(&mut buf[..len])?;
+            if n == 0 && len > 0 {
+                return Err(io::ErrorKind::UnexpectedEof.into());
+            }
+            self.remaining = Some(remaining - n as u64);
+            return Ok(n);
+        }
+    }
+}
+
+// ディスクイメージのエントリの解凍処理
+fn extract_iso_entries(
+    image: &mut IsoImage,
+    extract_dir: &Path,
+    options: &ExtractOptions,
+    tracker: &LimitTracker,
+    pb: &ProgressBar,
+) -> Result<()> {
+    let mut links = DeferredLinks::new(extract_dir, options.link_policy);
+    let entries = std::mem::take(&mut image.entries);
+
+    for entry in &entries {
+        tracker.start_entry()?;
+
+        // 解凍先の外を指すエントリはスキップ
+        let Some(output_path) = entry_output_path(extract_dir, &entry.name, options) else {
+            pb.inc(1);
+            continue;
+        };
+
+        // プログレスバーのメッセージを更新
+        if let Some(file_name) = output_path.file_name().and_then(|s| s.to_str()) {
+            pb.set_message(format!(
+                "{}",
+                t!("progress.extracting_file", file = file_name)
+            ));
+        }
+
+        match entry.kind {
+            EntryKind::Directory => fs::create_dir_all(&output_path)?,
+            EntryKind::Symlink => {
+                let target = entry.link_target.clone().unwrap_or_default();
+                links.add(LinkKind::Symbolic, &entry.name, output_path, &target);
+            }
+            _ => {
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                let mut output_file = File::create(&output_path)?;
+                let reader = ExtentReader::new(image, entry);
+                io::copy(&mut tracker.reader(reader), &mut output_file)?;
+
+                // ファイル権限を設定 (Unix系のみ)
+                #[cfg(unix)]
+                {
+                    use std::os::unix::fs::PermissionsExt;
+                    if let Some(mode) = entry.mode {
+                        fs::set_permissions(
+                            &output_path,
+                            fs::Permissions::from_mode(mode & 0o7777),
+                        )?;
+                    }
+                }
+            }
+        }
+        pb.inc(1);
+    }
+
+    links.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{read_output, Fixture};
+
+    const BLOCK: usize = SECTOR_SIZE as usize;
+    /// ルートディレクトリを置くセクタ（ボリューム記述子と終端記述子の後）
+    const ROOT: u32 = 20;
+
+    fn record(extent: u32, len: usize, flags: u8, name: &[u8], system_use: &[u8]) -> Vec<u8> {
+        let mut record = vec![0u8; 33];
+        record[2..6].copy_from_slice(&extent.to_le_bytes());
+        record[6..10].copy_from_slice(&extent.to_be_bytes());
+        record[10..14].copy_from_slice(&(len as u32).to_le_bytes());
+        record[14..18].copy_from_slice(&(len as u32).to_be_bytes());
+        // 2024-01-02 03:04:05 UTC
+        record[18..25].copy_from_slice(&[124, 1, 2, 3, 4, 5, 0]);
+        record[25] = flags;
+        record[32] = name.len() as u8;
+        record.extend_from_slice(name);
+        if name.len().is_multiple_of(2) {
+            record.push(0);
+        }
+        record.extend_from_slice(system_use);
+        if record.len() % 2 == 1 {
+            record.push(0);
+        }
+        record[0] = record.len() as u8;
+        record
+    }
+
+    fn file(extent: u32, data: &[u8], name: &str, system_use: &[u8]) -> Vec<u8> {
+        record(extent, data.len(), 0, name.as_bytes(), system_use)
+    }
+
+    /// `.` と `..` のレコードに続けて `records` を並べたディレクトリ
+    fn directory(extent: u32, root_system_use: &[u8], records: &[Vec<u8>]) -> Vec<u8> {
+        let mut data = record(extent, BLOCK, FLAG_DIRECTORY, &[0], root_system_use);
+        data.extend(record(ROOT, BLOCK, FLAG_DIRECTORY, &[1], &[]));
+        data.extend(records.concat());
+        data
+    }
+
+    fn descriptor(kind: u8, root_extent: u32, escape: &[u8]) -> Vec<u8> {
+        let mut descriptor = vec![0u8; BLOCK];
+        descriptor[0] = kind;
+        descriptor[1..6].copy_from_slice(ISO_MAGIC);
+        descriptor[6] = 1;
+        descriptor[88..88 + escape.len()].copy_from_slice(escape);
+        descriptor[128..130].copy_from_slice(&(BLOCK as u16).to_le_bytes());
+        descriptor[156..190].copy_from_slice(&record(
+            root_extent,
+            BLOCK,
+            FLAG_DIRECTORY,
+            &[0],
+            &[],
+        ));
+        descriptor
+    }
+
+    /// 各セクタに内容を置いたイメージ（基本ボリューム記述子はルートが `ROOT` を指す）
+    fn image(sectors: &[(u32, Vec<u8>)]) -> Vec<u8> {
+        let mut sectors = sectors.to_vec();
+        sectors.push((16, descriptor(DESCRIPTOR_PRIMARY, ROOT, b"")));
+        let mut terminator = vec![255u8];
+        terminator.extend_from_slice(ISO_MAGIC);
+        sectors.push((18, terminator));
+
+        let last = sectors.iter().map(|(sector, _)| *sector).max().unwrap();
+        let mut image = vec![0u8; (last as usize + 1) * BLOCK];
+        for (sector, data) in sectors {
+            let start = sector as usize * BLOCK;
+            image[start..start + data.len()].copy_from_slice(&data);
+        }
+        image
+    }
+
+    fn susp(signature: &[u8], data: &[u8]) -> Vec<u8> {
+        [signature, &[data.len() as u8 + 4, 1], data].concat()
+    }
+
+    fn extract(data: &[u8]) -> Fixture {
+        let fixture = Fixture::new("test.iso", data);
+        assert!(IsoExtractor.detect(&fixture.archive, &[]));
+        fixture
+            .extract(&IsoExtractor, &ExtractOptions::default())
+            .unwrap();
+        fixture
+    }
+
+    #[test]
+    fn extracts_primary_names() {
+        let fixture = extract(&image(&[
+            (
+                ROOT,
+                directory(
+                    ROOT,
+                    &[],
+                    &[
+                        file(22, b"hello", "HELLO.TXT;1", &[]),
+                        file(23, b"readme", "README.;1", &[]),
+                        record(21, BLOCK, FLAG_DIRECTORY, b"SUB", &[]),
+                    ],
+                ),
+            ),
+            (
+                21,
+                directory(21, &[], &[file(24, b"deep", "DEEP.TXT;1", &[])]),
+            ),
+            (22, b"hello".to_vec()),
+            (23, b"readme".to_vec()),
+            (24, b"deep".to_vec()),
+        ]));
+
+        assert_eq!(read_output(&fixture.out, "HELLO.TXT").unwrap(), b"hello");
+        assert_eq!(read_output(&fixture.out, "README").unwrap(), b"readme");
+        assert_eq!(read_output(&fixture.out, "SUB/DEEP.TXT").unwrap(), b"deep");
+    }
+
+    #[test]
+    fn prefers_rock_ridge_names() {
+        let sp = susp(b"SP", &[0xbe, 0xef, 0]);
+        let px = |mode: u32| susp(b"PX", &[mode.to_le_bytes(), mode.to_be_bytes()].concat());
+        let name = |name: &str| susp(b"NM", &[&[0], name.as_bytes()].concat());
+        // SL の構成要素（フラグ, 長さ, 名前）
+        let link = susp(
+            b"SL",
+            &[&[0, 0, 4][..], b"data", &[0, 8], b"long.txt"].concat(),
+        );
+
+        let fixture = extract(&image(&[
+            (
+                ROOT,
+                directory(
+                    ROOT,
+                    &sp,
+                    &[
+                        record(21, BLOCK, FLAG_DIRECTORY, b"DATA", &name("data")),
+                        file(
+                            0,
+                            b"",
+                            "LNK.;1",
+                            &[name("link"), px(0o120777), link].concat(),
+                        ),
+                    ],
+                ),
+            ),
+            (
+                21,
+                directory(
+                    21,
+                    &[],
+                    &[file(
+                        22,
+                        b"long",
+                        "LONG.TXT;1",
+                        &[name("long.txt"), px(0o100600)].concat(),
+                    )],
+                ),
+            ),
+            (22, b"long".to_vec()),
+        ]));
+
+        assert_eq!(read_output(&fixture.out, "data/long.txt").unwrap(), b"long");
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            assert_eq!(
+                fs::read_link(fixture.out.join("link")).unwrap(),
+                Path::new("data/long.txt")
+            );
+            let mode = fs::metadata(fixture.out.join("data/long.txt"))
+                .unwrap()
+                .permissions()
+                .mode();
+            assert_eq!(mode & 0o777, 0o600);
+        }
+    }
+
+    #[test]
+    fn uses_joliet_names_without_rock_ridge() {
+        let joliet_name: Vec<u8> = "日本語のファイル.txt;1"
+            .encode_utf16()
+            .flat_map(u16::to_be_bytes)
+            .collect();
+        let joliet_root = record(21, BLOCK, FLAG_DIRECTORY, &[0], &[]);
+        let joliet_file = record(23, 7, 0, &joliet_name, &[]);
+
+        let fixture = extract(&image(&[
+            (17, descriptor(DESCRIPTOR_SUPPLEMENTARY, 21, b"%/E")),
+            (
+                ROOT,
+                directory(ROOT, &[], &[file(23, b"unicode", "FILE.TXT;1", &[])]),
+            ),
+            (
+                21,
+                [
+                    joliet_root,
+                    record(ROOT, BLOCK, FLAG_DIRECTORY, &[1], &[]),
+                    joliet_file,
+                ]
+                .concat(),
+            ),
+            (23, b"unicode".to_vec()),
+        ]));
+
+        assert_eq!(
+            read_output(&fixture.out, "日本語のファイル.txt").unwrap(),
+            b"unicode"
+        );
+        assert!(read_output(&fixture.out, "FILE.TXT").is_none());
+    }
+
+    #[test]
+    fn joins_multi_extent_files() {
+        let fixture = extract(&image(&[
+            (
+                ROOT,
+                directory(
+                    ROOT,
+                    &[],
+                    &[
+                        record(22, 5, FLAG_MULTI_EXTENT, b"BIG.BIN;1", &[]),
+                        record(23, 6, 0, b"BIG.BIN;1", &[]),
+                    ],
+                ),
+            ),
+            (22, b"first".to_vec()),
+            (23, b"second".to_vec()),
+        ]));
+        assert_eq!(
+            read_output(&fixture.out, "BIG.BIN").unwrap(),
+            b"firstsecond"
+        );
+    }
+
+    #[test]
+    fn rejects_directory_loops() {
+        // サブディレクトリがルートディレクトリ自身を指す
+        let data = image(&[(
+            ROOT,
+            directory(
+                ROOT,
+                &[],
+                &[record(ROOT, BLOCK, FLAG_DIRECTORY, b"LOOP", &[])],
+            ),
+        )]);
+        let fixture = Fixture::new("loop.iso", &data);
+        assert!(IsoExtractor.list(&fixture.archive, None).is_err());
+    }
+
+    #[test]
+    fn reports_extents_past_the_end() {
+        let data = image(&[(
+            ROOT,
+            directory(ROOT, &[], &[record(1000, 100, 0, b"GONE.BIN;1", &[])]),
+        )]);
+        let fixture = Fixture::new("short.iso", &data);
+        assert_eq!(
+            fixture.test(&IsoExtractor).unwrap(),
+            vec![("GONE.BIN".to_string(), false)]
+        );
+    }
+
+    #[test]
+    fn rejects_images_without_descriptors() {
+        let fixture = Fixture::new("empty.iso", &vec![0u8; 20 * BLOCK]);
+        assert!(!IsoExtractor.detect(&fixture.archive, &[]));
+        assert!(IsoExtractor.list(&fixture.archive, None).is_err());
+    }
+}
@@ -0,0 +1,1521 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDateTime;
+use delharc::crc::Crc16;
+use delharc::decode::{Decoder, DecoderAny};
+use delharc::CompressionMethod;
+use indicatif::ProgressBar;
+use std::fs;
+use std::io::{self, Read};
+use std::ops::Range;
+use std::path::Path;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+// Import common decode function
+use super::common::{decode_filename, dos_datetime, entry_output_path, new_progress_bar};
+use super::compress_extractor::LzwDecoder;
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::limits::{ExtractLimits, LimitTracker};
+
+/// ARJ のヘッダの先頭に置かれるマジックナンバー
+pub const ARJ_MAGIC: &[u8] = &[0x60, 0xea];
+
+/// ZOO のアーカイブヘッダ・ディレクトリエントリの識別子
+const ZOO_TAG: u32 = 0xfdc4_a7dc;
+
+/// ARC のヘッダの先頭に置かれる印
+const ARC_MARK: u8 = 0x1a;
+
+/// ARJ の基本ヘッダの最大の長さ
+const ARJ_MAX_HEADER_SIZE: usize = 2600;
+
+/// ARJ のヘッダのフラグ
+const ARJ_FLAG_GARBLED: u8 = 0x01;
+const ARJ_FLAG_VOLUME: u8 = 0x04;
+const ARJ_FLAG_EXTFILE: u8 = 0x08;
+
+/// ARJ のファイルの種類
+const ARJ_TYPE_BINARY: u8 = 0;
+const ARJ_TYPE_TEXT: u8 = 1;
+const ARJ_TYPE_DIRECTORY: u8 = 3;
+
+/// ZOO のディレクトリエントリの固定部分の長さ（種類 2 は可変部分が続く）
+const ZOO_DIRENT_LEN: usize = 51;
+const ZOO_DIRENT_V2_LEN: usize = 56;
+
+/// ARC（PAK 拡張）のサブディレクトリの開始と終了
+const ARC_SUBDIR_START: u8 = 30;
+const ARC_SUBDIR_END: u8 = 31;
+
+/// ARC の RLE で繰り返しを表す印
+const RLE_MARK: u8 = 0x90;
+
+/// squeeze 形式でデータの終わりを表す符号
+const SQUEEZE_EOF: usize = 256;
+
+/// ZOO の LZW の符号
+const ZOO_LZW_MAX_BITS: u32 = 13;
+const ZOO_LZW_CLEAR: usize = 256;
+const ZOO_LZW_EOF: usize = 257;
+const ZOO_LZW_FIRST_FREE: usize = 258;
+
+/// LHA と同じ時代のアーカイブ形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LegacyFormat {
+    Arj,
+    Zoo,
+    Arc,
+}
+
+/// エントリのデータに付いているチェックサム
+#[derive(Debug, Clone, Copy)]
+enum Checksum {
+    Crc32(u32),
+    Crc16(u16),
+}
+
+/// アーカイブ内のエントリの情報
+struct LegacyEntry {
+    name: String,
+    kind: EntryKind,
+    size: u64,
+    compressed_size: u64,
+    modified: Option<NaiveDateTime>,
+    method: u8,
+    checksum: Checksum,
+    /// 圧縮データのアーカイブ内の範囲
+    data: Range<usize>,
+    /// ARJ のパスワードでスクランブルされたエントリ
+    garbled: bool,
+    /// ARJ の複数ボリュームにまたがるエントリ
+    split: bool,
+}
+
+impl LegacyEntry {
+    fn to_archive_entry(&self) -> ArchiveEntry {
+        ArchiveEntry {
+            name: self.name.clone(),
+            size: self.size,
+            compressed_size: Some(self.compressed_size),
+            modified: self.modified,
+            kind: self.kind,
+        }
+    }
+}
+
+fn u16_at(data: &[u8], pos: usize) -> io::Result<u16> {
+    data.get(pos..pos + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(truncated)
+}
+
+fn u32_at(data: &[u8], pos: usize) -> io::Result<u32> {
+    data.get(pos..pos + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(truncated)
+}
+
+/// NUL で終わる文字列を取り出す（NUL がなければ末尾まで）
+fn c_string(bytes: &[u8]) -> &[u8] {
+    let len = bytes.iter().position(|&b| b == 0).unwrap_or(bytes.len());
+    &bytes[..len]
+}
+
+/// 名前を Shift_JIS なども考慮して変換し、区切り文字を `/` に揃える
+fn entry_name(bytes: &[u8]) -> String {
+    decode_filename(bytes).replace('\\', "/")
+}
+
+/// アーカイブ内のデータの範囲を求める（ファイルの外を指す場合はエラー）
+fn data_range(data: &[u8], start: usize, len: u64) -> io::Result<Range<usize>> {
+    let end = usize::try_from(len)
+        .ok()
+        .and_then(|len| start.checked_add(len))
+        .filter(|&end| end <= data.len())
+        .ok_or_else(truncated)?;
+    Ok(start..end)
+}
+
+/// ARJ の基本ヘッダを1つ読み、ヘッダの内容と拡張ヘッダの後の位置を返す
+/// （アーカイブの終わりを表す空のヘッダの場合は `None`）
+fn read_arj_header(data: &[u8], pos: usize) -> io::Result<(Option<&[u8]>, usize)> {
+    if data.get(pos..pos + 2) != Some(ARJ_MAGIC) {
+        return Err(invalid_data("arj: bad header"));
+    }
+    let size = usize::from(u16_at(data, pos + 2)?);
+    if size == 0 {
+        return Ok((None, pos + 4));
+    }
+    if size > ARJ_MAX_HEADER_SIZE {
+        return Err(invalid_data("arj: header too large"));
+    }
+
+    let header = data.get(pos + 4..pos + 4 + size).ok_or_else(truncated)?;
+    if crc32fast::hash(header) != u32_at(data, pos + 4 + size)? {
+        return Err(invalid_data("arj: header CRC mismatch"));
+    }
+
+    // 拡張ヘッダは使わないため読み飛ばす
+    let mut next = pos + 4 + size + 4;
+    loop {
+        let ext_size = usize::from(u16_at(data, next)?);
+        next += 2;
+        if ext_size == 0 {
+            break;
+        }
+        next += ext_size + 4;
+    }
+    Ok((Some(header), next))
+}
+
+fn parse_arj(data: &[u8]) -> io::Result<Vec<LegacyEntry>> {
+    // 最初のヘッダはアーカイブ全体の情報（メインヘッダ）
+    let (main_header, mut pos) = read_arj_header(data, 0)?;
+    if main_header.is_none() {
+        return Err(invalid_data("arj: missing main header"));
+    }
+
+    let mut entries = Vec::new();
+    while let (Some(header), next) = read_arj_header(data, pos)? {
+        let first_size = usize::from(header[0]);
+        if first_size < 24 || first_size > header.len() {
+            return Err(invalid_data("arj: bad local header"));
+        }
+        let flags = header[4];
+        let file_type = header[6];
+        let compressed_size = u64::from(u32_at(header, 12)?);
+        let range = data_range(data, next, compressed_size)?;
+        pos = range.end;
+
+        let kind = match file_type {
+            ARJ_TYPE_BINARY | ARJ_TYPE_TEXT => EntryKind::File,
+            ARJ_TYPE_DIRECTORY => EntryKind::Directory,
+            // ボリュームラベルなどはファイルとして展開しない
+            _ => continue,
+        };
+
+        entries.push(LegacyEntry {
+            name: entry_name(c_string(&header[first_size..])),
+            kind,
+            size: u64::from(u32_at(header, 16)?),
+            compressed_size,
+            modified: dos_datetime(u32_at(header, 8)?),
+            method: header[5],
+            checksum: Checksum::Crc32(u32_at(header, 20)?),
+            data: range,
+            garbled: flags & ARJ_FLAG_GARBLED != 0,
+            split: flags & (ARJ_FLAG_VOLUME | ARJ_FLAG_EXTFILE) != 0,
+        });
+    }
+    Ok(entries)
+}
+
+fn parse_zoo(data: &[u8]) -> io::Result<Vec<LegacyEntry>> {
+    if u32_at(data, 20)? != ZOO_TAG {
+        return Err(invalid_data("zoo: bad header"));
+    }
+
+    let mut entries = Vec::new();
+    let mut pos = u32_at(data, 24)? as usize;
+    loop {
+        let dirent = data.get(pos..pos + ZOO_DIRENT_LEN).ok_or_else(truncated)?;
+        if u32_at(dirent, 0)? != ZOO_TAG {
+            return Err(invalid_data("zoo: bad directory entry"));
+        }
+
+        // 最後のエントリは次を指さない空のエントリ
+        let next = u32_at(dirent, 6)? as usize;
+        if next == 0 {
+            break;
+        }
+        if next <= pos {
+            return Err(invalid_data("zoo: corrupt directory chain"));
+        }
+
+        let mut name = entry_name(c_string(&dirent[38..51]));
+        if dirent[4] == 2 {
+            // 長いファイル名とディレクトリ名は可変部分に入っている
+            let var_len = usize::from(u16_at(data, pos + ZOO_DIRENT_LEN)?);
+            let start = pos + ZOO_DIRENT_V2_LEN;
+            let var = data.get(start..start + var_len).ok_or_else(truncated)?;
+            if var.len() >= 2 {
+                let (name_len, dir_len) = (usize::from(var[0]), usize::from(var[1]));
+                let long_name = var.get(2..2 + name_len).ok_or_else(truncated)?;
+                let dir = var
+                    .get(2 + name_len..2 + name_len + dir_len)
+                    .ok_or_else(truncated)?;
+                if !c_string(long_name).is_empty() {
+                    name = entry_name(c_string(long_name));
+                }
+                let dir = entry_name(c_string(dir));
+                if !dir.is_empty() {
+                    name = format!("{}/{}", dir.trim_end_matches('/'), name);
+                }
+            }
+        }
+
+        // 削除済みのエントリは残っていても展開しない
+        let deleted = dirent[30] != 0;
+        if !deleted {
+            let compressed_size = u64::from(u32_at(dirent, 24)?);
+            let date = u32::from(u16_at(dirent, 14)?);
+            let time = u32::from(u16_at(dirent, 16)?);
+            entries.push(LegacyEntry {
+                name,
+                kind: EntryKind::File,
+                size: u64::from(u32_at(dirent, 20)?),
+                compressed_size,
+                modified: dos_datetime(date << 16 | time),
+                method: dirent[5],
+                checksum: Checksum::Crc16(u16_at(dirent, 18)?),
+                data: data_range(data, u32_at(dirent, 10)? as usize, compressed_size)?,
+                garbled: false,
+                split: false,
+            });
+        }
+        pos = next;
+    }
+    Ok(entries)
+}
+
+fn parse_arc(data: &[u8]) -> io::Result<Vec<LegacyEntry>> {
+    let mut entries = Vec::new();
+    // PAK 拡張のサブディレクトリの中にいる場合の、ディレクトリ名
+    let mut dirs: Vec<String> = Vec::new();
+    let mut pos = 0;
+
+    // 終わりの印がなくても、ファイルの終わりで一覧を終える
+    while pos < data.len() {
+        if data[pos] != ARC_MARK {
+            return Err(invalid_data("arc: bad header"));
+        }
+        let method = *data.get(pos + 1).ok_or_else(truncated)?;
+        match method {
+            0 if dirs.is_empty() => break,
+            0 | ARC_SUBDIR_END => {
+                dirs.pop();
+                pos += 2;
+                continue;
+            }
+            _ => {}
+        }
+
+        // 最も古い形式（method 1）には展開後のサイズがない
+        let header_len = if method == 1 { 25 } else { 29 };
+        let header = data.get(pos..pos + header_len).ok_or_else(truncated)?;
+        let name = entry_name(c_string(&header[2..15]));
+        let compressed_size = u64::from(u32_at(header, 15)?);
+        let date = u32::from(u16_at(header, 19)?);
+        let time = u32::from(u16_at(header, 21)?);
+        let size = if method == 1 {
+            compressed_size
+        } else {
+            u64::from(u32_at(header, 25)?)
+        };
+        let path = match dirs.last() {
+            Some(dir) => format!("{}/{}", dir, name),
+            None => name,
+        };
+        let range = data_range(data, pos + header_len, compressed_size)?;
+
+        if method == ARC_SUBDIR_START {
+            // サブディレクトリのデータは、中のエントリの並び
+            entries.push(LegacyEntry {
+                name: path.clone(),
+                kind: EntryKind::Directory,
+                size: 0,
+                compressed_size: 0,
+                modified: dos_datetime(date << 16 | time),
+                method,
+                checksum: Checksum::Crc16(0),
+                data: range.start..range.start,
+                garbled: false,
+                split: false,
+            });
+            dirs.push(path);
+            pos = range.start;
+            continue;
+        }
+        pos = range.end;
+
+        // PAK のコメントなどの情報ブロックは読み飛ばす
+        if method >= 20 {
+            continue;
+        }
+        entries.push(LegacyEntry {
+            name: path,
+            kind: EntryKind::File,
+            size,
+            compressed_size,
+            modified: dos_datetime(date << 16 | time),
+            method,
+            checksum: Checksum::Crc16(u16_at(header, 23)?),
+            data: range,
+            garbled: false,
+            split: false,
+        });
+    }
+    Ok(entries)
+}
+
+/// ビット単位でデータを読むリーダー
+struct BitReader<'a> {
+    data: &'a [u8],
+    /// 次に読むビットの位置
+    pos: usize,
+}
+
+impl<'a> BitReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        Self { data, pos: 0 }
+    }
+
+    fn bit(&self, lsb_first: bool) -> io::Result<u32> {
+        let byte = *self.data.get(self.pos / 8).ok_or_else(truncated)?;
+        let shift = if lsb_first {
+            self.pos % 8
+        } else {
+            7 - self.pos % 8
+        };
+        Ok(u32::from(byte >> shift) & 1)
+    }
+
+    /// 各バイトの上位ビットから順に `count` ビット読み、先に読んだビットを上位に置く
+    fn read_msb(&mut self, count: u32) -> io::Result<u32> {
+        let mut value = 0;
+        for _ in 0..count {
+            value = value << 1 | self.bit(false)?;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+
+    /// 各バイトの下位ビットから順に `count` ビット読み、先に読んだビットを下位に置く
+    fn read_lsb(&mut self, count: u32) -> io::Result<u32> {
+        let mut value = 0;
+        for i in 0..count {
+            value |= self.bit(true)? << i;
+            self.pos += 1;
+        }
+        Ok(value)
+    }
+}
+
+/// ARJ の method 4 の可変長の値を読む
+///
+/// 1 が続く数で幅（`start` から `stop` まで）を決め、その幅の値を読み足す。
+fn arj_fastest_value(bits: &mut BitReader, start: u32, stop: u32) -> io::Result<usize> {
+    let mut base = 0;
+    let mut width = start;
+    while width < stop {
+        if bits.read_msb(1)? == 0 {
+            break;
+        }
+        base += 1 << width;
+        width += 1;
+    }
+    Ok(base + bits.read_msb(width)? as usize)
+}
+
+/// ARJ の method 4（最速）を展開する
+fn decode_arj_fastest(input: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let mut bits = BitReader::new(input);
+    let mut output = Vec::new();
+
+    while output.len() < size {
+        let len = arj_fastest_value(&mut bits, 0, 7)?;
+        if len == 0 {
+            output.push(bits.read_msb(8)? as u8);
+            continue;
+        }
+
+        let distance = arj_fastest_value(&mut bits, 9, 13)? + 1;
+        if distance > output.len() {
+            return Err(invalid_data("arj: corrupt input"));
+        }
+        for _ in 0..(len + 2).min(size - output.len()) {
+            output.push(output[output.len() - distance]);
+        }
+    }
+    Ok(output)
+}
+
+/// LHA の静的ハフマン符号化（-lh5- や -lh7- と同じ）で圧縮されたデータを展開する
+fn decode_lh(method: CompressionMethod, input: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    const CHUNK_SIZE: usize = 64 * 1024;
+
+    let mut decoder = DecoderAny::new_from_compression(method, input);
+    let mut output = Vec::new();
+    while output.len() < size {
+        let start = output.len();
+        output.resize(start + CHUNK_SIZE.min(size - start), 0);
+        decoder.fill_buffer(&mut output[start..])?;
+    }
+    Ok(output)
+}
+
+/// ZOO の LZW（最大 13 ビット、ビットの区切りなし）を展開する
+fn decode_zoo_lzw(input: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    let table_size = 1 << ZOO_LZW_MAX_BITS;
+    let mut prefix = vec![0u16; table_size];
+    let mut suffix = vec![0u8; table_size];
+
+    let mut bits = BitReader::new(input);
+    let mut n_bits = 9;
+    let mut max_code = 1 << n_bits;
+    let mut free_code = ZOO_LZW_FIRST_FREE;
+    let mut old_code: Option<usize> = None;
+    let mut fin_char = 0;
+    let mut output = Vec::new();
+    let mut stack = Vec::new();
+
+    loop {
+        let code = bits.read_lsb(n_bits)? as usize;
+        match code {
+            ZOO_LZW_EOF => break,
+            ZOO_LZW_CLEAR => {
+                n_bits = 9;
+                max_code = 1 << n_bits;
+                free_code = ZOO_LZW_FIRST_FREE;
+                old_code = None;
+                continue;
+            }
+            _ => {}
+        }
+
+        // 辞書の初期化直後の符号は1文字そのもの
+        let Some(old) = old_code else {
+            if code >= 256 {
+                return Err(invalid_data("zoo: corrupt input"));
+            }
+            fin_char = code as u8;
+            output.push(fin_char);
+            old_code = Some(code);
+            continue;
+        };
+
+        // 辞書をたどると逆順に得られるため、スタックに積んでから出力する
+        let mut current = code;
+        if current >= free_code {
+            if current > free_code {
+                return Err(invalid_data("zoo: corrupt input"));
+            }
+            // KwKwK: まだ辞書にない、直前の文字列 + その先頭文字
+            stack.push(fin_char);
+            current = old;
+        }
+        while current >= 256 {
+            stack.push(suffix[current]);
+            current = usize::from(prefix[current]);
+        }
+        fin_char = current as u8;
+        stack.push(fin_char);
+        output.extend(stack.drain(..).rev());
+        if output.len() > size {
+            return Err(invalid_data("zoo: data larger than recorded size"));
+        }
+
+        if free_code < table_size {
+            prefix[free_code] = old as u16;
+            suffix[free_code] = fin_char;
+            free_code += 1;
+            if free_code >= max_code && n_bits < ZOO_LZW_MAX_BITS {
+                n_bits += 1;
+                max_code <<= 1;
+            }
+        }
+        old_code = Some(code);
+    }
+    Ok(output)
+}
+
+/// ARC の squeeze 形式（ハフマン符号化）を展開する
+fn decode_squeeze(input: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    let node_count = usize::from(u16_at(input, 0)?);
+    if node_count > 256 {
+        return Err(invalid_data("arc: corrupt squeeze table"));
+    }
+    let table = input.get(2..2 + node_count * 4).ok_or_else(truncated)?;
+    let nodes: Vec<[i16; 2]> = table
+        .chunks_exact(4)
+        .map(|n| {
+            [
+                i16::from_le_bytes([n[0], n[1]]),
+                i16::from_le_bytes([n[2], n[3]]),
+            ]
+        })
+        .collect();
+
+    let mut output = Vec::new();
+    if nodes.is_empty() {
+        return Ok(output);
+    }
+
+    let mut bits = BitReader::new(&input[2 + table.len()..]);
+    let mut node = 0;
+    // 終わりの符号がなくても、データの終わりで展開を終える
+    while let Ok(bit) = bits.read_lsb(1) {
+        let child = nodes[node][bit as usize];
+        if child >= 0 {
+            node = child as usize;
+            if node >= nodes.len() {
+                return Err(invalid_data("arc: corrupt squeeze table"));
+            }
+            continue;
+        }
+
+        // 葉は -(値 + 1) で表される
+        let value = usize::from(!child as u16);
+        if value == SQUEEZE_EOF {
+            break;
+        }
+        if value > SQUEEZE_EOF {
+            return Err(invalid_data("arc: corrupt squeeze table"));
+        }
+        output.push(value as u8);
+        if output.len() > limit {
+            return Err(invalid_data("arc: data larger than recorded size"));
+        }
+        node = 0;
+    }
+    Ok(output)
+}
+
+/// ARC の RLE（0x90 の後に繰り返し回数）を展開する
+fn decode_rle90(input: &[u8], limit: usize) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    let mut last = 0;
+    let mut repeat = false;
+
+    for &byte in input {
+        if repeat {
+            repeat = false;
+            if byte == 0 {
+                // 0x90 0x00 は 0x90 そのもの（直前の文字は変わらない）
+                output.push(RLE_MARK);
+            } else {
+                output.resize(output.len() + usize::from(byte) - 1, last);
+            }
+        } else if byte == RLE_MARK {
+            repeat = true;
+        } else {
+            last = byte;
+            output.push(byte);
+        }
+
+        if output.len() > limit {
+            return Err(invalid_data("arc: data larger than recorded size"));
+        }
+    }
+    Ok(output)
+}
+
+/// compress と同じ LZW で圧縮されたデータを展開する（`limit` バイトを超えたらエラー）
+fn decode_lzw(input: &[u8], max_bits: u32, limit: usize) -> io::Result<Vec<u8>> {
+    let mut output = Vec::new();
+    LzwDecoder::raw(input, max_bits, true)
+        .take(limit as u64 + 1)
+        .read_to_end(&mut output)?;
+    if output.len() > limit {
+        return Err(invalid_data("arc: data larger than recorded size"));
+    }
+    Ok(output)
+}
+
+fn decode_arc(method: u8, input: &[u8], size: usize) -> io::Result<Vec<u8>> {
+    // RLE の前のデータは、0x90 を2バイトで表すため最大で2倍になる
+    let packed_limit = size.saturating_mul(2).saturating_add(2);
+    match method {
+        1 | 2 => Ok(input.to_vec()),
+        3 => decode_rle90(input, size),
+        4 => decode_rle90(&decode_squeeze(input, packed_limit)?, size),
+        8 => {
+            // 先頭の1バイトが符号の最大ビット数
+            let (&max_bits, input) = input.split_first().ok_or_else(truncated)?;
+            let max_bits = u32::from(max_bits);
+            if !(9..=16).contains(&max_bits) {
+                return Err(invalid_data("arc: unsupported number of bits"));
+            }
+            decode_rle90(&decode_lzw(input, max_bits, packed_limit)?, size)
+        }
+        9 => decode_lzw(input, 13, size),
+        _ => Err(invalid_data(&format!(
+            "arc: unsupported compression method {}",
+            method
+        ))),
+    }
+}
+
+fn truncated() -> io::Error {
+    io::Error::from(io::ErrorKind::UnexpectedEof)
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+pub struct LegacyExtractor {
+    format: LegacyFormat,
+}
+
+impl LegacyExtractor {
+    pub fn new(format: LegacyFormat) -> Self {
+        Self { format }
+    }
+
+    /// アーカイブファイルを読み込み、エントリの一覧を作る
+    fn open(&self, file_path: &Path) -> Result<(Vec<u8>, Vec<LegacyEntry>)> {
+        let data = fs::read(file_path)?;
+        let entries = match self.format {
+            LegacyFormat::Arj => parse_arj(&data)?,
+            LegacyFormat::Zoo => parse_zoo(&data)?,
+            LegacyFormat::Arc => parse_arc(&data)?,
+        };
+        Ok((data, entries))
+    }
+
+    /// エントリを展開し、チェックサムを検証する
+    fn decode(&self, data: &[u8], entry: &LegacyEntry) -> Result<Vec<u8>> {
+        if entry.garbled {
+            return Err(anyhow!(t!("ui.error_arj_garbled_unsupported").to_string()));
+        }
+        if entry.split {
+            return Err(anyhow!(
+                t!("ui.error_arj_multivolume_unsupported").to_string()
+            ));
+        }
+
+        let input = &data[entry.data.clone()];
+        let size = usize::try_from(entry.size)?;
+        let output = match (self.format, entry.method) {
+            (LegacyFormat::Arj, 0) | (LegacyFormat::Zoo, 0) => input.to_vec(),
+            // ARJ の method 1〜3 は辞書の大きさが異なるだけで、-lh7- と同じ符号化
+            (LegacyFormat::Arj, 1..=3) => decode_lh(CompressionMethod::Lh7, input, size)?,
+            (LegacyFormat::Arj, 4) => decode_arj_fastest(input, size)?,
+            (LegacyFormat::Zoo, 1) => decode_zoo_lzw(input, size)?,
+            (LegacyFormat::Zoo, 2) => decode_lh(CompressionMethod::Lh5, input, size)?,
+            (LegacyFormat::Arc, method) => decode_arc(method, input, size)?,
+            (_, method) => {
+                return Err(invalid_data(&format!(
+                    "{}: unsupported compression method {}",
+                    self.name().to_lowercase(),
+                    method
+                ))
+                .into())
+            }
+        };
+
+        if output.len() != size {
+            return Err(invalid_data("size mismatch").into());
+        }
+        let valid = match entry.checksum {
+            Checksum::Crc32(crc) => crc32fast::hash(&output) == crc,
+            Checksum::Crc16(crc) => {
+                let mut sum = Crc16::default();
+                sum.digest(&output);
+                sum.sum16() == crc
+            }
+        };
+        if !valid {
+            return Err(invalid_data("CRC mismatch").into());
+        }
+        Ok(output)
+    }
+}
+
+impl Extractor for LegacyExtractor {
+    fn name(&self) -> &'static str {
+        match self.format {
+            LegacyFormat::Arj => "ARJ",
+            LegacyFormat::Zoo => "ZOO",
+            LegacyFormat::Arc => "ARC",
+        }
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        match self.format {
+            LegacyFormat::Arj => &["arj"],
+            LegacyFormat::Zoo => &["zoo"],
+            LegacyFormat::Arc => &["arc"],
+        }
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        match self.format {
+            LegacyFormat::Arj => header.starts_with(ARJ_MAGIC),
+            LegacyFormat::Zoo => u32_at(header, 20).is_ok_and(|tag| tag == ZOO_TAG),
+            LegacyFormat::Arc => {
+                // 先頭の1バイトだけでは弱いため、圧縮方式とファイル名も確かめる
+                let (Some(&ARC_MARK), Some(&method)) = (header.first(), header.get(1)) else {
+                    return false;
+                };
+                let Some(name) = header.get(2..15) else {
+                    return false;
+                };
+                let name_len = name.iter().position(|&b| b == 0);
+                matches!(method, 1..=11 | ARC_SUBDIR_START)
+                    && name_len.is_some_and(|len| len > 0)
+                    && c_string(name).iter().all(|&b| b >= 0x20)
+            }
+        }
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        let (_, entries) = self.open(file_path)?;
+        Ok(entries.iter().map(LegacyEntry::to_archive_entry).collect())
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let (data, entries) = self.open(file_path)?;
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+
+        // プログレスバーの設定
+        let message = match self.format {
+            LegacyFormat::Arj => t!("progress.extracting_arj"),
+            LegacyFormat::Zoo => t!("progress.extracting_zoo"),
+            LegacyFormat::Arc => t!("progress.extracting_arc"),
+        };
+        let pb = new_progress_bar(entries.len() as u64, message.to_string());
+
+        let result = self.extract_entries(&data, &entries, extract_dir, options, &tracker, &pb);
+        tracker.finish(result)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let (data, entries) = self.open(file_path)?;
+        // 展開したデータはメモリに置くため、ヘッダのサイズを偽ったアーカイブで
+        // メモリを使い果たさないよう、解凍時と同じ上限で確かめてから展開する
+        let tracker = LimitTracker::new(ExtractLimits::default(), file_path)?;
+
+        // 各エントリを展開して、サイズと CRC を検証する
+        for entry in &entries {
+            tracker.start_entry()?;
+            let result = match entry.kind {
+                EntryKind::Directory => Ok(()),
+                _ => {
+                    tracker.reserve(entry.size)?;
+                    self.decode(&data, entry).map(|_| ())
+                }
+            };
+            report(&entry.name, result);
+        }
+        Ok(())
+    }
+}
+
+impl LegacyExtractor {
+    fn extract_entries(
+        &self,
+        data: &[u8],
+        entries: &[LegacyEntry],
+        extract_dir: &Path,
+        options: &ExtractOptions,
+        tracker: &LimitTracker,
+        pb: &ProgressBar,
+    ) -> Result<()> {
+        for entry in entries {
+            tracker.start_entry()?;
+
+            // 解凍先の外を指すエントリはスキップ
+            let Some(output_path) = entry_output_path(extract_dir, &entry.name, options) else {
+                pb.inc(1);
+                continue;
+            };
+
+            // プログレスバーのメッセージを更新
+            if let Some(file_name) = output_path.file_name().and_then(|s| s.to_str()) {
+                pb.set_message(format!(
+                    "{}",
+                    t!("progress.extracting_file", file = file_name)
+                ));
+            }
+
+            if entry.kind == EntryKind::Directory {
+                fs::create_dir_all(&output_path)?;
+            } else {
+                // 展開前にヘッダのサイズで上限を確認する（展開後のサイズは decode で検証）
+                tracker.reserve(entry.size)?;
+                let contents = self.decode(data, entry)?;
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+                fs::write(&output_path, contents)?;
+            }
+            pb.inc(1);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::limits::LimitExceeded;
+    use crate::extractors::test_support::{read_output, Fixture};
+    use std::collections::HashMap;
+
+    /// 上位ビットから順、または下位ビットから順にビットを詰める
+    #[derive(Default)]
+    struct BitWriter {
+        bytes: Vec<u8>,
+        len: usize,
+    }
+
+    impl BitWriter {
+        fn push(&mut self, bit: bool, lsb_first: bool) {
+            if self.len.is_multiple_of(8) {
+                self.bytes.push(0);
+            }
+            if bit {
+                let shift = if lsb_first {
+                    self.len % 8
+                } else {
+                    7 - self.len % 8
+                };
+                *self.bytes.last_mut().unwrap() |= 1 << shift;
+            }
+            self.len += 1;
+        }
+
+        fn write_msb(&mut self, value: usize, count: u32) {
+            for i in (0..count).rev() {
+                self.push(value >> i & 1 != 0, false);
+            }
+        }
+
+        fn write_lsb(&mut self, value: usize, count: u32) {
+            for i in 0..count {
+                self.push(value >> i & 1 != 0, true);
+            }
+        }
+    }
+
+    /// 繰り返しの多い、単語を並べたテキスト
+    fn sample(len: usize) -> Vec<u8> {
+        const WORDS: [&str; 8] = [
+            "archive ",
+            "legacy ",
+            "zoo ",
+            "arj ",
+            "squeeze ",
+            "\u{90}",
+            "run\n",
+            "aaaaaaaa ",
+        ];
+        let mut state = 12345u32;
+        let mut data = Vec::new();
+        while data.len() < len {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12345);
+            data.extend_from_slice(WORDS[(state >> 16) as usize % WORDS.len()].as_bytes());
+        }
+        data.truncate(len);
+        data
+    }
+
+    fn crc16(data: &[u8]) -> u16 {
+        let mut sum = Crc16::default();
+        sum.digest(data);
+        sum.sum16()
+    }
+
+    fn encode_rle90(data: &[u8]) -> Vec<u8> {
+        let mut output = Vec::new();
+        let mut i = 0;
+        while i < data.len() {
+            let byte = data[i];
+            let run = data[i..]
+                .iter()
+                .take(255)
+                .take_while(|&&b| b == byte)
+                .count();
+            if byte == RLE_MARK {
+                output.extend_from_slice(&[RLE_MARK, 0]);
+                i += 1;
+            } else if run >= 3 {
+                output.extend_from_slice(&[byte, RLE_MARK, run as u8]);
+                i += run;
+            } else {
+                output.push(byte);
+                i += 1;
+            }
+        }
+        output
+    }
+
+    /// 出現する値と終わりの符号を葉に持つ平衡木で、squeeze 形式に符号化する
+    fn encode_squeeze(data: &[u8]) -> Vec<u8> {
+        fn build(
+            symbols: &[usize],
+            nodes: &mut Vec<[i16; 2]>,
+            codes: &mut HashMap<usize, Vec<bool>>,
+            path: Vec<bool>,
+        ) -> i16 {
+            if let [symbol] = symbols {
+                codes.insert(*symbol, path);
+                return !(*symbol as i16);
+            }
+            let index = nodes.len();
+            nodes.push([0, 0]);
+            let (left, right) = symbols.split_at(symbols.len() / 2);
+            for (side, half) in [left, right].into_iter().enumerate() {
+                let mut child_path = path.clone();
+                child_path.push(side == 1);
+                nodes[index][side] = build(half, nodes, codes, child_path);
+            }
+            index as i16
+        }
+
+        let mut symbols: Vec<usize> = data.iter().map(|&b| usize::from(b)).collect();
+        symbols.push(SQUEEZE_EOF);
+        symbols.sort_unstable();
+        symbols.dedup();
+        let mut nodes = Vec::new();
+        let mut codes = HashMap::new();
+        build(&symbols, &mut nodes, &mut codes, Vec::new());
+
+        let mut output = (nodes.len() as u16).to_le_bytes().to_vec();
+        for node in &nodes {
+            output.extend_from_slice(&node[0].to_le_bytes());
+            output.extend_from_slice(&node[1].to_le_bytes());
+        }
+        let mut bits = BitWriter::default();
+        for symbol in data.iter().map(|&b| usize::from(b)).chain([SQUEEZE_EOF]) {
+            for &bit in &codes[&symbol] {
+                bits.push(bit, true);
+            }
+        }
+        output.extend_from_slice(&bits.bytes);
+        output
+    }
+
+    /// ZOO の LZW の符号を、展開側と同じ規則で幅を広げながら書き出す
+    struct ZooCodes {
+        bits: BitWriter,
+        n_bits: u32,
+        free_code: usize,
+        first: bool,
+    }
+
+    impl ZooCodes {
+        fn new() -> Self {
+            Self {
+                bits: BitWriter::default(),
+                n_bits: 9,
+                free_code: ZOO_LZW_FIRST_FREE,
+                first: true,
+            }
+        }
+
+        fn emit(&mut self, code: usize) {
+            self.bits.write_lsb(code, self.n_bits);
+            if code == ZOO_LZW_CLEAR {
+                self.n_bits = 9;
+                self.free_code = ZOO_LZW_FIRST_FREE;
+                self.first = true;
+                return;
+            }
+            // 展開側は2つ目以降の符号を読むたびに辞書を1つ増やす
+            if !self.first && self.free_code < 1 << ZOO_LZW_MAX_BITS {
+                self.free_code += 1;
+                if self.free_code >= 1 << self.n_bits && self.n_bits < ZOO_LZW_MAX_BITS {
+                    self.n_bits += 1;
+                }
+            }
+            self.first = false;
+        }
+    }
+
+    fn encode_zoo_lzw(data: &[u8]) -> Vec<u8> {
+        let mut codes = ZooCodes::new();
+        let mut dictionary: HashMap<(usize, u8), usize> = HashMap::new();
+        let mut next_code = ZOO_LZW_FIRST_FREE;
+        let mut current: Option<usize> = None;
+        for &byte in data {
+            let Some(prefix) = current else {
+                current = Some(usize::from(byte));
+                continue;
+            };
+            if let Some(&code) = dictionary.get(&(prefix, byte)) {
+                current = Some(code);
+                continue;
+            }
+            codes.emit(prefix);
+            if next_code < 1 << ZOO_LZW_MAX_BITS {
+                dictionary.insert((prefix, byte), next_code);
+                next_code += 1;
+            }
+            current = Some(usize::from(byte));
+        }
+        if let Some(prefix) = current {
+            codes.emit(prefix);
+        }
+        codes.emit(ZOO_LZW_EOF);
+        codes.bits.bytes
+    }
+
+    /// ARJ の method 4 の可変長の値を書く（`arj_fastest_value` の逆）
+    fn write_arj_value(bits: &mut BitWriter, value: usize, start: u32, stop: u32) {
+        let mut base = 0;
+        let mut width = start;
+        while width < stop && value >= base + (1 << width) {
+            bits.write_msb(1, 1);
+            base += 1 << width;
+            width += 1;
+        }
+        if width < stop {
+            bits.write_msb(0, 1);
+        }
+        bits.write_msb(value - base, width);
+    }
+
+    /// 直前に出現した3バイトの並びだけを探す、単純な ARJ の method 4 の圧縮
+    fn encode_arj_fastest(data: &[u8]) -> Vec<u8> {
+        const MAX_DISTANCE: usize = 15872;
+        const MAX_LEN: usize = 256;
+
+        let mut bits = BitWriter::default();
+        let mut last_seen: HashMap<&[u8], usize> = HashMap::new();
+        let mut pos = 0;
+        while pos < data.len() {
+            let key = data.get(pos..pos + 3);
+            let candidate = key.and_then(|key| last_seen.get(key).copied());
+            let matched = candidate
+                .filter(|&start| pos - start <= MAX_DISTANCE)
+                .map(|start| {
+                    let len = (0..MAX_LEN.min(data.len() - pos))
+                        .take_while(|&i| data[start + i] == data[pos + i])
+                        .count();
+                    (pos - start, len)
+                });
+
+            let step = match matched {
+                Some((distance, len)) if len >= 3 => {
+                    write_arj_value(&mut bits, len - 2, 0, 7);
+                    write_arj_value(&mut bits, distance - 1, 9, 13);
+                    len
+                }
+                _ => {
+                    write_arj_value(&mut bits, 0, 0, 7);
+                    bits.write_msb(usize::from(data[pos]), 8);
+                    1
+                }
+            };
+            for i in pos..pos + step {
+                if let Some(key) = data.get(i..i + 3) {
+                    last_seen.insert(key, i);
+                }
+            }
+            pos += step;
+        }
+        bits.bytes
+    }
+
+    #[test]
+    fn rle90_round_trip() {
+        assert_eq!(
+            decode_rle90(&[b'A', RLE_MARK, 5, RLE_MARK, 0, b'B'], 16).unwrap(),
+            b"AAAAA\x90B"
+        );
+
+        let data = [sample(20_000), vec![b'z'; 600], vec![RLE_MARK; 3]].concat();
+        let packed = encode_rle90(&data);
+        assert!(packed.len() < data.len());
+        assert_eq!(decode_rle90(&packed, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn rle90_rejects_oversized_output() {
+        let error = decode_rle90(&[b'A', RLE_MARK, 255], 10).unwrap_err();
+        assert!(error.to_string().contains("larger"), "{error}");
+    }
+
+    #[test]
+    fn squeeze_round_trip() {
+        let data = sample(30_000);
+        let squeezed = encode_squeeze(&data);
+        assert_eq!(decode_squeeze(&squeezed, data.len()).unwrap(), data);
+
+        // ARC の method 4 は RLE の後に squeeze したもの
+        let packed = encode_squeeze(&encode_rle90(&data));
+        assert_eq!(decode_arc(4, &packed, data.len()).unwrap(), data);
+
+        assert!(decode_squeeze(&[0, 0], 0).unwrap().is_empty());
+    }
+
+    #[test]
+    fn squeeze_rejects_corrupt_tables() {
+        let error = decode_squeeze(&[0x01, 0x01], 10).unwrap_err();
+        assert!(error.to_string().contains("squeeze table"), "{error}");
+
+        // 子のノードが表の外を指す
+        let error = decode_squeeze(&[1, 0, 5, 0, 5, 0, 0xff], 10).unwrap_err();
+        assert!(error.to_string().contains("squeeze table"), "{error}");
+
+        // 葉の値が終わりの符号より大きい
+        let leaf = (!300i16).to_le_bytes();
+        let error =
+            decode_squeeze(&[1, 0, leaf[0], leaf[1], leaf[0], leaf[1], 0xff], 10).unwrap_err();
+        assert!(error.to_string().contains("squeeze table"), "{error}");
+
+        assert!(decode_squeeze(&[2, 0, 0, 0], 10).is_err());
+        assert!(decode_squeeze(&encode_squeeze(b"too long"), 4).is_err());
+    }
+
+    #[test]
+    fn zoo_lzw_round_trip() {
+        // 辞書が埋まるまで符号の幅が 13 ビットまで広がる
+        let data = sample(200_000);
+        let packed = encode_zoo_lzw(&data);
+        assert!(packed.len() < data.len() / 2);
+        assert_eq!(decode_zoo_lzw(&packed, data.len()).unwrap(), data);
+
+        assert_eq!(
+            decode_zoo_lzw(&encode_zoo_lzw(b"abababab"), 8).unwrap(),
+            b"abababab"
+        );
+    }
+
+    #[test]
+    fn zoo_lzw_clear_resets_dictionary() {
+        let mut codes = ZooCodes::new();
+        for code in [usize::from(b'a'), usize::from(b'b'), 258, ZOO_LZW_CLEAR] {
+            codes.emit(code);
+        }
+        for code in [usize::from(b'c'), 258, ZOO_LZW_EOF] {
+            codes.emit(code);
+        }
+        assert_eq!(decode_zoo_lzw(&codes.bits.bytes, 16).unwrap(), b"ababccc");
+    }
+
+    #[test]
+    fn zoo_lzw_rejects_corrupt_input() {
+        let mut codes = ZooCodes::new();
+        codes.emit(300);
+        assert!(decode_zoo_lzw(&codes.bits.bytes, 16).is_err());
+
+        let mut codes = ZooCodes::new();
+        codes.emit(usize::from(b'a'));
+        codes.emit(400);
+        let error = decode_zoo_lzw(&codes.bits.bytes, 16).unwrap_err();
+        assert!(error.to_string().contains("corrupt"), "{error}");
+
+        let error = decode_zoo_lzw(&encode_zoo_lzw(b"too long"), 4).unwrap_err();
+        assert!(error.to_string().contains("larger"), "{error}");
+
+        // 終わりの符号がないまま入力が尽きる
+        let packed = encode_zoo_lzw(b"truncated");
+        assert!(decode_zoo_lzw(&packed[..packed.len() - 2], 9).is_err());
+    }
+
+    #[test]
+    fn arj_fastest_round_trip() {
+        let data = [sample(40_000), vec![0; 1000]].concat();
+        let packed = encode_arj_fastest(&data);
+        assert!(packed.len() < data.len() / 2);
+        assert_eq!(decode_arj_fastest(&packed, data.len()).unwrap(), data);
+    }
+
+    #[test]
+    fn arj_fastest_rejects_corrupt_input() {
+        // 出力より前を指す一致
+        let mut bits = BitWriter::default();
+        write_arj_value(&mut bits, 0, 0, 7);
+        bits.write_msb(usize::from(b'a'), 8);
+        write_arj_value(&mut bits, 1, 0, 7);
+        write_arj_value(&mut bits, 5, 9, 13);
+        let error = decode_arj_fastest(&bits.bytes, 4).unwrap_err();
+        assert!(error.to_string().contains("corrupt"), "{error}");
+
+        let packed = encode_arj_fastest(b"truncated input");
+        assert!(decode_arj_fastest(&packed[..packed.len() / 2], 15).is_err());
+    }
+
+    /// ARC のエントリ（ヘッダとデータ）
+    fn arc_entry(name: &str, method: u8, packed: &[u8], original: &[u8]) -> Vec<u8> {
+        let mut entry = vec![ARC_MARK, method];
+        let mut name_field = [0u8; 13];
+        name_field[..name.len()].copy_from_slice(name.as_bytes());
+        entry.extend_from_slice(&name_field);
+        entry.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+        entry.extend_from_slice(&0x5822u16.to_le_bytes());
+        entry.extend_from_slice(&0x1883u16.to_le_bytes());
+        entry.extend_from_slice(&crc16(original).to_le_bytes());
+        entry.extend_from_slice(&(original.len() as u32).to_le_bytes());
+        entry.extend_from_slice(packed);
+        entry
+    }
+
+    #[test]
+    fn extracts_arc_entries() {
+        let text = sample(5000);
+        let data = [
+            arc_entry("STORED.TXT", 2, b"plain", b"plain"),
+            arc_entry("PACKED.TXT", 3, &encode_rle90(&text), &text),
+            arc_entry(
+                "SQUEEZED.TXT",
+                4,
+                &encode_squeeze(&encode_rle90(&text)),
+                &text,
+            ),
+            arc_entry("../EVIL.TXT", 2, b"evil", b"evil"),
+            vec![ARC_MARK, 0],
+        ]
+        .concat();
+        let fixture = Fixture::new("test.arc", &data);
+        let extractor = LegacyExtractor::new(LegacyFormat::Arc);
+        assert!(extractor.detect(&fixture.archive, &data));
+
+        fixture
+            .extract(&extractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(
+            read_output(&fixture.out, "STORED.TXT"),
+            Some(b"plain".to_vec())
+        );
+        assert_eq!(read_output(&fixture.out, "PACKED.TXT"), Some(text.clone()));
+        assert_eq!(read_output(&fixture.out, "SQUEEZED.TXT"), Some(text));
+        assert_eq!(read_output(&fixture.root.join("work"), "EVIL.TXT"), None);
+    }
+
+    #[test]
+    fn reports_arc_crc_mismatch() {
+        let mut entry = arc_entry("BAD.TXT", 2, b"data", b"data");
+        let last = entry.len() - 1;
+        entry[last] ^= 0x20;
+        let data = [entry, vec![ARC_MARK, 0]].concat();
+        let fixture = Fixture::new("test.arc", &data);
+        let extractor = LegacyExtractor::new(LegacyFormat::Arc);
+
+        assert_eq!(
+            fixture.test(&extractor).unwrap(),
+            vec![("BAD.TXT".to_string(), false)]
+        );
+        let error = fixture
+            .extract(&extractor, &ExtractOptions::default())
+            .unwrap_err();
+        assert!(error.to_string().contains("CRC"), "{error}");
+
+        // データがファイルの外を指す
+        let mut data = arc_entry("SHORT.TXT", 2, b"data", b"data");
+        data.truncate(data.len() - 2);
+        let fixture = Fixture::new("test.arc", &data);
+        assert!(extractor.list(&fixture.archive, None).is_err());
+    }
+
+    /// ARJ の基本ヘッダ（CRC と空の拡張ヘッダ付き）
+    fn arj_header(header: &[u8]) -> Vec<u8> {
+        let mut block = ARJ_MAGIC.to_vec();
+        block.extend_from_slice(&(header.len() as u16).to_le_bytes());
+        block.extend_from_slice(header);
+        block.extend_from_slice(&crc32fast::hash(header).to_le_bytes());
+        block.extend_from_slice(&[0, 0]);
+        block
+    }
+
+    fn arj_entry(name: &str, flags: u8, method: u8, packed: &[u8], original: &[u8]) -> Vec<u8> {
+        let size = original.len() as u32;
+        arj_entry_sized(name, flags, method, packed, size, crc32fast::hash(original))
+    }
+
+    /// ヘッダに記録するサイズと CRC を指定した ARJ のエントリ
+    fn arj_entry_sized(
+        name: &str,
+        flags: u8,
+        method: u8,
+        packed: &[u8],
+        size: u32,
+        crc: u32,
+    ) -> Vec<u8> {
+        let mut header = vec![30, 11, 1, 0, flags, method, ARJ_TYPE_BINARY, 0];
+        header.extend_from_slice(&0x5822_1883u32.to_le_bytes());
+        header.extend_from_slice(&(packed.len() as u32).to_le_bytes());
+        header.extend_from_slice(&size.to_le_bytes());
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&[0; 6]);
+        header.extend_from_slice(name.as_bytes());
+        header.extend_from_slice(&[0, 0]);
+        [arj_header(&header), packed.to_vec()].concat()
+    }
+
+    fn arj_archive(entries: &[Vec<u8>]) -> Vec<u8> {
+        let mut main = vec![30, 11, 1, 0, 0, 0, 2, 0];
+        main.extend_from_slice(&[0; 22]);
+        main.extend_from_slice(b"test.arj\0\0");
+        [
+            arj_header(&main),
+            entries.concat(),
+            [ARJ_MAGIC, &[0, 0]].concat(),
+        ]
+        .concat()
+    }
+
+    #[test]
+    fn extracts_arj_entries() {
+        let text = sample(20_000);
+        let data = arj_archive(&[
+            arj_entry("stored.txt", 0, 0, b"plain", b"plain"),
+            arj_entry("dir\\fastest.txt", 0, 4, &encode_arj_fastest(&text), &text),
+            arj_entry("garbled.txt", ARJ_FLAG_GARBLED, 0, b"xxxx", b"data"),
+        ]);
+        let fixture = Fixture::new("test.arj", &data);
+        let extractor = LegacyExtractor::new(LegacyFormat::Arj);
+
+        assert_eq!(
+            fixture.test(&extractor).unwrap(),
+            vec![
+                ("stored.txt".to_string(), true),
+                ("dir/fastest.txt".to_string(), true),
+                ("garbled.txt".to_string(), false),
+            ]
+        );
+        assert!(fixture
+            .extract(&extractor, &ExtractOptions::default())
+            .is_err());
+        assert_eq!(
+            read_output(&fixture.out, "stored.txt"),
+            Some(b"plain".to_vec())
+        );
+        assert_eq!(read_output(&fixture.out, "dir/fastest.txt"), Some(text));
+    }
+
+    #[test]
+    fn rejects_corrupt_arj_headers() {
+        let mut data = arj_archive(&[arj_entry("a.txt", 0, 0, b"a", b"a")]);
+        let extractor = LegacyExtractor::new(LegacyFormat::Arj);
+
+        // メインヘッダ（空のアーカイブから終わりの4バイトを除いた部分）に続く、
+        // ローカルヘッダの圧縮方式を書き換える
+        let main_len = arj_archive(&[]).len() - 4;
+        data[main_len + 4 + 5] ^= 0xff;
+        let fixture = Fixture::new("test.arj", &data);
+        let error = extractor.list(&fixture.archive, None).unwrap_err();
+        assert!(error.to_string().contains("header CRC"), "{error}");
+
+        let mut data = arj_archive(&[]);
+        data[2..4].copy_from_slice(&u16::MAX.to_le_bytes());
+        let fixture = Fixture::new("test.arj", &data);
+        let error = extractor.list(&fixture.archive, None).unwrap_err();
+        assert!(error.to_string().contains("too large"), "{error}");
+
+        let fixture = Fixture::new("test.arj", &[ARJ_MAGIC, &[0, 0]].concat());
+        assert!(extractor.list(&fixture.archive, None).is_err());
+    }
+
+    #[test]
+    fn checks_limits_before_decoding_in_test_mode() {
+        // 数バイトのデータを 4 GiB に展開できると偽ったエントリ
+        let data = arj_archive(&[arj_entry_sized("bomb.bin", 0, 1, b"xx", u32::MAX, 0)]);
+        let fixture = Fixture::new("bomb.arj", &data);
+        let extractor = LegacyExtractor::new(LegacyFormat::Arj);
+
+        let error = fixture.test(&extractor).unwrap_err();
+        assert_eq!(
+            error.downcast_ref::<LimitExceeded>(),
+            Some(&LimitExceeded::Ratio(ExtractLimits::default().max_ratio))
+        );
+        let error = fixture
+            .extract(&extractor, &ExtractOptions::default())
+            .unwrap_err();
+        assert!(error.downcast_ref::<LimitExceeded>().is_some(), "{error}");
+    }
+
+    /// tests/fixtures/legacy の実際のアーカイブの多くに入っている LICENSE のサイズと CRC-32
+    const LICENSE_SIZE: usize = 11357;
+    const LICENSE_CRC32: u32 = 0x7b5d_04bc;
+
+    /// 入っているエントリの（名前, サイズ, CRC-32）
+    type ExpectedEntries = &'static [(&'static str, usize, u32)];
+
+    #[test]
+    fn extracts_real_archives() {
+        let cases: [(&str, &[u8], LegacyFormat, ExpectedEntries); 6] = [
+            // ZOO の LZW
+            (
+                "default.zoo",
+                include_bytes!("../../tests/fixtures/legacy/default.zoo"),
+                LegacyFormat::Zoo,
+                &[("license", LICENSE_SIZE, LICENSE_CRC32)],
+            ),
+            // ARC の squeeze（method 4）と RLE（method 3）
+            (
+                "cpm.arc",
+                include_bytes!("../../tests/fixtures/legacy/cpm.arc"),
+                LegacyFormat::Arc,
+                &[
+                    ("DDTZ.COM", 9984, 0x92e6_a411),
+                    ("READ.COM", 128, 0x06f9_e3af),
+                ],
+            ),
+            // ARC の crunch（method 8）
+            (
+                "crunch.arc",
+                include_bytes!("../../tests/fixtures/legacy/crunch.arc"),
+                LegacyFormat::Arc,
+                &[("LICENSE", LICENSE_SIZE, LICENSE_CRC32)],
+            ),
+            // ARC の squash（method 9）
+            (
+                "squashed.arc",
+                include_bytes!("../../tests/fixtures/legacy/squashed.arc"),
+                LegacyFormat::Arc,
+                &[("LICENSE", LICENSE_SIZE, LICENSE_CRC32)],
+            ),
+            // ARJ の method 1
+            (
+                "method1.arj",
+                include_bytes!("../../tests/fixtures/legacy/method1.arj"),
+                LegacyFormat::Arj,
+                &[("LICENSE", LICENSE_SIZE, LICENSE_CRC32)],
+            ),
+            // ARJ の method 4（最速）
+            (
+                "method4.arj",
+                include_bytes!("../../tests/fixtures/legacy/method4.arj"),
+                LegacyFormat::Arj,
+                &[("LICENSE", LICENSE_SIZE, LICENSE_CRC32)],
+            ),
+        ];
+
+        for (file_name, data, format, expected) in cases {
+            let fixture = Fixture::new(file_name, data);
+            let extractor = LegacyExtractor::new(format);
+            assert!(extractor.detect(&fixture.archive, data), "{file_name}");
+
+            let listed: Vec<(String, u64)> = extractor
+                .list(&fixture.archive, None)
+                .unwrap()
+                .into_iter()
+                .map(|entry| (entry.name, entry.size))
+                .collect();
+            let names: Vec<(String, u64)> = expected
+                .iter()
+                .map(|(name, size, _)| (name.to_string(), *size as u64))
+                .collect();
+            assert_eq!(listed, names, "{file_name}");
+
+            assert!(
+                fixture.test(&extractor).unwrap().iter().all(|(_, ok)| *ok),
+                "{file_name}"
+            );
+            fixture
+                .extract(&extractor, &ExtractOptions::default())
+                .unwrap();
+            for (name, size, crc) in expected {
+                let contents = read_output(&fixture.out, name).unwrap();
+                assert_eq!(contents.len(), *size, "{file_name}: {name}");
+                assert_eq!(crc32fast::hash(&contents), *crc, "{file_name}: {name}");
+            }
+        }
+    }
+}
@@ -1,101 +1,274 @@
 use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use chrono::Local;
+use delharc::{LhaDecodeReader, TimestampResult};
+use indicatif::ProgressBar;
 use std::fs::{self, File};
-use std::io::Write;
+use std::io::Cursor;
 use std::path::Path;
 
 // Import the i18n macro
 use rust_i18n::t;
 
 // Import common decode function
-use super::common::decode_filename_as_pathbuf;
-
-pub fn extract_lha(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    fs::create_dir_all(extract_dir)?;
-
-    // プログレスバーの設定
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_lha")));
-
-    // アーカイブファイルを読み込み
-    let archive_data = std::fs::read(file_path)?;
-    let mut cursor = std::io::Cursor::new(archive_data);
-    let mut extracted_files = 0;
-
-    // delharc 0.6.1の新しいAPIを使用してアーカイブ全体を処理
-    loop {
-        // 各ファイルエントリを解凍
-        match delharc::LhaDecodeReader::new(&mut cursor) {
-            Ok(mut decoder) => {
-                let header = decoder.header();
-                let filename = decode_filename_as_pathbuf(&header.filename);
-                let output_path = extract_dir.join(&filename);
-
-                pb.set_message(format!(
-                    "{}",
-                    t!(
-                        "progress.extracting_file",
-                        file = filename.to_string_lossy()
-                    )
-                ));
-
-                if header.is_directory() {
-                    // ディレクトリの作成
-                    fs::create_dir_all(&output_path)?;
-                } else {
-                    // ファイルの解凍
-                    if let Some(parent) = output_path.parent() {
-                        fs::create_dir_all(parent)?;
-                    }
+use super::common::{decode_filename, entry_output_path, new_spinner};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::limits::LimitTracker;
+use super::sfx_extractor::{sfx_offset, SfxFormat};
 
-                    // ファイル内容を解凍して書き込み
-                    let mut content = Vec::new();
-                    std::io::copy(&mut decoder, &mut content)?;
+/// LHA のヘッダの種類（レベル）が置かれる位置
+const LEVEL_OFFSET: usize = 20;
+/// レベル 0 のヘッダの、先頭の2バイトを除いた最小の長さ（ファイル名が空の場合）
+const LEVEL0_MIN_SIZE: usize = 22;
+/// レベル 2 のヘッダの固定長部分の長さ
+const LEVEL2_MIN_SIZE: usize = 26;
 
-                    let mut output_file = File::create(&output_path)?;
-                    output_file.write_all(&content)?;
-                }
+/// `header` の先頭が LHA のヘッダかどうかを判定する
+///
+/// 圧縮方式IDだけでは他のファイルと偶然一致することがあるため、
+/// ヘッダのレベルと、レベルごとのヘッダの長さ・チェックサムも確かめる。
+pub fn is_lha_header(header: &[u8]) -> bool {
+    // ヘッダの 2 バイト目から "-lh5-" "-lzs-" "-lhd-" などの圧縮方式IDが続く
+    let Some(method) = header.get(2..7) else {
+        return false;
+    };
+    if !(method.starts_with(b"-l")
+        && matches!(method[2], b'h' | b'z')
+        && method[3].is_ascii_alphanumeric()
+        && method[4] == b'-')
+    {
+        return false;
+    }
+
+    match header.get(LEVEL_OFFSET) {
+        // レベル 0・1 は先頭の1バイトがヘッダの長さ、次の1バイトがその範囲の合計
+        Some(0 | 1) => {
+            let size = usize::from(header[0]);
+            size >= LEVEL0_MIN_SIZE
+                && header.get(2..2 + size).is_some_and(|bytes| {
+                    bytes.iter().fold(0u8, |sum, &b| sum.wrapping_add(b)) == header[1]
+                })
+        }
+        // レベル 2 は先頭の2バイトがヘッダ全体の長さ、レベル 3 は常に 4（ワード長）
+        Some(2) => usize::from(u16::from_le_bytes([header[0], header[1]])) >= LEVEL2_MIN_SIZE,
+        Some(3) => u16::from_le_bytes([header[0], header[1]]) == 4,
+        _ => false,
+    }
+}
 
-                extracted_files += 1;
-                pb.inc(1);
+pub struct LhaExtractor;
 
-                // デコーダー処理後の位置を確認
-                let current_pos = cursor.position();
-                let total_len = cursor.get_ref().len() as u64;
+impl LhaExtractor {
+    fn open(file_path: &Path) -> Result<LhaDecodeReader<Cursor<Vec<u8>>>> {
+        // アーカイブファイルを読み込み
+        let archive_data = fs::read(file_path)?;
+        let mut cursor = Cursor::new(archive_data);
+        // 自己解凍形式は、実行ファイルの中の LHA の先頭から読む
+        if let Some(offset) = sfx_offset(file_path, SfxFormat::Lha)? {
+            cursor.set_position(offset);
+        }
+        LhaDecodeReader::new(cursor)
+            .map_err(|_| anyhow::anyhow!("有効なLHAファイルが見つかりませんでした"))
+    }
+}
 
-                // アーカイブの終端に達している場合は終了
-                if current_pos >= total_len {
-                    break;
-                }
-            }
-            Err(_) => {
-                // エラーまたはアーカイブの終端
-                if extracted_files == 0 {
-                    pb.finish_with_message("LHA extraction failed");
-                    return Err(anyhow::anyhow!("有効なLHAファイルが見つかりませんでした"));
-                }
-                // すでにファイルを解凍している場合は正常終了
+impl Extractor for LhaExtractor {
+    fn name(&self) -> &'static str {
+        "LHA"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["lha", "lzh"]
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        is_lha_header(header)
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        let mut decoder = Self::open(file_path)?;
+        let mut entries = Vec::new();
+
+        loop {
+            let header = decoder.header();
+            entries.push(ArchiveEntry {
+                name: decode_filename(&header.filename),
+                size: header.original_size,
+                compressed_size: Some(header.compressed_size),
+                modified: match header.parse_last_modified() {
+                    TimestampResult::Naive(modified) => Some(modified),
+                    TimestampResult::Utc(modified) => {
+                        Some(modified.with_timezone(&Local).naive_local())
+                    }
+                    TimestampResult::None => None,
+                },
+                kind: if header.is_directory() {
+                    EntryKind::Directory
+                } else {
+                    EntryKind::File
+                },
+            });
+
+            if !decoder.next_file().map_err(std::io::Error::from)? {
                 break;
             }
         }
+        Ok(entries)
     }
 
-    if extracted_files > 0 {
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        // プログレスバーの設定
+        let pb = new_spinner(format!("{}", t!("progress.extracting_lha")));
+
+        let mut decoder = Self::open(file_path)?;
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+
+        let result = extract_lha_entries(&mut decoder, extract_dir, options, &tracker, &pb);
+        tracker.finish(result)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let mut decoder = Self::open(file_path)?;
+
+        loop {
+            // ヘッダの CRC は next_file で、データの CRC16 は crc_check で検証する
+            let name = decode_filename(&decoder.header().filename);
+            let result = if decoder.header().is_directory() {
+                Ok(())
+            } else {
+                std::io::copy(&mut decoder, &mut std::io::sink())
+                    .map_err(anyhow::Error::from)
+                    .and_then(|_| Ok(decoder.crc_check().map_err(std::io::Error::from)?))
+                    .map(|_| ())
+            };
+            report(&name, result);
+
+            if !decoder.next_file().map_err(std::io::Error::from)? {
+                break;
+            }
+        }
         Ok(())
-    } else {
-        pb.finish_with_message("LHA extraction failed");
-        Err(anyhow::anyhow!(
-            "LHAアーカイブからファイルを解凍できませんでした"
-        ))
     }
 }
 
-pub fn extract_lzh(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    // LZH形式もLHA形式と同じ処理
-    extract_lha(file_path, extract_dir)
+// LHAエントリの解凍処理（delharc 0.6.1 の next_file でアーカイブ全体を処理）
+fn extract_lha_entries(
+    decoder: &mut LhaDecodeReader<Cursor<Vec<u8>>>,
+    extract_dir: &Path,
+    options: &ExtractOptions,
+    tracker: &LimitTracker,
+    pb: &ProgressBar,
+) -> Result<()> {
+    loop {
+        // 各ファイルエントリを解凍
+        tracker.start_entry()?;
+        let header = decoder.header();
+        let filename = decode_filename(&header.filename);
+
+        pb.set_message(format!(
+            "{}",
+            t!("progress.extracting_file", file = filename)
+        ));
+
+        // 解凍先の外を指すエントリはスキップ
+        if let Some(output_path) = entry_output_path(extract_dir, &filename, options) {
+            if header.is_directory() {
+                // ディレクトリの作成
+                fs::create_dir_all(&output_path)?;
+            } else {
+                // ファイルの解凍
+                if let Some(parent) = output_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                // ファイル内容を解凍して書き込み
+                let mut output_file = File::create(&output_path)?;
+                std::io::copy(&mut tracker.reader(&mut *decoder), &mut output_file)?;
+            }
+        }
+
+        pb.inc(1);
+
+        // 次のエントリへ。終端以降のゴミデータなどで読めない場合も、
+        // すでにファイルを解凍しているので正常終了とする
+        match decoder.next_file() {
+            Ok(true) => {}
+            Ok(false) | Err(_) => break,
+        }
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{lha_archive, read_output, Fixture};
+
+    #[test]
+    fn detects_level0_headers() {
+        assert!(is_lha_header(&lha_archive("hello.txt", b"hello")));
+
+        // チェックサムが合わない
+        let mut data = lha_archive("hello.txt", b"hello");
+        data[1] = data[1].wrapping_add(1);
+        assert!(!is_lha_header(&data));
+
+        // ヘッダの長さがファイルより長い
+        let data = lha_archive("hello.txt", b"hello");
+        assert!(!is_lha_header(&data[..20]));
+        assert!(!is_lha_header(&data[..data.len() / 2]));
+    }
+
+    #[test]
+    fn detects_level2_and_level3_headers() {
+        let mut level2 = vec![0u8; 32];
+        level2[..2].copy_from_slice(&26u16.to_le_bytes());
+        level2[2..7].copy_from_slice(b"-lh5-");
+        level2[LEVEL_OFFSET] = 2;
+        assert!(is_lha_header(&level2));
+        level2[..2].copy_from_slice(&10u16.to_le_bytes());
+        assert!(!is_lha_header(&level2));
+
+        let mut level3 = level2.clone();
+        level3[..2].copy_from_slice(&4u16.to_le_bytes());
+        level3[LEVEL_OFFSET] = 3;
+        assert!(is_lha_header(&level3));
+    }
+
+    #[test]
+    fn rejects_text_that_looks_like_a_method_id() {
+        assert!(!is_lha_header(b"# -lh5- is the default method of LHA\n"));
+        let mut data = lha_archive("hello.txt", b"hello");
+        data[LEVEL_OFFSET] = 7;
+        assert!(!is_lha_header(&data));
+        data[LEVEL_OFFSET] = 0;
+        data[4] = b'x';
+        assert!(!is_lha_header(&data));
+    }
+
+    #[test]
+    fn extracts_stored_entries() {
+        let fixture = Fixture::new("test.lzh", &lha_archive("hello.txt", b"hello"));
+        fixture
+            .extract(&LhaExtractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(
+            read_output(&fixture.out, "hello.txt"),
+            Some(b"hello".to_vec())
+        );
+        assert_eq!(
+            fixture.test(&LhaExtractor).unwrap(),
+            vec![("hello.txt".to_string(), true)]
+        );
+    }
 }
@@ -0,0 +1,317 @@
+use anyhow::Result;
+use indicatif::HumanBytes;
+use std::cell::{Cell, RefCell};
+use std::fmt;
+use std::io::{self, Read};
+use std::path::Path;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use super::volumes::VolumeSet;
+
+const GIB: u64 = 1024 * 1024 * 1024;
+
+pub const DEFAULT_MAX_TOTAL_SIZE: u64 = 100 * GIB;
+pub const DEFAULT_MAX_RATIO: u64 = 1000;
+pub const DEFAULT_MAX_ENTRIES: u64 = 1_000_000;
+
+/// 圧縮率の判定を始める展開後のサイズ
+/// 小さなファイルは圧縮率が極端になりやすいため、ある程度展開してから判定する
+const RATIO_CHECK_THRESHOLD: u64 = 64 * 1024 * 1024;
+
+/// 解凍爆弾対策の上限値（0 は無制限）
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ExtractLimits {
+    /// 展開後の合計サイズ
+    pub max_total_size: u64,
+    /// 1エントリあたりの展開後のサイズ
+    pub max_entry_size: u64,
+    /// アーカイブのファイルサイズに対する展開後の合計サイズの比率
+    pub max_ratio: u64,
+    /// エントリ数
+    pub max_entries: u64,
+}
+
+impl Default for ExtractLimits {
+    fn default() -> Self {
+        Self {
+            max_total_size: DEFAULT_MAX_TOTAL_SIZE,
+            max_entry_size: 0,
+            max_ratio: DEFAULT_MAX_RATIO,
+            max_entries: DEFAULT_MAX_ENTRIES,
+        }
+    }
+}
+
+/// 上限を超えたため解凍を中止したことを表すエラー
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LimitExceeded {
+    TotalSize(u64),
+    EntrySize(u64),
+    Ratio(u64),
+    Entries(u64),
+}
+
+impl fmt::Display for LimitExceeded {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match *self {
+            LimitExceeded::TotalSize(limit) => {
+                t!("ui.error_limit_total_size", limit = HumanBytes(limit))
+            }
+            LimitExceeded::EntrySize(limit) => {
+                t!("ui.error_limit_entry_size", limit = HumanBytes(limit))
+            }
+            LimitExceeded::Ratio(limit) => t!("ui.error_limit_ratio", limit = limit),
+            LimitExceeded::Entries(limit) => t!("ui.error_limit_entries", limit = limit),
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for LimitExceeded {}
+
+/// 1回の解凍処理での展開サイズ・エントリ数を数え、上限を超えたら中止させる
+///
+/// 上限超過はライブラリのエラー型を経由すると種類が分からなくなるため、
+/// 最初に検出した超過を記録しておき `finish` で `LimitExceeded` として返す。
+pub struct LimitTracker {
+    limits: ExtractLimits,
+    archive_size: u64,
+    total_size: Cell<u64>,
+    entries: Cell<u64>,
+    exceeded: RefCell<Option<LimitExceeded>>,
+}
+
+impl LimitTracker {
+    pub fn new(limits: ExtractLimits, file_path: &Path) -> Result<Self> {
+        // 分割アーカイブは全ボリュームの合計サイズに対する比率で判定する
+        let archive_size = match VolumeSet::find(file_path)? {
+            Some(volume_set) => volume_set.total_size()?,
+            None => file_path.metadata()?.len(),
+        };
+        Ok(Self {
+            limits,
+            archive_size: archive_size.max(1),
+            total_size: Cell::new(0),
+            entries: Cell::new(0),
+            exceeded: RefCell::new(None),
+        })
+    }
+
+    /// エントリを1つ解凍し始める前に呼び出す
+    pub fn start_entry(&self) -> Result<(), LimitExceeded> {
+        let entries = self.entries.get() + 1;
+        self.entries.set(entries);
+
+        if self.limits.max_entries != 0 && entries > self.limits.max_entries {
+            return Err(self.exceed(LimitExceeded::Entries(self.limits.max_entries)));
+        }
+        Ok(())
+    }
+
+    /// ヘッダに記録された展開後のサイズを事前に確認し、合計に加算する
+    /// （ライブラリ側でサイズが保証され、書き込みを数えられない形式用）
+    pub fn reserve(&self, entry_size: u64) -> Result<(), LimitExceeded> {
+        self.add(entry_size, entry_size)
+    }
+
+    /// `reserve` で確認したサイズを、実際に書き込まれたサイズに置き換える
+    /// （ヘッダのサイズと異なるデータが書き込まれた場合も、実際のサイズで上限を確認する）
+    pub fn settle(&self, reserved: u64, written: u64) -> Result<(), LimitExceeded> {
+        if written <= reserved {
+            self.total_size
+                .set(self.total_size.get() - (reserved - written));
+            return Ok(());
+        }
+        self.add(written - reserved, written)
+    }
+
+    /// エントリの展開データを数えながら読み込むリーダーを返す
+    pub fn reader<R: Read>(&self, inner: R) -> LimitedReader<'_, R> {
+        LimitedReader {
+            inner,
+            tracker: self,
+            entry_size: 0,
+        }
+    }
+
+    /// 解凍処理の結果を受け取り、途中で上限を超えていた場合は `LimitExceeded` を返す
+    pub fn finish(&self, result: Result<()>) -> Result<()> {
+        match self.exceeded.borrow_mut().take() {
+            Some(exceeded) => Err(exceeded.into()),
+            None => result,
+        }
+    }
+
+    fn add(&self, bytes: u64, entry_size: u64) -> Result<(), LimitExceeded> {
+        let total_size = self.total_size.get() + bytes;
+        self.total_size.set(total_size);
+
+        let limits = &self.limits;
+        if limits.max_entry_size != 0 && entry_size > limits.max_entry_size {
+            return Err(self.exceed(LimitExceeded::EntrySize(limits.max_entry_size)));
+        }
+        if limits.max_total_size != 0 && total_size > limits.max_total_size {
+            return Err(self.exceed(LimitExceeded::TotalSize(limits.max_total_size)));
+        }
+        if limits.max_ratio != 0
+            && total_size > RATIO_CHECK_THRESHOLD
+            && total_size / self.archive_size > limits.max_ratio
+        {
+            return Err(self.exceed(LimitExceeded::Ratio(limits.max_ratio)));
+        }
+        Ok(())
+    }
+
+    fn exceed(&self, exceeded: LimitExceeded) -> LimitExceeded {
+        self.exceeded.borrow_mut().get_or_insert(exceeded);
+        exceeded
+    }
+}
+
+/// 読み込んだバイト数を `LimitTracker` に加算するリーダー
+pub struct LimitedReader<'a, R> {
+    inner: R,
+    tracker: &'a LimitTracker,
+    entry_size: u64,
+}
+
+impl<R: Read> Read for LimitedReader<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.entry_size += n as u64;
+        self.tracker
+            .add(n as u64, self.entry_size)
+            .map_err(io::Error::other)?;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::extractor::{ExtractOptions, Extractor};
+    use crate::extractors::tar_extractor::{TarCompression, TarExtractor};
+    use crate::extractors::test_support::{Fixture, TarBuilder};
+
+    fn extract_with(limits: ExtractLimits) -> Result<()> {
+        let data = TarBuilder::new()
+            .file("a.txt", &[b'a'; 1000])
+            .file("b.txt", &[b'b'; 1000])
+            .file("c.txt", &[b'c'; 1000])
+            .build();
+        let fixture = Fixture::new("test.tar", &data);
+        let options = ExtractOptions {
+            limits,
+            ..ExtractOptions::default()
+        };
+        TarExtractor::new(TarCompression::None).extract(&fixture.archive, &fixture.out, &options)
+    }
+
+    fn exceeded(result: Result<()>) -> Option<LimitExceeded> {
+        result.err()?.downcast_ref::<LimitExceeded>().copied()
+    }
+
+    #[test]
+    fn default_limits_allow_small_archives() {
+        assert!(extract_with(ExtractLimits::default()).is_ok());
+    }
+
+    #[test]
+    fn stops_at_entry_count() {
+        let limits = ExtractLimits {
+            max_entries: 2,
+            ..ExtractLimits::default()
+        };
+        assert_eq!(
+            exceeded(extract_with(limits)),
+            Some(LimitExceeded::Entries(2))
+        );
+    }
+
+    #[test]
+    fn stops_at_entry_size() {
+        let limits = ExtractLimits {
+            max_entry_size: 999,
+            ..ExtractLimits::default()
+        };
+        assert_eq!(
+            exceeded(extract_with(limits)),
+            Some(LimitExceeded::EntrySize(999))
+        );
+    }
+
+    #[test]
+    fn stops_at_total_size() {
+        let limits = ExtractLimits {
+            max_total_size: 2500,
+            ..ExtractLimits::default()
+        };
+        assert_eq!(
+            exceeded(extract_with(limits)),
+            Some(LimitExceeded::TotalSize(2500))
+        );
+    }
+
+    #[test]
+    fn zero_disables_limits() {
+        let limits = ExtractLimits {
+            max_total_size: 0,
+            max_entry_size: 0,
+            max_ratio: 0,
+            max_entries: 0,
+        };
+        assert!(extract_with(limits).is_ok());
+    }
+
+    #[test]
+    fn stops_at_ratio_after_threshold() {
+        let fixture = Fixture::new("bomb.bin", &[0; 1024]);
+        let limits = ExtractLimits {
+            max_ratio: 1000,
+            ..ExtractLimits::default()
+        };
+        let tracker = LimitTracker::new(limits, &fixture.archive).unwrap();
+
+        // 判定を始めるサイズまでは圧縮率が高くても止めない
+        let mut reader = tracker.reader(io::repeat(0).take(RATIO_CHECK_THRESHOLD));
+        io::copy(&mut reader, &mut io::sink()).unwrap();
+
+        let mut reader = tracker.reader(io::repeat(0).take(1024 * 1024));
+        assert!(io::copy(&mut reader, &mut io::sink()).is_err());
+        assert_eq!(
+            exceeded(tracker.finish(Ok(()))),
+            Some(LimitExceeded::Ratio(1000))
+        );
+    }
+
+    #[test]
+    fn reserve_counts_toward_total() {
+        let fixture = Fixture::new("test.bin", &[0; 16]);
+        let limits = ExtractLimits {
+            max_total_size: 100,
+            ..ExtractLimits::default()
+        };
+        let tracker = LimitTracker::new(limits, &fixture.archive).unwrap();
+        assert!(tracker.reserve(60).is_ok());
+        assert_eq!(tracker.reserve(60), Err(LimitExceeded::TotalSize(100)));
+    }
+
+    #[test]
+    fn settle_uses_written_size() {
+        let fixture = Fixture::new("test.bin", &[0; 16]);
+        let limits = ExtractLimits {
+            max_total_size: 100,
+            max_entry_size: 70,
+            ..ExtractLimits::default()
+        };
+        let tracker = LimitTracker::new(limits, &fixture.archive).unwrap();
+
+        // 書き込まれたサイズがヘッダより小さければ、差分は合計から除く
+        tracker.reserve(60).unwrap();
+        tracker.settle(60, 20).unwrap();
+        tracker.reserve(60).unwrap();
+        assert_eq!(tracker.settle(60, 80), Err(LimitExceeded::EntrySize(70)));
+    }
+}
@@ -0,0 +1,422 @@
+use anyhow::Result;
+use log::warn;
+use std::ffi::OsStr;
+use std::fs;
+use std::path::{Component, Path, PathBuf};
+use walkdir::WalkDir;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use super::common::{passes_through_symlink, sanitize_entry_path, SanitizedPath};
+use super::extractor::LinkPolicy;
+
+/// Unix のファイルモードの種別部分とシンボリックリンクの値（S_IFMT / S_IFLNK）
+const S_IFMT: u32 = 0o170000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Unix のファイルモードがシンボリックリンクを表すかどうか
+pub fn is_symlink_mode(mode: u32) -> bool {
+    mode & S_IFMT == S_IFLNK
+}
+
+/// リンクの種類
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LinkKind {
+    /// シンボリックリンク（リンク先はリンク自身のディレクトリからの相対パス）
+    Symbolic,
+    /// ハードリンク（リンク先はアーカイブのルートからのパス）
+    Hard,
+}
+
+struct PendingLink {
+    kind: LinkKind,
+    /// 警告表示用のエントリ名
+    name: String,
+    /// リンクの書き出し先
+    path: PathBuf,
+    /// アーカイブに記録されたリンク先
+    target: String,
+    /// 解凍先ディレクトリ内で解決したリンク先
+    resolved: PathBuf,
+}
+
+/// リンクのエントリを集めておき、通常のエントリをすべて解凍した後にまとめて作成する
+///
+/// 解凍中はリンクが存在しないため、後続のエントリがリンクを経由して
+/// 解凍先の外へ書き込むことはない。
+pub struct DeferredLinks<'a> {
+    extract_dir: &'a Path,
+    policy: LinkPolicy,
+    links: Vec<PendingLink>,
+}
+
+impl<'a> DeferredLinks<'a> {
+    pub fn new(extract_dir: &'a Path, policy: LinkPolicy) -> Self {
+        Self {
+            extract_dir,
+            policy,
+            links: Vec::new(),
+        }
+    }
+
+    /// リンクのエントリを登録する
+    ///
+    /// `path` は `entry_output_path` で決定した書き出し先。
+    /// リンク先が解凍先の外を指す場合は警告を出して無視する。
+    pub fn add(&mut self, kind: LinkKind, name: &str, path: PathBuf, target: &str) {
+        if self.policy == LinkPolicy::Skip {
+            return;
+        }
+
+        let resolved = match kind {
+            LinkKind::Symbolic => resolve_symlink_target(self.extract_dir, &path, target),
+            LinkKind::Hard => match sanitize_entry_path(target) {
+                SanitizedPath::Clean(target_path) => Some(self.extract_dir.join(target_path)),
+                SanitizedPath::Rewritten(_) | SanitizedPath::Rejected => None,
+            },
+        };
+
+        // リンク自身を含むディレクトリを指すリンクはコピー時に無限に再帰するため拒否する
+        let Some(resolved) = resolved.filter(|resolved| !path.starts_with(resolved)) else {
+            warn!(
+                "{}",
+                t!("ui.warning_link_outside", entry = name, target = target)
+            );
+            return;
+        };
+
+        self.links.push(PendingLink {
+            kind,
+            name: name.to_string(),
+            path,
+            target: target.to_string(),
+            resolved,
+        });
+    }
+
+    /// 登録したリンクを作成する
+    pub fn finish(self) -> Result<()> {
+        let mut pending = self.links;
+        let mut created = Vec::new();
+
+        // リンク先が別のリンクの場合に備え、作成できるものがなくなるまで繰り返す
+        loop {
+            let count = pending.len();
+            let mut remaining = Vec::new();
+            for link in pending {
+                if create_link(self.extract_dir, self.policy, &link)? {
+                    created.push(link);
+                } else {
+                    remaining.push(link);
+                }
+            }
+
+            pending = remaining;
+            if pending.is_empty() || pending.len() == count {
+                break;
+            }
+        }
+
+        for link in &pending {
+            warn!(
+                "{}",
+                t!(
+                    "ui.warning_link_unresolved",
+                    entry = link.name,
+                    target = link.target
+                )
+            );
+        }
+
+        // 後から作成したリンクを経由すると外を指すようになったシンボリックリンクを取り除く
+        // （例: `r/e -> d/../../../x` を作成した後に `r/d -> ../..` を作成した場合）
+        if self.policy == LinkPolicy::Recreate {
+            let root = self.extract_dir.canonicalize()?;
+            for link in &created {
+                if link.kind == LinkKind::Symbolic
+                    && link.path.symlink_metadata().is_ok_and(|m| m.is_symlink())
+                    && !link_target_on_disk(link).starts_with(&root)
+                {
+                    fs::remove_file(&link.path)?;
+                    warn!(
+                        "{}",
+                        t!(
+                            "ui.warning_link_outside",
+                            entry = link.name,
+                            target = link.target
+                        )
+                    );
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// シンボリックリンクのリンク先を、解凍先ディレクトリ内のパスとして字句的に解決する
+///
+/// 絶対パスや、`..` で解凍先の外に出るリンク先は `None` を返す。
+fn resolve_symlink_target(extract_dir: &Path, link_path: &Path, target: &str) -> Option<PathBuf> {
+    if target.starts_with(['/', '\\']) || target.as_bytes().get(1) == Some(&b':') {
+        return None;
+    }
+
+    let parent = link_path.parent()?.strip_prefix(extract_dir).ok()?;
+    let mut components: Vec<&OsStr> = parent.iter().collect();
+    for part in target.split(['/', '\\']) {
+        match part {
+            "" | "." => {}
+            ".." => {
+                components.pop()?;
+            }
+            _ => components.push(OsStr::new(part)),
+        }
+    }
+
+    if components.is_empty() {
+        return None;
+    }
+    Some(extract_dir.join(components.iter().collect::<PathBuf>()))
+}
+
+/// リンク先を、作成済みのシンボリックリンクもたどって実際のパスに解決する
+///
+/// 字句的な解決（`resolved`）は `d/..` を打ち消してしまうため、`d` が先に作成した
+/// シンボリックリンクの場合に実際とは異なる場所になる。存在する部分までは
+/// `canonicalize` で解決し、まだ存在しない残りの部分だけを字句的に解決する。
+fn link_target_on_disk(link: &PendingLink) -> PathBuf {
+    let raw_target = match link.kind {
+        LinkKind::Symbolic => {
+            let mut raw = link.path.parent().unwrap_or(Path::new("")).to_path_buf();
+            for part in link.target.split(['/', '\\']) {
+                match part {
+                    "" | "." => {}
+                    ".." => raw.push(Component::ParentDir),
+                    _ => raw.push(part),
+                }
+            }
+            raw
+        }
+        LinkKind::Hard => link.resolved.clone(),
+    };
+
+    for ancestor in raw_target.ancestors() {
+        let Ok(mut resolved) = ancestor.canonicalize() else {
+            continue;
+        };
+        let rest = raw_target.strip_prefix(ancestor).unwrap_or(Path::new(""));
+        for component in rest.components() {
+            match component {
+                Component::ParentDir => {
+                    resolved.pop();
+                }
+                Component::Normal(part) => resolved.push(part),
+                _ => {}
+            }
+        }
+        return resolved;
+    }
+    raw_target
+}
+
+/// リンクを1つ作成する。リンク先がまだ存在せず作成できない場合は `false` を返す
+fn create_link(extract_dir: &Path, policy: LinkPolicy, link: &PendingLink) -> Result<bool> {
+    // 先に作成したリンクを経由して書き込まない
+    let parent = link.path.parent().unwrap_or(extract_dir);
+    if passes_through_symlink(extract_dir, parent) {
+        warn!("{}", t!("ui.warning_entry_through_link", entry = link.name));
+        return Ok(true);
+    }
+
+    // 先に作成したリンクを経由して解凍先の外を指さない
+    let root = extract_dir.canonicalize()?;
+    let on_disk = link_target_on_disk(link);
+    if !on_disk.starts_with(&root) {
+        warn!(
+            "{}",
+            t!(
+                "ui.warning_link_outside",
+                entry = link.name,
+                target = link.target
+            )
+        );
+        return Ok(true);
+    }
+
+    // 同名の通常エントリがある場合は、アーカイブ内で後にあるリンクで置き換える
+    if let Ok(metadata) = link.path.symlink_metadata() {
+        if metadata.is_dir() {
+            warn!("{}", t!("ui.warning_entry_through_link", entry = link.name));
+            return Ok(true);
+        }
+        fs::remove_file(&link.path)?;
+    }
+
+    fs::create_dir_all(parent)?;
+
+    match (link.kind, policy) {
+        (_, LinkPolicy::Skip) => {}
+        (LinkKind::Symbolic, LinkPolicy::Recreate) => {
+            let target: PathBuf = link
+                .target
+                .split(['/', '\\'])
+                .filter(|part| !part.is_empty())
+                .collect();
+            create_symlink(&target, &link.path, on_disk.is_dir())?;
+        }
+        (LinkKind::Hard, LinkPolicy::Recreate) => {
+            if !on_disk.symlink_metadata().is_ok_and(|m| !m.is_dir()) {
+                return Ok(false);
+            }
+            fs::hard_link(&on_disk, &link.path)?;
+        }
+        (_, LinkPolicy::Copy) => {
+            if !on_disk.exists() {
+                return Ok(false);
+            }
+            if on_disk.is_dir() {
+                copy_dir_all(&on_disk, &link.path)?;
+            } else {
+                fs::copy(&on_disk, &link.path)?;
+            }
+        }
+    }
+    Ok(true)
+}
+
+/// ディレクトリを再帰的にコピーする（中のシンボリックリンクはたどらない）
+fn copy_dir_all(source: &Path, dest: &Path) -> Result<()> {
+    for entry in WalkDir::new(source) {
+        let entry = entry?;
+        let output_path = dest.join(entry.path().strip_prefix(source)?);
+
+        if entry.file_type().is_dir() {
+            fs::create_dir_all(&output_path)?;
+        } else if entry.file_type().is_file() {
+            fs::copy(entry.path(), &output_path)?;
+        }
+    }
+    Ok(())
+}
+
+#[cfg(unix)]
+fn create_symlink(target: &Path, link: &Path, _target_is_dir: bool) -> std::io::Result<()> {
+    std::os::unix::fs::symlink(target, link)
+}
+
+#[cfg(windows)]
+fn create_symlink(target: &Path, link: &Path, target_is_dir: bool) -> std::io::Result<()> {
+    if target_is_dir {
+        std::os::windows::fs::symlink_dir(target, link)
+    } else {
+        std::os::windows::fs::symlink_file(target, link)
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use crate::extractors::extractor::{ExtractOptions, Extractor};
+    use crate::extractors::tar_extractor::{TarCompression, TarExtractor};
+    use crate::extractors::test_support::{read_output, Fixture, TarBuilder};
+
+    fn extract(data: Vec<u8>, link_policy: LinkPolicy) -> Fixture {
+        let fixture = Fixture::new("test.tar", &data);
+        // 解凍先の外（`root/x/secret`）に読み出されてはいけないファイルを置く
+        fs::create_dir_all(fixture.root.join("x")).unwrap();
+        fs::write(fixture.root.join("x/secret"), b"secret").unwrap();
+
+        let options = ExtractOptions {
+            link_policy,
+            ..ExtractOptions::default()
+        };
+        TarExtractor::new(TarCompression::None)
+            .extract(&fixture.archive, &fixture.out, &options)
+            .unwrap();
+        fixture
+    }
+
+    /// 解凍先の中のパスがどこを指すか（リンクをたどって）確かめる
+    fn stays_inside(fixture: &Fixture, name: &str) -> bool {
+        match fixture.out.join(name).canonicalize() {
+            Ok(path) => path.starts_with(&fixture.out),
+            Err(_) => true,
+        }
+    }
+
+    #[test]
+    fn rejects_absolute_and_parent_targets() {
+        for policy in [LinkPolicy::Recreate, LinkPolicy::Copy] {
+            let fixture = extract(
+                TarBuilder::new()
+                    .symlink("abs", "/etc/passwd")
+                    .symlink("up", "../../x/secret")
+                    .hardlink("hard", "../x/secret")
+                    .build(),
+                policy,
+            );
+            for name in ["abs", "up", "hard"] {
+                assert!(fixture.out.join(name).symlink_metadata().is_err(), "{name}");
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_link_to_own_ancestor() {
+        let fixture = extract(
+            TarBuilder::new().dir("a/").symlink("a/loop", "..").build(),
+            LinkPolicy::Copy,
+        );
+        assert!(fixture.out.join("a/loop").symlink_metadata().is_err());
+    }
+
+    #[test]
+    fn rejects_chained_symlink_escape() {
+        // `d` は `s` を指すため、字句的には `e` は `a/x` だが実際には解凍先の外の `x` を指す
+        let entries = [("a/b/c/d", "../../../s"), ("a/b/c/e", "d/../../../x")];
+        for policy in [LinkPolicy::Recreate, LinkPolicy::Copy] {
+            for order in [[0, 1], [1, 0]] {
+                let mut builder = TarBuilder::new().dir("a/b/c/").dir("s/");
+                for i in order {
+                    builder = builder.symlink(entries[i].0, entries[i].1);
+                }
+                let fixture = extract(builder.hardlink("h", "a/b/c/e/secret").build(), policy);
+
+                assert!(stays_inside(&fixture, "a/b/c/e"), "{policy:?} {order:?}");
+                assert!(read_output(&fixture.out, "a/b/c/e/secret").is_none());
+                assert!(read_output(&fixture.out, "h").is_none());
+            }
+        }
+    }
+
+    #[test]
+    fn follows_chained_links_inside_extract_dir() {
+        for policy in [LinkPolicy::Recreate, LinkPolicy::Copy] {
+            let fixture = extract(
+                TarBuilder::new()
+                    .symlink("a", "b")
+                    .symlink("b", "dir/file")
+                    .file("dir/file", b"data")
+                    .build(),
+                policy,
+            );
+            assert_eq!(read_output(&fixture.out, "a").unwrap(), b"data");
+            assert_eq!(read_output(&fixture.out, "b").unwrap(), b"data");
+        }
+    }
+
+    #[test]
+    fn skip_policy_creates_no_links() {
+        let fixture = extract(
+            TarBuilder::new()
+                .file("file", b"data")
+                .symlink("sym", "file")
+                .hardlink("hard", "file")
+                .build(),
+            LinkPolicy::Skip,
+        );
+        assert!(fixture.out.join("sym").symlink_metadata().is_err());
+        assert!(fixture.out.join("hard").symlink_metadata().is_err());
+    }
+}
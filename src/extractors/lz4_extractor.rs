@@ -0,0 +1,45 @@
+use lz4_flex::frame::FrameDecoder;
+use std::io::{self, BufRead, Read};
+
+use super::common::SingleFileExtractor;
+
+/// LZ4 フレームのマジックナンバー
+pub const LZ4_MAGIC: &[u8] = &[0x04, 0x22, 0x4d, 0x18];
+
+/// LZ4 フレーム形式のストリームを展開するリーダー
+///
+/// `FrameDecoder` はフレームの終わりで読み込みを終えるため、
+/// 複数のフレームが連結されている場合は入力がなくなるまで続けて展開する。
+pub struct Lz4Decoder<R: BufRead> {
+    inner: FrameDecoder<R>,
+}
+
+impl<R: BufRead> Lz4Decoder<R> {
+    pub fn new(reader: R) -> Self {
+        Self {
+            inner: FrameDecoder::new(reader),
+        }
+    }
+}
+
+impl<R: BufRead> Read for Lz4Decoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            let read = self.inner.read(buf)?;
+            if read > 0 || buf.is_empty() || self.inner.get_mut().fill_buf()?.is_empty() {
+                return Ok(read);
+            }
+        }
+    }
+}
+
+/// LZ4 フレーム形式（.lz4）
+///
+/// フレームにチェックサムがあれば、末尾まで読むと検証される。
+pub const LZ4_EXTRACTOR: SingleFileExtractor = SingleFileExtractor {
+    name: "LZ4",
+    extensions: &["lz4"],
+    detect: |header| header.starts_with(LZ4_MAGIC),
+    decoder: |reader| Ok(Box::new(Lz4Decoder::new(reader))),
+    progress_key: "progress.extracting_lz4",
+};
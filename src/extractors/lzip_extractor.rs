@@ -0,0 +1,282 @@
+use std::io::{self, BufRead, Read};
+use xz2::stream::{Action, Status, Stream};
+
+use super::common::SingleFileExtractor;
+use super::lzma_extractor::LZMA_HEADER_LEN;
+
+/// lzip のメンバーのマジックナンバー
+pub const LZIP_MAGIC: &[u8] = b"LZIP";
+
+/// メンバーのヘッダ（マジックナンバー・バージョン・辞書サイズ）のサイズ
+const LZIP_HEADER_LEN: usize = 6;
+/// メンバーのトレーラー（CRC32・展開後のサイズ・メンバーのサイズ）のサイズ
+const LZIP_TRAILER_LEN: usize = 20;
+
+/// lzip 形式のストリームを展開するリーダー
+///
+/// lzip のメンバーは終端マーカーつきの LZMA ストリームなので、.lzma のヘッダを補って
+/// liblzma で展開し、メンバーごとにトレーラーの CRC32 とサイズを検証する。
+/// 複数のメンバーが連結されている場合は続けて展開する。
+/// （xz2 のリーダーはストリームの後ろにデータが続くとエラーにするため、`Stream` を直接使う）
+pub struct LzipDecoder<R: BufRead> {
+    reader: R,
+    /// 展開中のメンバー（すべて展開し終えたら `None`）
+    member: Option<Member>,
+    crc: crc32fast::Hasher,
+    size: u64,
+}
+
+/// 展開中のメンバー
+struct Member {
+    stream: Stream,
+    /// liblzma に先に渡す .lzma のヘッダ
+    lzma_header: [u8; LZMA_HEADER_LEN],
+    lzma_header_pos: usize,
+}
+
+impl<R: BufRead> LzipDecoder<R> {
+    pub fn new(mut reader: R) -> io::Result<Self> {
+        let member = start_member(&mut reader)?;
+        Ok(Self {
+            reader,
+            member: Some(member),
+            crc: crc32fast::Hasher::new(),
+            size: 0,
+        })
+    }
+
+    /// メンバーのトレーラーを検証し、続くメンバーがあれば展開を始める
+    fn finish_member(&mut self) -> io::Result<()> {
+        let mut trailer = [0; LZIP_TRAILER_LEN];
+        self.reader.read_exact(&mut trailer)?;
+        let crc = u32::from_le_bytes([trailer[0], trailer[1], trailer[2], trailer[3]]);
+        let mut size_bytes = [0; 8];
+        size_bytes.copy_from_slice(&trailer[4..12]);
+
+        let hasher = std::mem::replace(&mut self.crc, crc32fast::Hasher::new());
+        if hasher.finalize() != crc {
+            return Err(invalid_data("lzip: CRC mismatch"));
+        }
+        if u64::from_le_bytes(size_bytes) != std::mem::take(&mut self.size) {
+            return Err(invalid_data("lzip: data size mismatch"));
+        }
+
+        // 最後のメンバーの後ろのデータは無視する（lzip コマンドと同じ）
+        // `fill_buf` はバッファの残りしか返さず、次のメンバーのマジックナンバーが途中で
+        // 切れて見えることがあるため、マジックナンバーの長さだけ読み進めてから比べる
+        let mut magic = Vec::with_capacity(LZIP_MAGIC.len());
+        (&mut self.reader)
+            .take(LZIP_MAGIC.len() as u64)
+            .read_to_end(&mut magic)?;
+        if magic == LZIP_MAGIC {
+            self.member = Some(start_member(&mut magic.as_slice().chain(&mut self.reader))?);
+        } else if !magic.is_empty() && LZIP_MAGIC.starts_with(&magic) {
+            // マジックナンバーの途中で終わっている場合は、後ろのデータではなくメンバーの欠落
+            return Err(invalid_data("lzip: truncated member header"));
+        }
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for LzipDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return Ok(0);
+        }
+
+        while let Some(member) = &mut self.member {
+            let from_header = member.lzma_header_pos < LZMA_HEADER_LEN;
+            let input = if from_header {
+                &member.lzma_header[member.lzma_header_pos..]
+            } else {
+                self.reader.fill_buf()?
+            };
+            let eof = input.is_empty();
+
+            let before_in = member.stream.total_in();
+            let before_out = member.stream.total_out();
+            let action = if eof { Action::Finish } else { Action::Run };
+            let status = member.stream.process(input, buf, action)?;
+            let consumed = (member.stream.total_in() - before_in) as usize;
+            let read = (member.stream.total_out() - before_out) as usize;
+
+            if from_header {
+                member.lzma_header_pos += consumed;
+            } else {
+                self.reader.consume(consumed);
+            }
+            if status != Status::StreamEnd && read == 0 && consumed == 0 {
+                return Err(invalid_data("lzip: unexpected end of stream"));
+            }
+
+            self.crc.update(&buf[..read]);
+            self.size += read as u64;
+            if status == Status::StreamEnd {
+                self.member = None;
+                self.finish_member()?;
+            }
+            if read > 0 {
+                return Ok(read);
+            }
+        }
+        Ok(0)
+    }
+}
+
+/// メンバーのヘッダを読み、LZMA ストリームの展開を始める
+fn start_member<R: Read>(reader: &mut R) -> io::Result<Member> {
+    let mut header = [0; LZIP_HEADER_LEN];
+    reader.read_exact(&mut header)?;
+    if !header.starts_with(LZIP_MAGIC) {
+        return Err(invalid_data("lzip: bad magic number"));
+    }
+    if header[4] != 1 {
+        return Err(invalid_data("lzip: unsupported version"));
+    }
+
+    // 辞書サイズは 2^n から 2^n / 16 の 0〜7 倍を引いた値で表される
+    let exponent = header[5] & 0x1f;
+    if !(12..=29).contains(&exponent) {
+        return Err(invalid_data("lzip: invalid dictionary size"));
+    }
+    let base = 1u32 << exponent;
+    let dict_size = base - (base / 16) * u32::from(header[5] >> 5);
+
+    // lc=3, lp=0, pb=2 と、展開後のサイズ不明（終端マーカーで終わる）を表す .lzma のヘッダ
+    let mut lzma_header = [0xff; LZMA_HEADER_LEN];
+    lzma_header[0] = 0x5d;
+    lzma_header[1..5].copy_from_slice(&dict_size.to_le_bytes());
+
+    Ok(Member {
+        stream: Stream::new_lzma_decoder(u64::MAX)?,
+        lzma_header,
+        lzma_header_pos: 0,
+    })
+}
+
+fn invalid_data(message: &str) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+/// lzip 形式（.lz）
+///
+/// 末尾まで読むと各メンバーのトレーラーの CRC32 が検証される。
+pub const LZIP_EXTRACTOR: SingleFileExtractor = SingleFileExtractor {
+    name: "LZIP",
+    extensions: &["lz"],
+    detect: |header| header.starts_with(LZIP_MAGIC),
+    decoder: |reader| Ok(Box::new(LzipDecoder::new(reader)?)),
+    progress_key: "progress.extracting_lz",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{read_output, Fixture};
+    use crate::extractors::ExtractOptions;
+    use std::io::BufReader;
+    use xz2::read::XzEncoder;
+    use xz2::stream::LzmaOptions;
+
+    /// liblzma の .lzma エンコーダーの出力から lzip のメンバーを組み立てる
+    /// （.lzma は終端マーカーつきで出力されるため、ヘッダを lzip のものに差し替えればよい）
+    fn lzip_member(data: &[u8]) -> Vec<u8> {
+        let stream = Stream::new_lzma_encoder(&LzmaOptions::new_preset(6).unwrap()).unwrap();
+        let mut lzma = Vec::new();
+        XzEncoder::new_stream(data, stream)
+            .read_to_end(&mut lzma)
+            .unwrap();
+
+        // プリセット 6 の辞書サイズは 8MiB（2^23）
+        let mut member = b"LZIP\x01\x17".to_vec();
+        member.extend_from_slice(&lzma[LZMA_HEADER_LEN..]);
+        member.extend_from_slice(&crc32fast::hash(data).to_le_bytes());
+        member.extend_from_slice(&(data.len() as u64).to_le_bytes());
+        let member_size = (member.len() + 8) as u64;
+        member.extend_from_slice(&member_size.to_le_bytes());
+        member
+    }
+
+    fn decode(data: &[u8], capacity: usize) -> io::Result<Vec<u8>> {
+        let mut output = Vec::new();
+        LzipDecoder::new(BufReader::with_capacity(capacity, data))?.read_to_end(&mut output)?;
+        Ok(output)
+    }
+
+    fn multi_member() -> (Vec<u8>, Vec<u8>) {
+        let parts: [&[u8]; 3] = [b"first member\n", &[b'x'; 1000], b"third member\n"];
+        let data = parts.iter().flat_map(|part| lzip_member(part)).collect();
+        (data, parts.concat())
+    }
+
+    #[test]
+    fn decodes_every_member_at_any_buffer_boundary() {
+        let (data, expected) = multi_member();
+        for capacity in (1..=64).chain([data.len(), 8192]) {
+            assert_eq!(
+                decode(&data, capacity).unwrap(),
+                expected,
+                "capacity {capacity}"
+            );
+        }
+    }
+
+    #[test]
+    fn ignores_trailing_data() {
+        let (mut data, expected) = multi_member();
+        data.extend_from_slice(b"\0\0 trailing garbage");
+        for capacity in 1..=64 {
+            assert_eq!(
+                decode(&data, capacity).unwrap(),
+                expected,
+                "capacity {capacity}"
+            );
+        }
+    }
+
+    #[test]
+    fn rejects_truncated_member_header() {
+        let (data, _) = multi_member();
+        for partial in [&b"L"[..], b"LZ", b"LZI"] {
+            let data = [data.as_slice(), partial].concat();
+            for capacity in 1..=64 {
+                assert!(
+                    decode(&data, capacity).is_err(),
+                    "{partial:?} capacity {capacity}"
+                );
+            }
+        }
+        assert!(decode(&[data.as_slice(), b"LZIP\x01"].concat(), 8192).is_err());
+    }
+
+    #[test]
+    fn rejects_corrupt_members() {
+        let member = lzip_member(b"hello lzip");
+
+        let mut bad_crc = member.clone();
+        let crc_pos = bad_crc.len() - LZIP_TRAILER_LEN;
+        bad_crc[crc_pos] ^= 0xff;
+        assert!(decode(&bad_crc, 8192).is_err());
+
+        assert!(decode(&member[..member.len() - 1], 8192).is_err());
+        assert!(decode(&member[..member.len() / 2], 8192).is_err());
+        assert!(decode(b"LZIP\x02\x17", 8192).is_err());
+        assert!(decode(b"LZIX\x01\x17", 8192).is_err());
+    }
+
+    #[test]
+    fn extracts_and_tests_files() {
+        let (data, expected) = multi_member();
+        let fixture = Fixture::new("notes.txt.lz", &data);
+        fixture
+            .extract(&LZIP_EXTRACTOR, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(read_output(&fixture.out, "notes.txt").unwrap(), expected);
+
+        let truncated = Fixture::new("notes.txt.lz", &[data.as_slice(), b"LZ"].concat());
+        assert_eq!(
+            truncated.test(&LZIP_EXTRACTOR).unwrap(),
+            vec![("notes.txt".to_string(), false)]
+        );
+    }
+}
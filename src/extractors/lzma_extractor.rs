@@ -0,0 +1,46 @@
+use anyhow::Result;
+use std::io::Read;
+use xz2::read::XzDecoder;
+use xz2::stream::Stream;
+
+use super::common::SingleFileExtractor;
+
+/// .lzma（LZMA_Alone）のヘッダのサイズ（プロパティ・辞書サイズ・展開後のサイズ）
+pub const LZMA_HEADER_LEN: usize = 13;
+
+/// .lzma のヘッダかどうか
+///
+/// .lzma には固有のマジックナンバーがないため、lzma / xz コマンドが標準で使う
+/// プロパティ（lc=3, lp=0, pb=2）と、辞書サイズ・展開後のサイズが妥当かで判定する。
+pub fn is_lzma_header(header: &[u8]) -> bool {
+    if header.len() < LZMA_HEADER_LEN || header[0] != 0x5d {
+        return false;
+    }
+    let dict_size = u32::from_le_bytes([header[1], header[2], header[3], header[4]]);
+    let mut size_bytes = [0; 8];
+    size_bytes.copy_from_slice(&header[5..13]);
+    let size = u64::from_le_bytes(size_bytes);
+
+    // 辞書サイズは 2^n か 2^n + 2^(n-1)、展開後のサイズは不明（全ビット 1）か 256TiB 未満
+    let valid_dict_size = dict_size >= 4096
+        && (dict_size.is_power_of_two()
+            || (dict_size.is_multiple_of(3) && (dict_size / 3).is_power_of_two()));
+    valid_dict_size && (size == u64::MAX || size < 1 << 48)
+}
+
+/// .lzma のストリームを展開するリーダーを作成する
+pub fn lzma_decoder<R: Read>(reader: R) -> Result<XzDecoder<R>> {
+    let stream = Stream::new_lzma_decoder(u64::MAX)?;
+    Ok(XzDecoder::new_stream(reader, stream))
+}
+
+/// LZMA_Alone 形式（.lzma）
+///
+/// .lzma にはチェックサムがないため、検査では最後までデコードできるかだけを確認する。
+pub const LZMA_EXTRACTOR: SingleFileExtractor = SingleFileExtractor {
+    name: "LZMA",
+    extensions: &["lzma"],
+    detect: is_lzma_header,
+    decoder: |reader| Ok(Box::new(lzma_decoder(reader)?)),
+    progress_key: "progress.extracting_lzma",
+};
@@ -1,18 +1,53 @@
+pub mod ar_extractor;
 pub mod bzip2_extractor;
+pub mod cab_extractor;
 pub mod common;
+pub mod compress_extractor;
+pub mod cpio_extractor;
+pub mod extractor;
 pub mod gzip_extractor;
+pub mod iso_extractor;
+pub mod legacy_extractor;
 pub mod lha_extractor;
+pub mod limits;
+pub mod links;
+pub mod lz4_extractor;
+pub mod lzip_extractor;
+pub mod lzma_extractor;
+pub mod password;
 pub mod rar_extractor;
+pub mod registry;
 pub mod sevenz_extractor;
+pub mod sfx_extractor;
 pub mod tar_extractor;
+#[cfg(test)]
+pub mod test_support;
+pub mod volumes;
 pub mod xz_extractor;
 pub mod zip_extractor;
+pub mod zstd_extractor;
 
+pub use ar_extractor::*;
 pub use bzip2_extractor::*;
+pub use cab_extractor::*;
+pub use compress_extractor::*;
+pub use cpio_extractor::*;
+pub use extractor::*;
 pub use gzip_extractor::*;
+pub use iso_extractor::*;
+pub use legacy_extractor::*;
 pub use lha_extractor::*;
+pub use limits::*;
+pub use lz4_extractor::*;
+pub use lzip_extractor::*;
+pub use lzma_extractor::*;
+pub use password::*;
 pub use rar_extractor::*;
+pub use registry::*;
 pub use sevenz_extractor::*;
+pub use sfx_extractor::*;
 pub use tar_extractor::*;
+pub use volumes::*;
 pub use xz_extractor::*;
 pub use zip_extractor::*;
+pub use zstd_extractor::*;
@@ -0,0 +1,30 @@
+use std::fmt;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+/// 暗号化されたアーカイブでパスワードが足りない・間違っていることを表すエラー
+///
+/// 破損によるエラーと区別し、呼び出し側でパスワードを尋ねて再試行できるようにする。
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PasswordError {
+    /// 暗号化されているがパスワードが指定されていない
+    Required,
+    /// ファイル一覧（ヘッダ）まで暗号化されており、パスワードがないと開けない
+    HeaderEncrypted,
+    /// 指定されたパスワードでは復号できない
+    Wrong,
+}
+
+impl fmt::Display for PasswordError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let message = match self {
+            PasswordError::Required => t!("ui.error_password_required"),
+            PasswordError::HeaderEncrypted => t!("ui.error_password_header_encrypted"),
+            PasswordError::Wrong => t!("ui.error_password_wrong"),
+        };
+        write!(f, "{}", message)
+    }
+}
+
+impl std::error::Error for PasswordError {}
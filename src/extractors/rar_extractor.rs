@@ -1,62 +1,291 @@
 use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use log::warn;
 use std::path::Path;
+use unrar::error::{Code, UnrarError, When};
 use unrar::Archive;
 
 // Import the i18n macro
 use rust_i18n::t;
 
-pub fn extract_rar(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    // unrar クレートを使用した実装
-    std::fs::create_dir_all(extract_dir)?;
-
-    let mut archive = Archive::new(file_path.to_str().unwrap()).open_for_processing()?;
-
-    // プログレスバーの設定
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_rar")));
-
-    loop {
-        match archive.read_header() {
-            Ok(Some(header)) => {
-                let entry = header.entry();
-                let output_path = extract_dir.join(entry.filename.as_path());
-
-                // プログレスバーのメッセージを更新
-                if let Some(file_name) = entry
-                    .filename
-                    .as_path()
-                    .file_name()
-                    .and_then(|s| s.to_str())
-                {
-                    pb.set_message(format!(
-                        "{}",
-                        t!("progress.extracting_file", file = file_name)
-                    ));
-                }
+use super::common::{decode_filename, dos_datetime, entry_output_path, new_spinner};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::limits::LimitTracker;
+use super::links::{is_symlink_mode, DeferredLinks, LinkKind};
+use super::password::PasswordError;
+use super::volumes::missing_next_volume_error;
+
+/// RAR 1.5〜4.x と RAR 5.0 のシグネチャ
+pub const RAR_MAGICS: [&[u8]; 2] = [b"Rar!\x1a\x07\x00", b"Rar!\x1a\x07\x01\x00"];
+
+pub struct RarExtractor;
+
+/// パスワードが指定されていれば、それを使ってアーカイブを開く準備をする
+fn open_archive<'a>(file_path: &'a Path, password: Option<&'a str>) -> Archive<'a> {
+    match password {
+        Some(password) => Archive::with_password(file_path, password),
+        None => Archive::new(file_path),
+    }
+}
+
+/// unrar のエラーを、パスワードの誤りや欠けているボリュームが分かるエラーに変換する
+///
+/// RAR 4.x 以前にはパスワードの検証値がないため、パスワードを指定したうえでの
+/// 暗号化されたデータの CRC エラーやヘッダの破損も、パスワードの誤りとして扱う。
+fn convert_error(
+    file_path: &Path,
+    error: UnrarError,
+    encrypted: bool,
+    password: Option<&str>,
+) -> anyhow::Error {
+    match (error.code, error.when) {
+        // ファイル一覧を読む段階でパスワードが必要になるのはヘッダが暗号化されている場合
+        (Code::MissingPassword, When::Process) => PasswordError::Required.into(),
+        (Code::MissingPassword, _) => PasswordError::HeaderEncrypted.into(),
+        (Code::BadPassword, _) => PasswordError::Wrong.into(),
+        (Code::BadData, when) if password.is_some() && (encrypted || when != When::Process) => {
+            PasswordError::Wrong.into()
+        }
+        // 分割アーカイブの次のボリュームが開けない
+        (Code::EOpen, When::Process) => {
+            missing_next_volume_error(file_path).unwrap_or_else(|| error.into())
+        }
+        _ => error.into(),
+    }
+}
+
+impl Extractor for RarExtractor {
+    fn name(&self) -> &'static str {
+        "RAR"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["rar"]
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        RAR_MAGICS.iter().any(|magic| header.starts_with(magic))
+    }
+
+    fn list(&self, file_path: &Path, password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        let archive = open_archive(file_path, password)
+            .open_for_listing()
+            .map_err(|e| convert_error(file_path, e, false, password))?;
+        let mut entries = Vec::new();
 
-                if entry.is_directory() {
-                    std::fs::create_dir_all(&output_path)?;
-                    archive = header.skip()?;
+        for header in archive {
+            let header = header.map_err(|e| convert_error(file_path, e, false, password))?;
+            entries.push(ArchiveEntry {
+                name: header.filename.to_string_lossy().into_owned(),
+                size: header.unpacked_size,
+                // unrar のヘッダには圧縮後のサイズが含まれない
+                compressed_size: None,
+                modified: dos_datetime(header.file_time),
+                kind: if header.is_directory() {
+                    EntryKind::Directory
+                } else if is_symlink_mode(header.file_attr) {
+                    EntryKind::Symlink
                 } else {
-                    if let Some(parent) = output_path.parent() {
-                        std::fs::create_dir_all(parent)?;
+                    EntryKind::File
+                },
+            });
+        }
+        Ok(entries)
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        // unrar クレートを使用した実装
+        let password = options.password.as_deref();
+        let mut archive = open_archive(file_path, password)
+            .open_for_processing()
+            .map_err(|e| convert_error(file_path, e, false, password))?;
+
+        // プログレスバーの設定
+        let pb = new_spinner(format!("{}", t!("progress.extracting_rar")));
+
+        let mut links = DeferredLinks::new(extract_dir, options.link_policy);
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+
+        loop {
+            match archive.read_header() {
+                Ok(Some(header)) => {
+                    let entry = header.entry();
+                    let encrypted = entry.is_encrypted();
+                    let entry_size = entry.unpacked_size;
+                    tracker.start_entry()?;
+
+                    // 解凍先の外を指すエントリはスキップ
+                    let Some(output_path) =
+                        entry_output_path(extract_dir, &entry.filename.to_string_lossy(), options)
+                    else {
+                        archive = header
+                            .skip()
+                            .map_err(|e| convert_error(file_path, e, false, password))?;
+                        continue;
+                    };
+
+                    // プログレスバーのメッセージを更新
+                    if let Some(file_name) = entry
+                        .filename
+                        .as_path()
+                        .file_name()
+                        .and_then(|s| s.to_str())
+                    {
+                        pb.set_message(format!(
+                            "{}",
+                            t!("progress.extracting_file", file = file_name)
+                        ));
+                    }
+
+                    // unrar が直接ファイルに書き出し、書き込みを数えられないため、
+                    // ヘッダのサイズで事前に上限を確認する（書き込み後に実際のサイズでも確認する）
+                    if entry.is_file() {
+                        tracker.reserve(entry_size)?;
+                    }
+
+                    if entry.is_file() && is_symlink_mode(entry.file_attr) {
+                        // RAR4 のシンボリックリンクはリンク先が内容として格納されている
+                        // （RAR5 ではヘッダのリダイレクトレコードに格納され、unrar からは読み出せないため
+                        // 内容が空になる）
+                        let name = entry.filename.to_string_lossy().into_owned();
+                        let (data, next_archive) = header
+                            .read()
+                            .map_err(|e| convert_error(file_path, e, encrypted, password))?;
+                        if data.is_empty() {
+                            warn!("{}", t!("ui.warning_link_target_unreadable", entry = name));
+                        } else {
+                            links.add(
+                                LinkKind::Symbolic,
+                                &name,
+                                output_path,
+                                &decode_filename(&data),
+                            );
+                        }
+                        archive = next_archive;
+                    } else if entry.is_directory() {
+                        std::fs::create_dir_all(&output_path)?;
+                        archive = header
+                            .skip()
+                            .map_err(|e| convert_error(file_path, e, false, password))?;
+                    } else {
+                        if let Some(parent) = output_path.parent() {
+                            std::fs::create_dir_all(parent)?;
+                        }
+
+                        // メモリには読み込まず、unrar にファイルへ書き出させる
+                        archive = header
+                            .extract_to(&output_path)
+                            .map_err(|e| convert_error(file_path, e, encrypted, password))?;
+                        let written = std::fs::metadata(&output_path)?.len();
+                        tracker.settle(entry_size, written)?;
                     }
+                }
+                Ok(None) => break,
+                Err(e) => return Err(convert_error(file_path, e, false, password)),
+            }
+        }
+
+        links.finish()
+    }
 
-                    let (data, next_archive) = header.read()?;
-                    std::fs::write(&output_path, data)?;
-                    archive = next_archive;
+    fn test(
+        &self,
+        file_path: &Path,
+        password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let mut archive = open_archive(file_path, password)
+            .open_for_processing()
+            .map_err(|e| convert_error(file_path, e, false, password))?;
+
+        // unrar 側で展開して CRC を検証する（ファイルは書き出さない）
+        while let Some(header) = archive
+            .read_header()
+            .map_err(|e| convert_error(file_path, e, false, password))?
+        {
+            let name = header.entry().filename.to_string_lossy().into_owned();
+            let encrypted = header.entry().is_encrypted();
+            match header.test() {
+                Ok(next) => {
+                    report(&name, Ok(()));
+                    archive = next;
+                }
+                Err(e) => {
+                    // パスワードの問題は破損として報告せず、尋ね直せるように呼び出し元へ返す
+                    let e = convert_error(file_path, e, encrypted, password);
+                    if e.is::<PasswordError>() {
+                        return Err(e);
+                    }
+                    // 失敗するとアーカイブのハンドルが失われるため、以降は検査できない
+                    report(&name, Err(e));
+                    break;
                 }
             }
-            Ok(None) => break,
-            Err(e) => return Err(e.into()),
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::limits::{ExtractLimits, LimitExceeded};
+    use crate::extractors::test_support::{rar4_archive, rar4_volumes, read_output, Fixture};
+    use std::fs;
+
+    #[test]
+    fn extracts_stored_entries() {
+        let data = rar4_archive(&[("a.txt", b"hello"), ("dir/b.txt", b"world")]);
+        let fixture = Fixture::new("test.rar", &data);
+        fixture
+            .extract(&RarExtractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(read_output(&fixture.out, "a.txt"), Some(b"hello".to_vec()));
+        assert_eq!(
+            read_output(&fixture.out, "dir/b.txt"),
+            Some(b"world".to_vec())
+        );
+    }
+
+    #[test]
+    fn extracts_volumes() {
+        let contents: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        let fixture = Fixture::new("x.part1.rar", b"");
+        for (i, volume) in rar4_volumes("big.bin", &contents, 3).iter().enumerate() {
+            fs::write(fixture.root.join(format!("x.part{}.rar", i + 1)), volume).unwrap();
+        }
+        fixture
+            .extract(&RarExtractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(read_output(&fixture.out, "big.bin"), Some(contents));
     }
 
-    Ok(())
+    #[test]
+    fn enforces_limits_on_written_entries() {
+        let data = rar4_archive(&[("a.bin", &[b'a'; 3000]), ("b.bin", &[b'b'; 3000])]);
+        let fixture = Fixture::new("test.rar", &data);
+        let extract = |limits| {
+            let options = ExtractOptions {
+                limits,
+                ..ExtractOptions::default()
+            };
+            let error = fixture.extract(&RarExtractor, &options).unwrap_err();
+            error.downcast_ref::<LimitExceeded>().copied()
+        };
+
+        let limits = ExtractLimits {
+            max_entry_size: 2999,
+            ..ExtractLimits::default()
+        };
+        assert_eq!(extract(limits), Some(LimitExceeded::EntrySize(2999)));
+        let limits = ExtractLimits {
+            max_total_size: 5000,
+            ..ExtractLimits::default()
+        };
+        assert_eq!(extract(limits), Some(LimitExceeded::TotalSize(5000)));
+    }
 }
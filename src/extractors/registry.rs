@@ -0,0 +1,354 @@
+use log::warn;
+use std::path::Path;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use super::common::read_magic_header;
+use super::extractor::{has_extension, Extractor};
+use super::{
+    ArExtractor, ArFlavor, Bz2Extractor, CabExtractor, CpioCompression, CpioExtractor, GzExtractor,
+    IsoExtractor, LegacyExtractor, LegacyFormat, LhaExtractor, RarExtractor, SevenZExtractor,
+    SfxExtractor, TarCompression, TarExtractor, XzExtractor, ZipExtractor, COMPRESS_EXTRACTOR,
+    LZ4_EXTRACTOR, LZIP_EXTRACTOR, LZMA_EXTRACTOR, ZSTD_EXTRACTOR,
+};
+
+/// 既存の形式の別名として扱う拡張子（別名, 形式の拡張子）
+///
+/// Java のアーカイブや電子書籍・コミックのように、中身は ZIP・RAR・7z の形式も含む。
+pub const EXTENSION_ALIASES: &[(&str, &str)] = &[
+    ("tbz2", "tar.bz2"),
+    ("tbz", "tar.bz2"),
+    ("txz", "tar.xz"),
+    ("tlz", "tar.lzma"),
+    ("taz", "tar.z"),
+    ("jar", "zip"),
+    ("apk", "zip"),
+    ("epub", "zip"),
+    ("cbz", "zip"),
+    ("cbr", "rar"),
+    ("cb7", "7z"),
+];
+
+/// ファイル名の拡張子が別名の場合、対応する形式の拡張子を返す
+pub fn resolve_extension_alias(file_path: &Path) -> Option<&'static str> {
+    find_alias(file_path).map(|(_, ext)| ext)
+}
+
+/// ファイル名に一致する別名と、対応する形式の拡張子
+fn find_alias(file_path: &Path) -> Option<(&'static str, &'static str)> {
+    let file_name = file_path.file_name().and_then(|s| s.to_str()).unwrap_or("");
+    EXTENSION_ALIASES
+        .iter()
+        .find(|(alias, _)| has_extension(file_name, alias))
+        .copied()
+}
+
+/// ファイル名から `extractor` の形式の拡張子（別名も含む）を除いた名前
+///
+/// `.tar.gz` のような複合拡張子も1つの拡張子として除く。
+/// 内容で判定した形式と拡張子が食い違う場合など、どれにも一致しなければ `None` を返す。
+pub fn strip_format_extension<'a>(
+    file_path: &'a Path,
+    extractor: &dyn Extractor,
+) -> Option<&'a str> {
+    let file_name = file_path.file_name()?.to_str()?;
+    let ext = extractor.matched_extension(file_path).or_else(|| {
+        find_alias(file_path)
+            .filter(|(_, ext)| extractor.extensions().contains(ext))
+            .map(|(alias, _)| alias)
+    })?;
+    Some(&file_name[..file_name.len() - ext.len() - 1]).filter(|stem| !stem.is_empty())
+}
+
+/// 対応形式の一覧
+///
+/// 判定は登録順に行われるため、`tar.gz` のような複合拡張子を持つ形式は
+/// `gz` などの単一拡張子の形式より先に登録すること。
+pub struct Registry {
+    extractors: Vec<Box<dyn Extractor>>,
+}
+
+impl Registry {
+    /// 何も登録されていないレジストリ
+    pub fn empty() -> Self {
+        Self {
+            extractors: Vec::new(),
+        }
+    }
+
+    /// 組み込みの全形式を登録したレジストリ
+    pub fn with_builtin() -> Self {
+        let mut registry = Self::empty();
+        registry.register(ZipExtractor);
+        registry.register(SevenZExtractor);
+        registry.register(RarExtractor);
+        registry.register(IsoExtractor);
+        registry.register(CabExtractor);
+        registry.register(TarExtractor::new(TarCompression::Gzip));
+        registry.register(TarExtractor::new(TarCompression::Xz));
+        registry.register(TarExtractor::new(TarCompression::Bzip2));
+        registry.register(TarExtractor::new(TarCompression::Zstd));
+        registry.register(TarExtractor::new(TarCompression::Lz4));
+        registry.register(TarExtractor::new(TarCompression::Lzma));
+        registry.register(TarExtractor::new(TarCompression::Lzip));
+        registry.register(TarExtractor::new(TarCompression::Compress));
+        registry.register(TarExtractor::new(TarCompression::None));
+        registry.register(CpioExtractor::new(CpioCompression::Gzip));
+        registry.register(CpioExtractor::new(CpioCompression::Xz));
+        registry.register(CpioExtractor::new(CpioCompression::Zstd));
+        registry.register(CpioExtractor::new(CpioCompression::None));
+        registry.register(ArExtractor::new(ArFlavor::Deb));
+        registry.register(ArExtractor::new(ArFlavor::Plain));
+        registry.register(GzExtractor);
+        registry.register(XzExtractor);
+        registry.register(Bz2Extractor);
+        registry.register(ZSTD_EXTRACTOR);
+        registry.register(LZ4_EXTRACTOR);
+        registry.register(LZMA_EXTRACTOR);
+        registry.register(LZIP_EXTRACTOR);
+        registry.register(COMPRESS_EXTRACTOR);
+        registry.register(LhaExtractor);
+        registry.register(LegacyExtractor::new(LegacyFormat::Arj));
+        registry.register(LegacyExtractor::new(LegacyFormat::Zoo));
+        // ARC は先頭の1バイトでしか判定できないため最後に登録する
+        registry.register(LegacyExtractor::new(LegacyFormat::Arc));
+        registry.register(SfxExtractor);
+        registry
+    }
+
+    pub fn register<E: Extractor + 'static>(&mut self, extractor: E) {
+        self.extractors.push(Box::new(extractor));
+    }
+
+    /// ファイルを扱える形式を返す
+    ///
+    /// ファイル内容（マジックナンバー）による判定を優先し、判定できない場合は
+    /// 拡張子で判定する。両者が食い違う場合は警告を出して内容の方を採用する。
+    pub fn find(&self, file_path: &Path) -> Option<&dyn Extractor> {
+        let by_extension = self.find_by_extension(file_path);
+
+        // 読み込めない場合は内容による判定を諦め、拡張子だけで判定する
+        let header = read_magic_header(file_path).unwrap_or_default();
+        let by_content = self.find_by_content(file_path, &header);
+
+        match (by_content, by_extension) {
+            (Some(detected), Some(expected)) if detected.name() != expected.name() => {
+                warn!(
+                    "{}",
+                    t!(
+                        "ui.warning_format_mismatch",
+                        file = file_path.display(),
+                        expected = expected.name(),
+                        detected = detected.name()
+                    )
+                );
+                Some(detected)
+            }
+            (Some(detected), _) => Some(detected),
+            (None, by_extension) => by_extension,
+        }
+    }
+
+    /// 拡張子が一致する最初の形式を返す
+    ///
+    /// どの形式の拡張子とも一致しない場合は、別名の表（`EXTENSION_ALIASES`）も調べる。
+    pub fn find_by_extension(&self, file_path: &Path) -> Option<&dyn Extractor> {
+        self.extractors
+            .iter()
+            .find(|e| e.matches_extension(file_path))
+            .or_else(|| {
+                let ext = resolve_extension_alias(file_path)?;
+                self.extractors
+                    .iter()
+                    .find(|e| e.extensions().contains(&ext))
+            })
+            .map(|e| e.as_ref())
+    }
+
+    /// ファイル先頭のマジックナンバーが一致する最初の形式を返す
+    pub fn find_by_content(&self, file_path: &Path, header: &[u8]) -> Option<&dyn Extractor> {
+        self.extractors
+            .iter()
+            .find(|e| e.detect(file_path, header))
+            .map(|e| e.as_ref())
+    }
+
+    /// 登録されている全拡張子と別名（ファイル選択ダイアログのフィルタ用）
+    pub fn extensions(&self) -> Vec<&'static str> {
+        let aliases = EXTENSION_ALIASES
+            .iter()
+            .filter(|(_, ext)| self.extractors.iter().any(|e| e.extensions().contains(ext)))
+            .map(|(alias, _)| *alias);
+
+        self.extractors
+            .iter()
+            .flat_map(|e| e.extensions().iter().copied())
+            .chain(aliases)
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{lha_archive, zip_archive, Fixture, TarBuilder};
+    use bzip2::write::BzEncoder;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+    use std::fs;
+    use std::io::Write;
+
+    /// `file_name` として置いた `data` を扱う形式の名前
+    fn detected(file_name: &str, data: &[u8]) -> Option<&'static str> {
+        let fixture = Fixture::new(file_name, data);
+        Registry::with_builtin()
+            .find(&fixture.archive)
+            .map(|extractor| extractor.name())
+    }
+
+    fn gzip(data: &[u8]) -> Vec<u8> {
+        let mut encoder = GzEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data).unwrap();
+        encoder.finish().unwrap()
+    }
+
+    #[test]
+    fn detects_format_from_content() {
+        let zip = zip_archive(&[("a.txt", b"a")]);
+        let tar = TarBuilder::new().file("a.txt", b"a").build();
+        assert_eq!(detected("a.zip", &zip), Some("ZIP"));
+        assert_eq!(detected("a.tar", &tar), Some("TAR"));
+        assert_eq!(detected("a.tar.gz", &gzip(&tar)), Some("TAR.GZ"));
+        assert_eq!(detected("a.lzh", &lha_archive("a.txt", b"a")), Some("LHA"));
+    }
+
+    #[test]
+    fn detects_renamed_archives() {
+        // 拡張子が形式と無関係
+        let zip = zip_archive(&[("a.txt", b"a")]);
+        assert_eq!(detected("download.bin", &zip), Some("ZIP"));
+        assert_eq!(detected("download", &zip), Some("ZIP"));
+        assert_eq!(detected("a.dat", &lha_archive("a.txt", b"a")), Some("LHA"));
+        let tar = TarBuilder::new().file("a.txt", b"a").build();
+        assert_eq!(detected("backup.gz", &gzip(&tar)), Some("TAR.GZ"));
+    }
+
+    #[test]
+    fn prefers_content_over_mismatched_extension() {
+        // 警告を出したうえで、内容から判定した形式を使う
+        let zip = zip_archive(&[("a.txt", b"a")]);
+        assert_eq!(detected("a.rar", &zip), Some("ZIP"));
+        assert_eq!(detected("a.7z", &zip), Some("ZIP"));
+        assert_eq!(detected("a.zip", &lha_archive("a.txt", b"a")), Some("LHA"));
+        assert_eq!(detected("a.tar.gz", &gzip(b"plain text")), Some("GZIP"));
+    }
+
+    #[test]
+    fn falls_back_to_extension() {
+        // マジックナンバーを持たない、または内容から判定できない場合は拡張子で判定する
+        let text = b"not an archive\n";
+        assert_eq!(detected("a.tar", text), Some("TAR"));
+        assert_eq!(detected("a.gz", text), Some("GZIP"));
+        assert_eq!(detected("A.ZIP", text), Some("ZIP"));
+
+        let fixture = Fixture::new("a.zip", b"");
+        fs::remove_file(&fixture.archive).unwrap();
+        let registry = Registry::with_builtin();
+        assert_eq!(
+            registry.find(&fixture.archive).map(|e| e.name()),
+            Some("ZIP")
+        );
+    }
+
+    #[test]
+    fn rejects_unknown_files() {
+        assert_eq!(detected("notes.txt", b"plain text\n"), None);
+        assert_eq!(detected("notes", b""), None);
+        assert_eq!(
+            detected("notes.dat", b"# -lh5- is the default method of LHA\n"),
+            None
+        );
+    }
+
+    #[test]
+    fn strips_the_matched_format_extension() {
+        let registry = Registry::with_builtin();
+        let stem = |file_name: &'static str| {
+            let path = Path::new(file_name);
+            strip_format_extension(path, registry.find_by_extension(path)?)
+        };
+
+        for (file_name, expected) in [
+            ("a.tar.gz", "a"),
+            ("a.TGZ", "a"),
+            ("a.tar.lz4", "a"),
+            ("a.tar.lzma", "a"),
+            ("a.tar.Z", "a"),
+            ("a.tzst", "a"),
+            ("a.cpio.zst", "a"),
+            ("a.tbz2", "a"),
+            ("a.tlz", "a"),
+            ("book.epub", "book"),
+            ("v1.2.zip", "v1.2"),
+            ("data.gz", "data"),
+        ] {
+            assert_eq!(stem(file_name), Some(expected), "{file_name}");
+        }
+        // 名前が拡張子だけの場合は除かない
+        assert_eq!(stem(".tar.gz"), None);
+
+        // 選ばれた形式の拡張子でなければ除かない
+        let path = Path::new("a.tar.gz");
+        assert_eq!(strip_format_extension(path, &ZipExtractor), None);
+        assert_eq!(
+            strip_format_extension(Path::new("a.cbz"), &RarExtractor),
+            None
+        );
+    }
+
+    #[test]
+    fn resolves_extension_aliases() {
+        let registry = Registry::with_builtin();
+        for (file_name, expected) in [
+            ("a.tbz2", "TAR.BZ2"),
+            ("a.tbz", "TAR.BZ2"),
+            ("a.txz", "TAR.XZ"),
+            ("a.tlz", "TAR.LZMA"),
+            ("a.taz", "TAR.Z"),
+            ("a.jar", "ZIP"),
+            ("a.apk", "ZIP"),
+            ("a.epub", "ZIP"),
+            ("a.cbz", "ZIP"),
+            ("a.cbr", "RAR"),
+            ("a.cb7", "7Z"),
+            ("A.CBR", "RAR"),
+        ] {
+            assert_eq!(
+                registry
+                    .find_by_extension(Path::new(file_name))
+                    .map(|e| e.name()),
+                Some(expected),
+                "{file_name}"
+            );
+        }
+
+        // 内容で判定しても、別名の形式と一致する
+        let tar = TarBuilder::new().file("a.txt", b"a").build();
+        let mut encoder = BzEncoder::new(Vec::new(), bzip2::Compression::default());
+        encoder.write_all(&tar).unwrap();
+        assert_eq!(
+            detected("a.tbz2", &encoder.finish().unwrap()),
+            Some("TAR.BZ2")
+        );
+        let zip = zip_archive(&[("mimetype", b"application/epub+zip")]);
+        assert_eq!(detected("book.epub", &zip), Some("ZIP"));
+
+        // すべての別名が登録されている形式を指し、ファイル選択ダイアログのフィルタにも含まれる
+        let extensions = registry.extensions();
+        for (alias, ext) in EXTENSION_ALIASES {
+            assert!(extensions.contains(ext), "{alias} -> {ext}");
+            assert!(extensions.contains(alias), "{alias}");
+        }
+    }
+}
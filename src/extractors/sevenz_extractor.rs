@@ -1,58 +1,316 @@
 use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
-use sevenz_rust::{Password, SevenZReader};
+use chrono::NaiveDateTime;
+use sevenz_rust::{Password, SevenZArchiveEntry, SevenZReader};
 use std::fs::{self, File};
+use std::io::{self, BufReader, Read, Seek, SeekFrom};
 use std::path::Path;
 
 // Import the i18n macro
 use rust_i18n::t;
 
 // Import common decode function
-use super::common::decode_filename_as_pathbuf;
-
-pub fn extract_7z(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    let mut file = File::open(file_path)?;
-    let file_size = file.metadata()?.len();
-    let mut sz = SevenZReader::new(&mut file, file_size, Password::empty())?;
-
-    fs::create_dir_all(extract_dir)?;
-
-    // プログレスバーの設定（7zも事前にエントリ数が分からないのでスピナー形式）
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_7z")));
-
-    sz.for_each_entries(|entry, reader| {
-        // ファイル名を適切にデコード
-        let decoded_name = decode_filename_as_pathbuf(entry.name.as_bytes());
-        let entry_path = extract_dir.join(&decoded_name);
-
-        // プログレスバーのメッセージを更新
-        if let Some(file_name) = decoded_name.file_name().and_then(|s| s.to_str()) {
-            pb.set_message(format!(
-                "{}",
-                t!("progress.extracting_file", file = file_name)
-            ));
+use super::common::{decode_filename, entry_output_path, new_spinner, unix_datetime};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::limits::LimitTracker;
+use super::links::{is_symlink_mode, DeferredLinks, LinkKind};
+use super::password::PasswordError;
+use super::sfx_extractor::{sfx_offset, SfxFormat};
+use super::volumes::{missing_next_volume_error, VolumeReader};
+
+/// 上位16ビットに Unix のファイルモードが格納されていることを示す属性（p7zip 拡張）
+const FILE_ATTRIBUTE_UNIX_EXTENSION: u32 = 0x8000;
+
+pub const SEVENZ_MAGIC: &[u8] = &[b'7', b'z', 0xbc, 0xaf, 0x27, 0x1c];
+/// シグネチャと末尾のヘッダの位置を含む開始ヘッダのサイズ
+const START_HEADER_LEN: usize = 32;
+
+pub struct SevenZExtractor;
+
+fn is_symlink_entry(entry: &SevenZArchiveEntry) -> bool {
+    let attributes = entry.windows_attributes;
+    entry.has_windows_attributes
+        && attributes & FILE_ATTRIBUTE_UNIX_EXTENSION != 0
+        && is_symlink_mode(attributes >> 16)
+}
+
+/// Windows の FILETIME（1601年1月1日からの100ナノ秒単位）をローカル時刻に変換する
+fn filetime_datetime(filetime: u64) -> Option<NaiveDateTime> {
+    const UNIX_EPOCH_OFFSET: i64 = 11_644_473_600;
+    unix_datetime((filetime / 10_000_000) as i64 - UNIX_EPOCH_OFFSET)
+}
+
+/// sevenz-rust のパスワード関連のエラーを `PasswordError` に変換する
+///
+/// ヘッダを開く段階でパスワードが必要になった場合はファイル一覧まで暗号化されている。
+/// 7z には復号の検証用データがないため、復号したデータが壊れている場合は誤ったパスワードとみなす。
+fn password_error(error: sevenz_rust::Error, opening: bool) -> anyhow::Error {
+    match error {
+        sevenz_rust::Error::PasswordRequired if opening => PasswordError::HeaderEncrypted.into(),
+        sevenz_rust::Error::PasswordRequired => PasswordError::Required.into(),
+        sevenz_rust::Error::MaybeBadPassword(_) => PasswordError::Wrong.into(),
+        error => error.into(),
+    }
+}
+
+impl SevenZExtractor {
+    /// アーカイブを開く（`.7z.001` のような分割アーカイブは全ボリュームを連結して読む）
+    fn open(
+        file_path: &Path,
+        password: Option<&str>,
+    ) -> Result<SevenZReader<BufReader<VolumeReader>>> {
+        let mut reader = VolumeReader::open(file_path)?;
+        // 自己解凍形式は、実行ファイルの中の 7z の先頭から読む
+        if let Some(offset) = sfx_offset(file_path, SfxFormat::SevenZ)? {
+            reader.set_base(offset);
         }
+        let size = reader.size();
 
-        if entry.is_directory() {
-            fs::create_dir_all(&entry_path)?;
-        } else {
-            if let Some(parent) = entry_path.parent() {
-                fs::create_dir_all(parent)?;
+        // 分割アーカイブの末尾のボリュームが欠けていると、ヘッダを読む途中で読み込みエラーになる。
+        // 開始ヘッダに記録された末尾のヘッダの位置と比べ、欠けているボリュームを示す。
+        if reader.volume_count() > 1 {
+            let mut start_header = [0; START_HEADER_LEN];
+            reader.read_exact(&mut start_header)?;
+            reader.seek(SeekFrom::Start(0))?;
+            let next_header_end = u64::from_le_bytes(start_header[12..20].try_into()?)
+                .saturating_add(u64::from_le_bytes(start_header[20..28].try_into()?))
+                .saturating_add(START_HEADER_LEN as u64);
+            if size < next_header_end {
+                if let Some(error) = missing_next_volume_error(file_path) {
+                    return Err(error);
+                }
             }
+        }
+
+        let password = password.map_or_else(Password::empty, Password::from);
+        SevenZReader::new(BufReader::new(reader), size, password)
+            .map_err(|e| password_error(e, true))
+    }
+}
+
+impl Extractor for SevenZExtractor {
+    fn name(&self) -> &'static str {
+        "7Z"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["7z"]
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        header.starts_with(SEVENZ_MAGIC)
+    }
+
+    fn list(&self, file_path: &Path, password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        // ファイル一覧が暗号化されていなければ、パスワードなしで一覧を取得できる
+        let sz = Self::open(file_path, password)?;
+
+        Ok(sz
+            .archive()
+            .files
+            .iter()
+            .map(|entry| ArchiveEntry {
+                name: decode_filename(entry.name.as_bytes()),
+                size: entry.size(),
+                // ソリッド圧縮ではブロック単位でしか圧縮後のサイズが分からない
+                compressed_size: None,
+                modified: entry
+                    .has_last_modified_date
+                    .then(|| filetime_datetime(entry.last_modified_date.to_raw()))
+                    .flatten(),
+                kind: if entry.is_directory() {
+                    EntryKind::Directory
+                } else if is_symlink_entry(entry) {
+                    EntryKind::Symlink
+                } else {
+                    EntryKind::File
+                },
+            })
+            .collect())
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let mut sz = Self::open(file_path, options.password.as_deref())?;
+
+        // プログレスバーの設定（7zも事前にエントリ数が分からないのでスピナー形式）
+        let pb = new_spinner(format!("{}", t!("progress.extracting_7z")));
+
+        let mut links = DeferredLinks::new(extract_dir, options.link_policy);
+
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+
+        let result = sz.for_each_entries(|entry, reader| {
+            tracker.start_entry().map_err(io::Error::other)?;
 
-            let mut output_file = File::create(&entry_path)?;
-            std::io::copy(reader, &mut output_file)?;
+            // ファイル名を適切にデコード
+            let decoded_name = decode_filename(entry.name.as_bytes());
+
+            // 解凍先の外を指すエントリはスキップ
+            let Some(entry_path) = entry_output_path(extract_dir, &decoded_name, options) else {
+                pb.inc(1);
+                return Ok(true);
+            };
+
+            // プログレスバーのメッセージを更新
+            if let Some(file_name) = Path::new(&decoded_name)
+                .file_name()
+                .and_then(|s| s.to_str())
+            {
+                pb.set_message(format!(
+                    "{}",
+                    t!("progress.extracting_file", file = file_name)
+                ));
+            }
+
+            // シンボリックリンクはリンク先のパスが内容として格納されている
+            if is_symlink_entry(entry) {
+                let mut target = Vec::new();
+                tracker.reader(reader).read_to_end(&mut target)?;
+                links.add(
+                    LinkKind::Symbolic,
+                    &decoded_name,
+                    entry_path,
+                    &decode_filename(&target),
+                );
+                pb.inc(1);
+                return Ok(true);
+            }
+
+            if entry.is_directory() {
+                fs::create_dir_all(&entry_path)?;
+            } else {
+                if let Some(parent) = entry_path.parent() {
+                    fs::create_dir_all(parent)?;
+                }
+
+                let mut output_file = File::create(&entry_path)?;
+                std::io::copy(&mut tracker.reader(reader), &mut output_file)?;
+            }
+
+            pb.inc(1);
+            Ok(true)
+        });
+
+        tracker.finish(
+            result
+                .map_err(|e| password_error(e, false))
+                .and_then(|()| links.finish()),
+        )
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let mut sz = Self::open(file_path, password)?;
+        // 最初のデータを復号できれば、パスワードは正しいと分かる
+        let mut verified = password.is_none();
+
+        // 末尾まで読むと sevenz-rust が CRC を検証する
+        sz.for_each_entries(|entry, reader| {
+            let name = decode_filename(entry.name.as_bytes());
+            let result = std::io::copy(reader, &mut std::io::sink());
+
+            // 最初のデータの読み込みでは破損とパスワードの誤りを区別できないため、
+            // エントリの失敗として報告せずにパスワードを尋ね直せるようにする
+            if !verified && entry.has_stream && entry.size() > 0 {
+                result?;
+                verified = true;
+                report(&name, Ok(()));
+            } else {
+                report(&name, result.map(|_| ()).map_err(Into::into));
+            }
+            Ok(true)
+        })
+        .map_err(|e| password_error(e, false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::Fixture;
+    use sevenz_rust::lzma::LZMA2Options;
+    use sevenz_rust::{AesEncoderOptions, SevenZWriter};
+    use std::io::Cursor;
+
+    const PASSWORD: &str = "secret";
+
+    /// エントリごとに別のブロックで AES 暗号化した 7z（ファイル一覧は暗号化しない）
+    fn encrypted_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+        let mut writer = SevenZWriter::new(Cursor::new(Vec::new())).unwrap();
+        writer.set_content_methods(vec![
+            AesEncoderOptions::new(PASSWORD.into()).into(),
+            LZMA2Options::with_preset(1).into(),
+        ]);
+        writer.set_encrypt_header(false);
+        for (name, contents) in files {
+            let mut entry = SevenZArchiveEntry::new();
+            entry.name = name.to_string();
+            entry.has_stream = true;
+            writer.push_archive_entry(entry, Some(*contents)).unwrap();
         }
+        writer.finish().unwrap().into_inner()
+    }
+
+    fn test_results(fixture: &Fixture, password: Option<&str>) -> Result<Vec<(String, bool)>> {
+        let mut results = Vec::new();
+        SevenZExtractor.test(&fixture.archive, password, &mut |name, result| {
+            results.push((name.to_string(), result.is_ok()))
+        })?;
+        Ok(results)
+    }
+
+    #[test]
+    fn reports_corrupt_entries_after_the_password_is_verified() {
+        let contents: Vec<Vec<u8>> = (0..3u8)
+            .map(|i| (0..2000u32).map(|j| (j % 97) as u8 ^ i).collect())
+            .collect();
+        let mut data = encrypted_archive(&[
+            ("a.bin", &contents[0]),
+            ("b.bin", &contents[1]),
+            ("c.bin", &contents[2]),
+        ]);
+
+        // 2つ目のエントリの暗号化されたデータを壊す
+        let archive = SevenZReader::new(
+            Cursor::new(&data),
+            data.len() as u64,
+            Password::from(PASSWORD),
+        )
+        .unwrap()
+        .archive()
+        .clone();
+        let offset = START_HEADER_LEN as u64 + archive.pack_pos + archive.pack_sizes[0] + 16;
+        data[offset as usize] ^= 0xff;
+
+        let fixture = Fixture::new("test.7z", &data);
+        assert_eq!(
+            test_results(&fixture, Some(PASSWORD)).unwrap(),
+            [
+                ("a.bin".to_string(), true),
+                ("b.bin".to_string(), false),
+                ("c.bin".to_string(), true),
+            ]
+        );
+    }
 
-        pb.inc(1);
-        Ok(true)
-    })?;
+    #[test]
+    fn reports_wrong_password_on_the_first_entry() {
+        let data = encrypted_archive(&[("a.bin", &[b'a'; 2000]), ("b.bin", &[b'b'; 2000])]);
+        let fixture = Fixture::new("test.7z", &data);
 
-    Ok(())
+        let error = test_results(&fixture, Some("wrong")).unwrap_err();
+        assert_eq!(error.downcast_ref(), Some(&PasswordError::Wrong));
+        assert_eq!(
+            test_results(&fixture, Some(PASSWORD)).unwrap(),
+            [("a.bin".to_string(), true), ("b.bin".to_string(), true)]
+        );
+    }
 }
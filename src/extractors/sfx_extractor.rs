@@ -0,0 +1,498 @@
+use anyhow::{anyhow, Result};
+use delharc::LhaHeader;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use super::extractor::{ArchiveEntry, ExtractOptions, Extractor, TestReport};
+use super::rar_extractor::RAR_MAGICS;
+use super::sevenz_extractor::SEVENZ_MAGIC;
+use super::{LhaExtractor, RarExtractor, SevenZExtractor, ZipExtractor};
+
+/// 実行ファイル（MS-DOS / PE 形式）のマジックナンバー
+pub const MZ_MAGIC: &[u8] = b"MZ";
+
+/// 実行ファイル本体の後ろで、埋め込まれたアーカイブを探す範囲
+///
+/// 自己解凍形式の設定やコメントがアーカイブの前に置かれることがあるため、余裕を持たせる。
+const MAX_SCAN_LEN: u64 = 16 * 1024 * 1024;
+
+/// ZIP の終端レコード（EOCD）の固定長部分のサイズ
+const EOCD_LEN: usize = 22;
+/// ZIP64 の終端レコードの位置を示すレコードのサイズ
+const ZIP64_LOCATOR_LEN: usize = 20;
+/// ZIP64 の終端レコードの固定長部分のサイズ
+const ZIP64_EOCD_LEN: u64 = 56;
+
+/// PE 形式のセクションヘッダのサイズ
+const SECTION_HEADER_LEN: u64 = 40;
+
+/// RAR 4.x のメインヘッダの種類
+const RAR4_MAIN_HEADER: u8 = 0x73;
+
+/// 自己解凍形式に埋め込まれたアーカイブの形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SfxFormat {
+    Zip,
+    SevenZ,
+    Rar,
+    Lha,
+}
+
+/// 自己解凍形式に埋め込まれたアーカイブ
+#[derive(Debug, Clone, Copy)]
+pub struct SfxPayload {
+    pub format: SfxFormat,
+    /// アーカイブの先頭の、実行ファイル内の位置
+    ///
+    /// ZIP はエントリの位置を記録し直した（`zip -A` など）場合は 0 になる。
+    pub offset: u64,
+}
+
+/// 実行ファイルを実行せずに調べ、埋め込まれたアーカイブを探す
+///
+/// ZIP は末尾の終端レコードから、記録された中央ディレクトリの位置と実際の位置の差で
+/// アーカイブの先頭を求める。7z・RAR・LHA は実行ファイル本体の後ろから署名を探し、
+/// ヘッダの CRC などで本物のアーカイブか確かめる。
+pub fn find_sfx_payload(file_path: &Path) -> io::Result<Option<SfxPayload>> {
+    let mut file = File::open(file_path)?;
+    let file_size = file.metadata()?.len();
+
+    let mut header = [0; 64];
+    if file_size < header.len() as u64 {
+        return Ok(None);
+    }
+    file.read_exact(&mut header)?;
+    if !header.starts_with(MZ_MAGIC) {
+        return Ok(None);
+    }
+
+    if let Some(offset) = find_zip_start(&mut file, file_size)? {
+        return Ok(Some(SfxPayload {
+            format: SfxFormat::Zip,
+            offset,
+        }));
+    }
+
+    // 読めないヘッダは無視して、ファイルの先頭から探す
+    let image_end = executable_image_end(&mut file, &header)
+        .unwrap_or(0)
+        .min(file_size);
+    let mut window = Vec::new();
+    file.seek(SeekFrom::Start(image_end))?;
+    (&mut file).take(MAX_SCAN_LEN).read_to_end(&mut window)?;
+
+    Ok((0..window.len()).find_map(|pos| {
+        let format = payload_format_at(&window[pos..])?;
+        Some(SfxPayload {
+            format,
+            offset: image_end + pos as u64,
+        })
+    }))
+}
+
+/// `file_path` が `format` の自己解凍形式なら、埋め込まれたアーカイブの開始位置を返す
+pub fn sfx_offset(file_path: &Path, format: SfxFormat) -> io::Result<Option<u64>> {
+    Ok(find_sfx_payload(file_path)?
+        .filter(|payload| payload.format == format)
+        .map(|payload| payload.offset))
+}
+
+/// 実行ファイル本体（ヘッダとセクション）の終わりの位置を求める
+fn executable_image_end(file: &mut File, header: &[u8]) -> io::Result<u64> {
+    let pe_offset = u64::from(read_u32(header, 0x3c));
+    let mut pe_header = [0; 24];
+    file.seek(SeekFrom::Start(pe_offset))?;
+    if file.read_exact(&mut pe_header).is_err() || !pe_header.starts_with(b"PE\0\0") {
+        // MS-DOS 形式はヘッダのページ数（512 バイト単位）と最後のページのバイト数で長さを示す
+        let last_page = u64::from(read_u16(header, 2));
+        let pages = u64::from(read_u16(header, 4));
+        return Ok(match last_page {
+            0 => pages * 512,
+            _ => pages.saturating_sub(1) * 512 + last_page,
+        });
+    }
+
+    // PE 形式はセクションのファイル内の範囲の終わりのうち、最も後ろの位置
+    let section_count = u64::from(read_u16(&pe_header, 6));
+    let optional_header_len = u64::from(read_u16(&pe_header, 20));
+    let table_start = pe_offset + 24 + optional_header_len;
+    let mut table = vec![0; (section_count * SECTION_HEADER_LEN) as usize];
+    file.seek(SeekFrom::Start(table_start))?;
+    file.read_exact(&mut table)?;
+
+    Ok(table
+        .chunks_exact(SECTION_HEADER_LEN as usize)
+        .map(|section| u64::from(read_u32(section, 20)) + u64::from(read_u32(section, 16)))
+        .fold(table_start + table.len() as u64, u64::max))
+}
+
+/// 末尾の終端レコードから、ZIP の先頭の位置を求める
+///
+/// 中央ディレクトリに記録された位置は ZIP の先頭からの位置なので、
+/// 終端レコードの直前にある実際の位置との差が、実行ファイル内の ZIP の先頭になる。
+fn find_zip_start(file: &mut File, file_size: u64) -> io::Result<Option<u64>> {
+    // 終端レコードはコメントを含めて末尾の 64KiB 以内にある
+    let tail_start = file_size.saturating_sub((EOCD_LEN + u16::MAX as usize) as u64);
+    let mut tail = Vec::new();
+    file.seek(SeekFrom::Start(tail_start))?;
+    file.read_to_end(&mut tail)?;
+    let Some(eocd_pos) = tail
+        .windows(4)
+        .rposition(|window| window == b"PK\x05\x06")
+        .filter(|&pos| pos + EOCD_LEN <= tail.len())
+    else {
+        return Ok(None);
+    };
+    let eocd = &tail[eocd_pos..eocd_pos + EOCD_LEN];
+    let eocd_offset = tail_start + eocd_pos as u64;
+
+    // ZIP64 は ZIP64 の終端レコードの位置で同じように求める
+    let (expected, actual, signature): (u64, u64, &[u8]) = match eocd_pos
+        .checked_sub(ZIP64_LOCATOR_LEN)
+        .map(|pos| &tail[pos..eocd_pos])
+        .filter(|locator| locator.starts_with(b"PK\x06\x07"))
+    {
+        Some(locator) => (
+            read_u64(locator, 8),
+            (eocd_offset - ZIP64_LOCATOR_LEN as u64)
+                .checked_sub(ZIP64_EOCD_LEN)
+                .unwrap_or(u64::MAX),
+            b"PK\x06\x06",
+        ),
+        None => {
+            let entries = read_u16(eocd, 10);
+            let directory_size = u64::from(read_u32(eocd, 12));
+            let directory_offset = u64::from(read_u32(eocd, 16));
+            if entries == 0 {
+                return Ok(None);
+            }
+            (
+                directory_offset,
+                eocd_offset.checked_sub(directory_size).unwrap_or(u64::MAX),
+                b"PK\x01\x02",
+            )
+        }
+    };
+    let Some(start) = actual.checked_sub(expected) else {
+        return Ok(None);
+    };
+
+    // 求めた位置に中央ディレクトリ（または ZIP64 の終端レコード）があることを確かめる
+    let mut found = [0; 4];
+    file.seek(SeekFrom::Start(actual))?;
+    if file.read_exact(&mut found).is_err() || found != signature {
+        return Ok(None);
+    }
+    Ok(Some(start))
+}
+
+/// `data` の先頭に 7z・RAR・LHA のアーカイブがあれば、その形式を返す
+///
+/// 展開プログラムの中に署名と同じバイト列が含まれることがあるため、
+/// 署名の後ろのヘッダのチェックサムも確かめる。
+fn payload_format_at(data: &[u8]) -> Option<SfxFormat> {
+    if data.starts_with(SEVENZ_MAGIC) {
+        // 開始ヘッダの後半 20 バイトの CRC32
+        let start_header = data.get(..32)?;
+        return (crc32fast::hash(&start_header[12..]) == read_u32(start_header, 8))
+            .then_some(SfxFormat::SevenZ);
+    }
+
+    if data.starts_with(RAR_MAGICS[0]) {
+        // RAR 4.x のメインヘッダの CRC は、CRC32 の下位 16 ビット
+        let block = &data[RAR_MAGICS[0].len()..];
+        let size = usize::from(read_u16(block.get(..7)?, 5));
+        let main_header = block.get(..size).filter(|_| size >= 7)?;
+        let crc = crc32fast::hash(&main_header[2..]) as u16;
+        return (main_header[2] == RAR4_MAIN_HEADER && crc == read_u16(main_header, 0))
+            .then_some(SfxFormat::Rar);
+    }
+
+    if data.starts_with(RAR_MAGICS[1]) {
+        // RAR 5.0 のヘッダは CRC32 の後に、可変長整数のサイズとヘッダの内容が続く
+        let block = &data[RAR_MAGICS[1].len()..];
+        let crc = read_u32(block.get(..4)?, 0);
+        let size_len = block[4..].iter().take(3).position(|&b| b & 0x80 == 0)? + 1;
+        let size = block[4..4 + size_len]
+            .iter()
+            .rev()
+            .fold(0, |size, &b| size << 7 | usize::from(b & 0x7f));
+        let header = block.get(4..4 + size_len + size)?;
+        return (crc32fast::hash(header) == crc).then_some(SfxFormat::Rar);
+    }
+
+    // LHA はヘッダの 2 バイト目から "-lh5-" などの圧縮方式IDが続く
+    let method = data.get(2..7)?;
+    if method.starts_with(b"-l") && method[4] == b'-' {
+        let header = LhaHeader::read(&mut &data[..]).ok()??;
+        return header
+            .compression_method()
+            .is_ok()
+            .then_some(SfxFormat::Lha);
+    }
+    None
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+}
+
+fn read_u64(bytes: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(bytes[offset..offset + 8].try_into().unwrap())
+}
+
+/// 自己解凍形式（SFX）の実行ファイル
+///
+/// 実行ファイルは実行せず、埋め込まれたアーカイブをその形式の展開処理で扱う。
+pub struct SfxExtractor;
+
+impl SfxExtractor {
+    /// 埋め込まれたアーカイブの形式の展開処理を返す
+    fn payload_extractor(file_path: &Path) -> Result<Box<dyn Extractor>> {
+        let payload = find_sfx_payload(file_path)?
+            .ok_or_else(|| anyhow!(t!("ui.error_sfx_no_payload").to_string()))?;
+
+        // ZIP・7z・LHA は `sfx_offset` で埋め込まれた位置から読む。
+        // unrar は自己解凍形式の RAR を自分で探して読めるため、そのまま渡す
+        Ok(match payload.format {
+            SfxFormat::Zip => Box::new(ZipExtractor),
+            SfxFormat::SevenZ => Box::new(SevenZExtractor),
+            SfxFormat::Rar => Box::new(RarExtractor),
+            SfxFormat::Lha => Box::new(LhaExtractor),
+        })
+    }
+}
+
+impl Extractor for SfxExtractor {
+    fn name(&self) -> &'static str {
+        "SFX"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["exe"]
+    }
+
+    fn detect(&self, file_path: &Path, header: &[u8]) -> bool {
+        // アーカイブを含まない実行ファイルは対象外
+        header.starts_with(MZ_MAGIC) && find_sfx_payload(file_path).is_ok_and(|p| p.is_some())
+    }
+
+    fn list(&self, file_path: &Path, password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        Self::payload_extractor(file_path)?.list(file_path, password)
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        Self::payload_extractor(file_path)?.extract(file_path, extract_dir, options)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        Self::payload_extractor(file_path)?.test(file_path, password, report)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{lha_archive, read_output, zip_archive, Fixture};
+    use std::io::{Cursor, Write};
+    use zip::write::FileOptions;
+
+    /// PE ヘッダの位置
+    const PE_OFFSET: usize = 0x40;
+
+    /// (ファイル内の位置, サイズ) のセクションを持つ PE 形式の実行ファイル
+    ///
+    /// セクションの中身には、埋め込まれたアーカイブと誤認しやすい署名を置く。
+    fn pe_stub(sections: &[(u32, u32)]) -> Vec<u8> {
+        let mut stub = vec![0u8; PE_OFFSET];
+        stub[..2].copy_from_slice(MZ_MAGIC);
+        stub[0x3c..0x40].copy_from_slice(&(PE_OFFSET as u32).to_le_bytes());
+        stub.extend_from_slice(b"PE\0\0");
+        let mut coff = [0u8; 20];
+        coff[2..4].copy_from_slice(&(sections.len() as u16).to_le_bytes());
+        stub.extend_from_slice(&coff);
+        for &(offset, size) in sections {
+            let mut section = [0u8; SECTION_HEADER_LEN as usize];
+            section[16..20].copy_from_slice(&size.to_le_bytes());
+            section[20..24].copy_from_slice(&offset.to_le_bytes());
+            stub.extend_from_slice(&section);
+        }
+
+        let end = sections
+            .iter()
+            .map(|&(offset, size)| (offset + size) as usize)
+            .fold(stub.len(), usize::max);
+        stub.resize(end, 0x90);
+        let code_start = end - 64;
+        stub[code_start..code_start + 6].copy_from_slice(SEVENZ_MAGIC);
+        stub[code_start + 32..code_start + 39].copy_from_slice(RAR_MAGICS[0]);
+        stub
+    }
+
+    /// 7z の開始ヘッダ（後続のヘッダは持たない）
+    fn sevenz_start_header(valid: bool) -> Vec<u8> {
+        let mut header = SEVENZ_MAGIC.to_vec();
+        header.extend_from_slice(&[0, 4]);
+        let rest = [0u8; 20];
+        let crc = crc32fast::hash(&rest) ^ u32::from(!valid);
+        header.extend_from_slice(&crc.to_le_bytes());
+        header.extend_from_slice(&rest);
+        header
+    }
+
+    fn rar4_main_header() -> Vec<u8> {
+        let mut main_header = vec![RAR4_MAIN_HEADER, 0, 0, 13, 0, 0, 0, 0, 0, 0, 0];
+        let crc = crc32fast::hash(&main_header) as u16;
+        main_header.splice(0..0, crc.to_le_bytes());
+        [RAR_MAGICS[0], &main_header].concat()
+    }
+
+    fn rar5_main_header() -> Vec<u8> {
+        let header = [3, 1, 0, 0];
+        let crc = crc32fast::hash(&header);
+        [RAR_MAGICS[1], &crc.to_le_bytes(), &header].concat()
+    }
+
+    fn payload(data: &[u8]) -> Option<SfxPayload> {
+        let fixture = Fixture::new("setup.exe", data);
+        find_sfx_payload(&fixture.archive).unwrap()
+    }
+
+    #[test]
+    fn finds_payloads_after_the_image() {
+        let stub = pe_stub(&[(0x200, 0x400), (0x600, 0x200)]);
+        assert_eq!(stub.len(), 0x800);
+
+        let cases = [
+            (SfxFormat::SevenZ, sevenz_start_header(true)),
+            (SfxFormat::Rar, rar4_main_header()),
+            (SfxFormat::Rar, rar5_main_header()),
+            (SfxFormat::Lha, lha_archive("a.txt", b"hello")),
+        ];
+        for (format, archive) in cases {
+            // 本物の前に、チェックサムの合わない署名を置く
+            let data = [
+                stub.as_slice(),
+                b"config",
+                &sevenz_start_header(false),
+                &archive,
+            ]
+            .concat();
+            let found = payload(&data).unwrap();
+            assert_eq!(found.format, format);
+            assert_eq!(found.offset, data.len() as u64 - archive.len() as u64);
+        }
+    }
+
+    #[test]
+    fn ignores_executables_without_payloads() {
+        let stub = pe_stub(&[(0x200, 0x400)]);
+        assert!(payload(&stub).is_none());
+        assert!(payload(&[stub.as_slice(), &sevenz_start_header(false)].concat()).is_none());
+
+        let mut not_executable = stub.clone();
+        not_executable[0] = b'X';
+        assert!(payload(&[not_executable, sevenz_start_header(true)].concat()).is_none());
+        assert!(payload(b"MZ").is_none());
+    }
+
+    #[test]
+    fn uses_dos_header_length_without_pe_header() {
+        // 3 ページ（最後のページは 0x10 バイト）の MS-DOS 形式
+        let mut stub = vec![0u8; 0x410];
+        stub[..2].copy_from_slice(MZ_MAGIC);
+        stub[2..4].copy_from_slice(&0x10u16.to_le_bytes());
+        stub[4..6].copy_from_slice(&3u16.to_le_bytes());
+        stub[0x100..0x106].copy_from_slice(SEVENZ_MAGIC);
+        let mut fake = sevenz_start_header(true);
+        fake.truncate(32);
+        stub[0x200..0x220].copy_from_slice(&fake);
+
+        let data = [stub, rar5_main_header()].concat();
+        let found = payload(&data).unwrap();
+        assert_eq!((found.format, found.offset), (SfxFormat::Rar, 0x410));
+    }
+
+    #[test]
+    fn extracts_zip_payloads() {
+        // ZIP 内の位置が ZIP の先頭からのもの
+        let stub = pe_stub(&[(0x200, 0x400)]);
+        let data = [stub.clone(), zip_archive(&[("docs/readme.txt", b"readme")])].concat();
+        let fixture = Fixture::new("setup.exe", &data);
+        assert!(SfxExtractor.detect(&fixture.archive, &data));
+        assert_eq!(
+            sfx_offset(&fixture.archive, SfxFormat::Zip).unwrap(),
+            Some(stub.len() as u64)
+        );
+        fixture
+            .extract(&SfxExtractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(
+            read_output(&fixture.out, "docs/readme.txt"),
+            Some(b"readme".to_vec())
+        );
+
+        // `zip -A` のように、位置を実行ファイルの先頭からに記録し直したもの
+        let mut cursor = Cursor::new(stub);
+        cursor.set_position(cursor.get_ref().len() as u64);
+        let mut writer = zip::ZipWriter::new(cursor);
+        writer
+            .start_file("adjusted.txt", FileOptions::default())
+            .unwrap();
+        writer.write_all(b"adjusted").unwrap();
+        let data = writer.finish().unwrap().into_inner();
+        let fixture = Fixture::new("setup.exe", &data);
+        assert_eq!(
+            sfx_offset(&fixture.archive, SfxFormat::Zip).unwrap(),
+            Some(0)
+        );
+        let results = fixture.test(&SfxExtractor).unwrap();
+        assert_eq!(results, vec![("adjusted.txt".to_string(), true)]);
+    }
+
+    #[test]
+    fn extracts_lha_payloads() {
+        let data = [
+            pe_stub(&[(0x200, 0x400)]),
+            lha_archive("hello.txt", b"hello"),
+        ]
+        .concat();
+        let fixture = Fixture::new("setup.exe", &data);
+
+        let entries = SfxExtractor.list(&fixture.archive, None).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].name, "hello.txt");
+        fixture
+            .extract(&SfxExtractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(
+            read_output(&fixture.out, "hello.txt"),
+            Some(b"hello".to_vec())
+        );
+    }
+
+    #[test]
+    fn reports_missing_payload() {
+        let fixture = Fixture::new("plain.exe", &pe_stub(&[(0x200, 0x400)]));
+        assert!(!SfxExtractor.detect(&fixture.archive, MZ_MAGIC));
+        assert!(SfxExtractor.list(&fixture.archive, None).is_err());
+    }
+}
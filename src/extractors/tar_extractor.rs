@@ -1,119 +1,281 @@
 use anyhow::Result;
 use bzip2::read::BzDecoder;
 use flate2::read::GzDecoder;
-use indicatif::{ProgressBar, ProgressStyle};
+use indicatif::ProgressBar;
 use std::fs::{self, File};
-use std::io::BufReader;
-use std::path::Path;
-use tar::Archive;
+use std::io::{BufRead, BufReader, Read};
+use std::path::{Path, PathBuf};
+use tar::{Archive, EntryType};
 use xz2::read::XzDecoder;
 
 // Import the i18n macro
 use rust_i18n::t;
 
 // Import common decode function
-use super::common::decode_filename_as_pathbuf;
+use super::bzip2_extractor::BZIP2_MAGIC;
+use super::common::{
+    decode_filename, entry_output_path, new_spinner, read_header_from, unix_datetime,
+};
+use super::compress_extractor::{LzwDecoder, COMPRESS_MAGIC};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::gzip_extractor::GZIP_MAGIC;
+use super::limits::LimitTracker;
+use super::links::{DeferredLinks, LinkKind};
+use super::lz4_extractor::{Lz4Decoder, LZ4_MAGIC};
+use super::lzip_extractor::{LzipDecoder, LZIP_MAGIC};
+use super::lzma_extractor::{is_lzma_header, lzma_decoder};
+use super::xz_extractor::XZ_MAGIC;
+use super::zstd_extractor::{zstd_decoder, ZSTD_MAGIC};
 
-pub fn extract_tar(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let mut archive = Archive::new(reader);
+/// TARアーカイブの外側の圧縮形式
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TarCompression {
+    None,
+    Gzip,
+    Xz,
+    Bzip2,
+    Zstd,
+    Lz4,
+    Lzma,
+    Lzip,
+    /// compress (.Z)
+    Compress,
+}
 
-    fs::create_dir_all(extract_dir)?;
+pub struct TarExtractor {
+    compression: TarCompression,
+}
 
-    // プログレスバーの設定（TARは事前にエントリ数が分からないのでスピナー形式）
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_tar")));
+impl TarExtractor {
+    pub fn new(compression: TarCompression) -> Self {
+        Self { compression }
+    }
 
-    extract_tar_entries(&mut archive, extract_dir, &pb)?;
+    fn open(&self, file_path: &Path) -> Result<Archive<Box<dyn Read>>> {
+        Ok(Archive::new(self.open_decoder(file_path)?))
+    }
 
-    Ok(())
+    /// 外側の圧縮を解いた TAR ストリームを開く
+    fn open_decoder(&self, file_path: &Path) -> Result<Box<dyn Read>> {
+        let file = File::open(file_path)?;
+        self.compression.decoder(BufReader::new(file))
+    }
 }
 
-pub fn extract_tar_gz(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let gz_decoder = GzDecoder::new(reader);
-    let mut archive = Archive::new(gz_decoder);
+impl TarCompression {
+    const ALL: [TarCompression; 9] = [
+        TarCompression::Gzip,
+        TarCompression::Xz,
+        TarCompression::Bzip2,
+        TarCompression::Zstd,
+        TarCompression::Lz4,
+        TarCompression::Lzma,
+        TarCompression::Lzip,
+        TarCompression::Compress,
+        TarCompression::None,
+    ];
 
-    fs::create_dir_all(extract_dir)?;
+    /// `data.tar.xz` のようなファイル名の拡張子から圧縮形式を判定する（.deb の中身など）
+    pub fn from_file_name(name: &str) -> Option<Self> {
+        let name = name.to_lowercase();
+        Self::ALL.into_iter().find(|compression| {
+            TarExtractor::new(*compression)
+                .extensions()
+                .iter()
+                .any(|ext| name.ends_with(&format!(".{}", ext)))
+        })
+    }
 
-    // プログレスバーの設定
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_tar_gz")));
+    /// 圧縮を解いた TAR ストリームを返す
+    pub fn decoder<'a, R: BufRead + 'a>(self, reader: R) -> Result<Box<dyn Read + 'a>> {
+        let decoder: Box<dyn Read + 'a> = match self {
+            TarCompression::None => Box::new(reader),
+            TarCompression::Gzip => Box::new(GzDecoder::new(reader)),
+            TarCompression::Xz => Box::new(XzDecoder::new(reader)),
+            TarCompression::Bzip2 => Box::new(BzDecoder::new(reader)),
+            TarCompression::Zstd => Box::new(zstd_decoder(reader)?),
+            TarCompression::Lz4 => Box::new(Lz4Decoder::new(reader)),
+            TarCompression::Lzma => Box::new(lzma_decoder(reader)?),
+            TarCompression::Lzip => Box::new(LzipDecoder::new(reader)?),
+            TarCompression::Compress => Box::new(LzwDecoder::new(reader)?),
+        };
+        Ok(decoder)
+    }
+}
 
-    extract_tar_entries(&mut archive, extract_dir, &pb)?;
+impl TarExtractor {
+    /// 圧縮を解いた先頭ブロックが TAR かどうか（単一ファイルの圧縮と区別する）
+    fn is_tar(&self, file_path: &Path) -> bool {
+        self.open_decoder(file_path)
+            .and_then(|decoder| Ok(read_header_from(decoder)?))
+            .map(|block| is_ustar(&block))
+            .unwrap_or(false)
+    }
+}
 
-    Ok(())
+/// POSIX ustar / GNU tar の先頭ブロックかどうか（オフセット 257 の "ustar"）
+fn is_ustar(block: &[u8]) -> bool {
+    block.get(257..262) == Some(b"ustar")
 }
 
-pub fn extract_tar_xz(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let xz_decoder = XzDecoder::new(reader);
-    let mut archive = Archive::new(xz_decoder);
+impl Extractor for TarExtractor {
+    fn name(&self) -> &'static str {
+        match self.compression {
+            TarCompression::None => "TAR",
+            TarCompression::Gzip => "TAR.GZ",
+            TarCompression::Xz => "TAR.XZ",
+            TarCompression::Bzip2 => "TAR.BZ2",
+            TarCompression::Zstd => "TAR.ZST",
+            TarCompression::Lz4 => "TAR.LZ4",
+            TarCompression::Lzma => "TAR.LZMA",
+            TarCompression::Lzip => "TAR.LZ",
+            TarCompression::Compress => "TAR.Z",
+        }
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        match self.compression {
+            TarCompression::None => &["tar"],
+            TarCompression::Gzip => &["tar.gz", "tgz"],
+            TarCompression::Xz => &["tar.xz"],
+            TarCompression::Bzip2 => &["tar.bz2"],
+            TarCompression::Zstd => &["tar.zst", "tzst"],
+            TarCompression::Lz4 => &["tar.lz4"],
+            TarCompression::Lzma => &["tar.lzma"],
+            TarCompression::Lzip => &["tar.lz"],
+            TarCompression::Compress => &["tar.z"],
+        }
+    }
 
-    fs::create_dir_all(extract_dir)?;
+    fn detect(&self, file_path: &Path, header: &[u8]) -> bool {
+        let outer_magic = match self.compression {
+            TarCompression::None => return is_ustar(header),
+            // .lzma には固有のマジックナンバーがない
+            TarCompression::Lzma => return is_lzma_header(header) && self.is_tar(file_path),
+            TarCompression::Gzip => GZIP_MAGIC,
+            TarCompression::Xz => XZ_MAGIC,
+            TarCompression::Bzip2 => BZIP2_MAGIC,
+            TarCompression::Zstd => ZSTD_MAGIC,
+            TarCompression::Lz4 => LZ4_MAGIC,
+            TarCompression::Lzip => LZIP_MAGIC,
+            TarCompression::Compress => COMPRESS_MAGIC,
+        };
+        header.starts_with(outer_magic) && self.is_tar(file_path)
+    }
 
-    // プログレスバーの設定
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_tar_xz")));
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        list_tar_entries(&mut self.open(file_path)?)
+    }
 
-    extract_tar_entries(&mut archive, extract_dir, &pb)?;
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let mut archive = self.open(file_path)?;
+        let tracker = LimitTracker::new(options.limits, file_path)?;
 
-    Ok(())
+        // プログレスバーの設定（TARは事前にエントリ数が分からないのでスピナー形式）
+        let message = match self.compression {
+            TarCompression::None => t!("progress.extracting_tar"),
+            TarCompression::Gzip => t!("progress.extracting_tar_gz"),
+            TarCompression::Xz => t!("progress.extracting_tar_xz"),
+            TarCompression::Bzip2 => t!("progress.extracting_tar_bz2"),
+            TarCompression::Zstd => t!("progress.extracting_tar_zst"),
+            TarCompression::Lz4 => t!("progress.extracting_tar_lz4"),
+            TarCompression::Lzma => t!("progress.extracting_tar_lzma"),
+            TarCompression::Lzip => t!("progress.extracting_tar_lz"),
+            TarCompression::Compress => t!("progress.extracting_tar_z"),
+        };
+        let pb = new_spinner(message.to_string());
+
+        let result = extract_tar_entries(&mut archive, extract_dir, options, &tracker, &pb);
+        tracker.finish(result)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        test_tar_entries(self.open(file_path)?, report)
+    }
 }
 
-pub fn extract_tar_bz2(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let bz_decoder = BzDecoder::new(reader);
-    let mut archive = Archive::new(bz_decoder);
+/// TAR のエントリの一覧を取得する
+pub fn list_tar_entries<R: Read>(archive: &mut Archive<R>) -> Result<Vec<ArchiveEntry>> {
+    let mut entries = Vec::new();
 
-    fs::create_dir_all(extract_dir)?;
+    for entry in archive.entries()? {
+        let entry = entry?;
+        let header = entry.header();
+        entries.push(ArchiveEntry {
+            name: decode_filename(&entry.path_bytes()),
+            size: header.size()?,
+            // TAR 全体を圧縮するため、エントリごとの圧縮後のサイズはない
+            compressed_size: None,
+            modified: header
+                .mtime()
+                .ok()
+                .and_then(|mtime| unix_datetime(mtime as i64)),
+            kind: match header.entry_type() {
+                EntryType::Directory => EntryKind::Directory,
+                EntryType::Symlink => EntryKind::Symlink,
+                EntryType::Link => EntryKind::Hardlink,
+                _ => EntryKind::File,
+            },
+        });
+    }
+    Ok(entries)
+}
 
-    // プログレスバーの設定
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_tar_bz2")));
+/// TAR のすべてのエントリを読み込んで検査する
+pub fn test_tar_entries<R: Read>(mut archive: Archive<R>, report: &mut TestReport) -> Result<()> {
+    // TAR のヘッダのチェックサムはエントリを読み込む際に tar クレートが検証する
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let name = decode_filename(&entry.path_bytes());
+        let result = std::io::copy(&mut entry, &mut std::io::sink());
 
-    extract_tar_entries(&mut archive, extract_dir, &pb)?;
+        // 外側の圧縮ストリームが壊れている場合は以降のエントリを読めない
+        let failed = result.is_err();
+        report(&name, result.map(|_| ()).map_err(Into::into));
+        if failed {
+            return Ok(());
+        }
+    }
 
+    // 終端ブロック以降も読み切り、gzip の CRC32 や xz のチェックサムを検証させる
+    std::io::copy(&mut archive.into_inner(), &mut std::io::sink())?;
     Ok(())
 }
 
 // 共通のTARエントリ処理関数
-fn extract_tar_entries<R: std::io::Read>(
+pub fn extract_tar_entries<R: Read>(
     archive: &mut Archive<R>,
     extract_dir: &Path,
+    options: &ExtractOptions,
+    tracker: &LimitTracker,
     pb: &ProgressBar,
 ) -> Result<()> {
+    let mut links = DeferredLinks::new(extract_dir, options.link_policy);
+
     for entry in archive.entries()? {
         let mut entry = entry?;
+        tracker.start_entry()?;
 
         // ファイル名を適切にデコード
         let path_bytes = entry.path_bytes();
-        let decoded_path = decode_filename_as_pathbuf(&path_bytes);
-        let output_path = extract_dir.join(&decoded_path);
+        let decoded_name = decode_filename(&path_bytes);
+        let decoded_path = PathBuf::from(&decoded_name);
+
+        // 解凍先の外を指すエントリはスキップ
+        let Some(output_path) = entry_output_path(extract_dir, &decoded_name, options) else {
+            pb.inc(1);
+            continue;
+        };
 
         // プログレスバーのメッセージを更新
         if let Some(file_name) = decoded_path.file_name().and_then(|s| s.to_str()) {
@@ -123,8 +285,150 @@ fn extract_tar_entries<R: std::io::Read>(
             ));
         }
 
+        // リンクは通常のエントリをすべて解凍した後に作成する
+        let entry_type = entry.header().entry_type();
+        if entry_type.is_symlink() || entry_type.is_hard_link() {
+            let kind = if entry_type.is_symlink() {
+                LinkKind::Symbolic
+            } else {
+                LinkKind::Hard
+            };
+            let target = entry
+                .link_name_bytes()
+                .map(|bytes| decode_filename(&bytes))
+                .unwrap_or_default();
+            links.add(kind, &decoded_name, output_path, &target);
+            pb.inc(1);
+            continue;
+        }
+
+        // TARのエントリはヘッダのサイズ分しか読み出されないため、事前に上限を確認できる
+        tracker.reserve(entry.header().size()?)?;
+
+        if let Some(parent) = output_path.parent() {
+            fs::create_dir_all(parent)?;
+        }
         entry.unpack(&output_path)?;
         pb.inc(1);
     }
-    Ok(())
+
+    links.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{read_output, Fixture, TarBuilder};
+
+    fn extract(data: Vec<u8>) -> Fixture {
+        let fixture = Fixture::new("test.tar", &data);
+        fixture
+            .extract(
+                &TarExtractor::new(TarCompression::None),
+                &ExtractOptions::default(),
+            )
+            .unwrap();
+        fixture
+    }
+
+    #[test]
+    fn extracts_regular_entries() {
+        let fixture = extract(
+            TarBuilder::new()
+                .dir("dir/")
+                .file("dir/a.txt", b"hello")
+                .file("b.txt", b"world")
+                .build(),
+        );
+        assert_eq!(read_output(&fixture.out, "dir/a.txt").unwrap(), b"hello");
+        assert_eq!(read_output(&fixture.out, "b.txt").unwrap(), b"world");
+    }
+
+    #[test]
+    fn skips_parent_traversal() {
+        let fixture = extract(
+            TarBuilder::new()
+                .file("../evil.txt", b"evil")
+                .file("a/../../../evil2.txt", b"evil")
+                .file("..\\evil3.txt", b"evil")
+                .file("safe.txt", b"safe")
+                .build(),
+        );
+        assert!(!fixture.root.join("work/evil.txt").exists());
+        assert!(!fixture.root.join("evil2.txt").exists());
+        assert!(!fixture.root.join("work/evil3.txt").exists());
+        assert_eq!(read_output(&fixture.out, "safe.txt").unwrap(), b"safe");
+    }
+
+    #[test]
+    fn rewrites_absolute_and_drive_paths_into_extract_dir() {
+        // 書き込まれても害のないよう、存在しないディレクトリを指す絶対パスを使う
+        let absolute = "/archr-test-nonexistent/abs.txt";
+        let fixture = extract(
+            TarBuilder::new()
+                .file(absolute, b"abs")
+                .file("C:drive.txt", b"drive")
+                .file("\\\\server\\share\\unc.txt", b"unc")
+                .build(),
+        );
+        assert_eq!(
+            read_output(&fixture.out, absolute.trim_start_matches('/')).unwrap(),
+            b"abs"
+        );
+        assert_eq!(read_output(&fixture.out, "drive.txt").unwrap(), b"drive");
+        assert_eq!(read_output(&fixture.out, "unc.txt").unwrap(), b"unc");
+    }
+
+    #[test]
+    fn test_mode_reports_entries() {
+        let fixture = Fixture::new(
+            "test.tar",
+            &TarBuilder::new()
+                .file("a.txt", b"a")
+                .file("b.txt", b"b")
+                .build(),
+        );
+        let results = fixture
+            .test(&TarExtractor::new(TarCompression::None))
+            .unwrap();
+        assert_eq!(
+            results,
+            vec![("a.txt".to_string(), true), ("b.txt".to_string(), true)]
+        );
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn skips_entries_through_existing_symlink() {
+        let fixture = Fixture::new(
+            "test.tar",
+            &TarBuilder::new().file("l/evil.txt", b"evil").build(),
+        );
+        std::os::unix::fs::symlink(&fixture.root, fixture.out.join("l")).unwrap();
+        fixture
+            .extract(
+                &TarExtractor::new(TarCompression::None),
+                &ExtractOptions::default(),
+            )
+            .unwrap();
+        assert!(!fixture.root.join("evil.txt").exists());
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn recreates_links_inside_extract_dir() {
+        let fixture = extract(
+            TarBuilder::new()
+                .file("dir/a.txt", b"hello")
+                .symlink("sym", "dir/a.txt")
+                .hardlink("hard", "dir/a.txt")
+                .build(),
+        );
+        assert_eq!(
+            fs::read_link(fixture.out.join("sym")).unwrap(),
+            Path::new("dir/a.txt")
+        );
+        assert_eq!(read_output(&fixture.out, "sym").unwrap(), b"hello");
+        assert_eq!(read_output(&fixture.out, "hard").unwrap(), b"hello");
+    }
 }
@@ -0,0 +1,267 @@
+use anyhow::Result;
+use delharc::crc::Crc16;
+use std::fs;
+use std::io::{Cursor, Write};
+use std::path::{Path, PathBuf};
+use tempfile::TempDir;
+use zip::write::FileOptions;
+
+use super::extractor::{ExtractOptions, Extractor};
+
+const TAR_BLOCK_LEN: usize = 512;
+
+/// 名前を検査せずにそのまま書き込む TAR の組み立て
+///
+/// tar クレートの `Builder` は `..` を含む名前を拒否するため、悪意のあるアーカイブは
+/// ヘッダを直接組み立てて作る。
+#[derive(Default)]
+pub struct TarBuilder {
+    data: Vec<u8>,
+}
+
+impl TarBuilder {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn file(self, name: &str, contents: &[u8]) -> Self {
+        self.entry(name, b'0', "", contents)
+    }
+
+    pub fn dir(self, name: &str) -> Self {
+        self.entry(name, b'5', "", &[])
+    }
+
+    pub fn symlink(self, name: &str, target: &str) -> Self {
+        self.entry(name, b'2', target, &[])
+    }
+
+    pub fn hardlink(self, name: &str, target: &str) -> Self {
+        self.entry(name, b'1', target, &[])
+    }
+
+    pub fn build(mut self) -> Vec<u8> {
+        self.data.extend_from_slice(&[0; TAR_BLOCK_LEN * 2]);
+        self.data
+    }
+
+    fn entry(mut self, name: &str, type_flag: u8, link_name: &str, contents: &[u8]) -> Self {
+        let mut header = [0u8; TAR_BLOCK_LEN];
+        header[..name.len()].copy_from_slice(name.as_bytes());
+        let mode: &[u8] = if type_flag == b'5' {
+            b"0000755"
+        } else {
+            b"0000644"
+        };
+        header[100..107].copy_from_slice(mode);
+        header[108..115].copy_from_slice(b"0000000");
+        header[116..123].copy_from_slice(b"0000000");
+        header[124..135].copy_from_slice(format!("{:011o}", contents.len()).as_bytes());
+        header[136..147].copy_from_slice(b"00000000000");
+        header[156] = type_flag;
+        header[157..157 + link_name.len()].copy_from_slice(link_name.as_bytes());
+        header[257..263].copy_from_slice(b"ustar\0");
+        header[263..265].copy_from_slice(b"00");
+
+        // チェックサムはチェックサム欄を空白として計算する
+        header[148..156].copy_from_slice(b"        ");
+        let checksum: u32 = header.iter().map(|&b| u32::from(b)).sum();
+        header[148..156].copy_from_slice(format!("{:06o}\0 ", checksum).as_bytes());
+
+        self.data.extend_from_slice(&header);
+        self.data.extend_from_slice(contents);
+        let padding = (TAR_BLOCK_LEN - contents.len() % TAR_BLOCK_LEN) % TAR_BLOCK_LEN;
+        self.data.extend(std::iter::repeat_n(0, padding));
+        self
+    }
+}
+
+/// 一時ディレクトリに置いたアーカイブと解凍先
+///
+/// 解凍先は `root/work/out` にあるため、`../..` で外に出たエントリも一時ディレクトリ内に収まり、
+/// テストで書き込まれていないことを確かめられる。
+pub struct Fixture {
+    _dir: TempDir,
+    pub root: PathBuf,
+    pub archive: PathBuf,
+    pub out: PathBuf,
+}
+
+impl Fixture {
+    pub fn new(file_name: &str, data: &[u8]) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let root = dir.path().canonicalize().unwrap();
+        let archive = root.join(file_name);
+        fs::write(&archive, data).unwrap();
+        let out = root.join("work").join("out");
+        fs::create_dir_all(&out).unwrap();
+        Self {
+            _dir: dir,
+            root,
+            archive,
+            out,
+        }
+    }
+
+    pub fn extract(&self, extractor: &dyn Extractor, options: &ExtractOptions) -> Result<()> {
+        extractor.extract(&self.archive, &self.out, options)
+    }
+
+    /// 検査モードの結果を（エントリ名, 成功したか）の一覧で返す
+    pub fn test(&self, extractor: &dyn Extractor) -> Result<Vec<(String, bool)>> {
+        let mut results = Vec::new();
+        extractor.test(&self.archive, None, &mut |name, result| {
+            results.push((name.to_string(), result.is_ok()))
+        })?;
+        Ok(results)
+    }
+}
+
+/// 解凍先の中のファイルを読む（存在しなければ `None`）
+pub fn read_output(dir: &Path, name: &str) -> Option<Vec<u8>> {
+    fs::read(dir.join(name)).ok()
+}
+
+/// (名前, 内容) のファイルを持つ ZIP
+pub fn zip_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut writer = zip::ZipWriter::new(Cursor::new(Vec::new()));
+    for (name, contents) in files {
+        writer.start_file(*name, FileOptions::default()).unwrap();
+        writer.write_all(contents).unwrap();
+    }
+    writer.finish().unwrap().into_inner()
+}
+
+/// 3つのエントリのうち、2つ目のエントリのデータを壊した ZIP
+pub fn corrupt_zip() -> Vec<u8> {
+    let text = b"the quick brown fox jumps over the lazy dog\n".repeat(50);
+    let mut data = zip_archive(&[("a.txt", &text), ("b.txt", &text), ("c.txt", &text)]);
+    let second = data
+        .windows(4)
+        .enumerate()
+        .filter(|(_, window)| *window == b"PK\x03\x04")
+        .nth(1)
+        .map(|(pos, _)| pos)
+        .unwrap();
+    // ローカルファイルヘッダ（30 バイト）と名前の後ろがデータ
+    data[second + 30 + 5 + 10] ^= 0xff;
+    data
+}
+
+/// 無圧縮（-lh0-）のファイル1つを持つ、レベル 0 ヘッダの LHA
+pub fn lha_archive(name: &str, contents: &[u8]) -> Vec<u8> {
+    let mut header = b"-lh0-".to_vec();
+    header.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+    header.extend_from_slice(&(contents.len() as u32).to_le_bytes());
+    header.extend_from_slice(&0x5822_1883u32.to_le_bytes());
+    header.extend_from_slice(&[0x20, 0, name.len() as u8]);
+    header.extend_from_slice(name.as_bytes());
+    let mut crc = Crc16::default();
+    crc.digest(contents);
+    header.extend_from_slice(&crc.sum16().to_le_bytes());
+
+    let checksum = header.iter().fold(0u8, |sum, &b| sum.wrapping_add(b));
+    let mut archive = vec![header.len() as u8, checksum];
+    archive.extend_from_slice(&header);
+    archive.extend_from_slice(contents);
+    archive.push(0);
+    archive
+}
+
+/// RAR 4.x のヘッダ（先頭の CRC は、種類以降の CRC32 の下位 16 ビット）
+fn rar4_block(head_type: u8, flags: u16, fields: &[u8]) -> Vec<u8> {
+    let mut block = vec![head_type];
+    block.extend_from_slice(&flags.to_le_bytes());
+    block.extend_from_slice(&((fields.len() + 7) as u16).to_le_bytes());
+    block.extend_from_slice(fields);
+    let crc = crc32fast::hash(&block) as u16;
+    [crc.to_le_bytes().to_vec(), block].concat()
+}
+
+/// RAR 4.x のファイルヘッダと、無圧縮（store）のデータ
+fn rar4_file(name: &str, flags: u16, data: &[u8], crc: u32, size: usize) -> Vec<u8> {
+    /// データが続くヘッダであることを示すフラグ
+    const LONG_BLOCK: u16 = 0x8000;
+
+    let mut fields = Vec::new();
+    fields.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    fields.extend_from_slice(&(size as u32).to_le_bytes());
+    // 作成した OS（Unix）、CRC、更新日時、展開に必要なバージョン、圧縮方式（store）
+    fields.push(3);
+    fields.extend_from_slice(&crc.to_le_bytes());
+    fields.extend_from_slice(&0x5822_1883u32.to_le_bytes());
+    fields.extend_from_slice(&[29, 0x30]);
+    fields.extend_from_slice(&(name.len() as u16).to_le_bytes());
+    fields.extend_from_slice(&0o100644u32.to_le_bytes());
+    fields.extend_from_slice(name.as_bytes());
+    [rar4_block(0x74, flags | LONG_BLOCK, &fields), data.to_vec()].concat()
+}
+
+/// 無圧縮（store）のファイルを持つ RAR 4.x
+///
+/// RAR を作成するツールを使わずにテストできるよう、ヘッダを直接組み立てる。
+pub fn rar4_archive(files: &[(&str, &[u8])]) -> Vec<u8> {
+    let mut archive = b"Rar!\x1a\x07\x00".to_vec();
+    archive.extend(rar4_block(0x73, 0, &[0; 6]));
+    for (name, contents) in files {
+        archive.extend(rar4_file(
+            name,
+            0,
+            contents,
+            crc32fast::hash(contents),
+            contents.len(),
+        ));
+    }
+    archive.extend(rar4_block(0x7b, 0, &[]));
+    archive
+}
+
+/// 1つのファイルを `volume_count` 個のボリューム（`.partN.rar` 形式）に分割した RAR 4.x
+pub fn rar4_volumes(name: &str, contents: &[u8], volume_count: usize) -> Vec<Vec<u8>> {
+    /// メインヘッダのフラグ（分割アーカイブ・新しい名前の付け方・最初のボリューム）
+    const MAIN_VOLUME: u16 = 0x0001;
+    const MAIN_NEW_NUMBERING: u16 = 0x0010;
+    const MAIN_FIRST_VOLUME: u16 = 0x0100;
+    /// ファイルヘッダのフラグ（前・次のボリュームに続く）
+    const FILE_SPLIT_BEFORE: u16 = 0x0001;
+    const FILE_SPLIT_AFTER: u16 = 0x0002;
+    /// 終端ヘッダのフラグ（次のボリュームがある）
+    const END_NEXT_VOLUME: u16 = 0x0001;
+
+    let chunk_len = contents.len().div_ceil(volume_count);
+    let chunks: Vec<&[u8]> = contents.chunks(chunk_len).collect();
+    assert_eq!(chunks.len(), volume_count);
+
+    chunks
+        .iter()
+        .enumerate()
+        .map(|(i, chunk)| {
+            let first = i == 0;
+            let last = i == volume_count - 1;
+            let mut main_flags = MAIN_VOLUME | MAIN_NEW_NUMBERING;
+            let mut file_flags = 0;
+            let mut end_flags = 0;
+            if first {
+                main_flags |= MAIN_FIRST_VOLUME;
+            } else {
+                file_flags |= FILE_SPLIT_BEFORE;
+            }
+            if !last {
+                file_flags |= FILE_SPLIT_AFTER;
+                end_flags |= END_NEXT_VOLUME;
+            }
+            // 途中のボリュームの CRC はその部分のもの、最後のボリュームの CRC はファイル全体のもの
+            let crc = if last {
+                crc32fast::hash(contents)
+            } else {
+                crc32fast::hash(chunk)
+            };
+
+            let mut volume = b"Rar!\x1a\x07\x00".to_vec();
+            volume.extend(rar4_block(0x73, main_flags, &[0; 6]));
+            volume.extend(rar4_file(name, file_flags, chunk, crc, contents.len()));
+            volume.extend(rar4_block(0x7b, end_flags, &[]));
+            volume
+        })
+        .collect()
+}
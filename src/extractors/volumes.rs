@@ -0,0 +1,721 @@
+use anyhow::{anyhow, Result};
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use super::cab_extractor::read_cabinet_links;
+
+/// 分割アーカイブのボリュームの命名規則
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Naming {
+    /// `name.part1.rar`, `name.part2.rar`, ...（番号の桁数はセット内で揃っている）
+    RarParts {
+        part: String,
+        width: usize,
+        ext: String,
+    },
+    /// `name.rar`, `name.r00`, `name.r01`, ...（`r99` の次は `s00`）
+    RarOld { ext: String },
+    /// 7-Zip で分割した `name.7z.001`, `name.7z.002`, ...（単純にバイト列を分割したもの）
+    Numbered { ext: String, width: usize },
+    /// WinZip・Info-ZIP で分割した `name.z01`, `name.z02`, ..., `name.zip`（最後が `.zip`）
+    ZipSplit { ext: String },
+    /// 各キャビネットのヘッダに記録された前後のキャビネット名でつながる `.cab` の組
+    /// （名前に規則がないため、見つかったキャビネットと、その次に必要なキャビネットの名前を持つ）
+    Cabinet { names: Vec<String> },
+}
+
+/// キャビネットの組のキャビネット数の上限（前後のキャビネット名が循環している場合の対策）
+const MAX_CABINETS: usize = 1000;
+
+/// WinZip 形式の分割 ZIP で、最後のボリューム（`.zip`）を表すボリューム番号
+const ZIP_SPLIT_LAST: usize = usize::MAX;
+
+impl Naming {
+    /// `index` 番目（0 始まり）のボリュームのファイル名
+    fn file_name(&self, stem: &str, index: usize) -> String {
+        match self {
+            Naming::RarParts { part, width, ext } => {
+                format!("{}.{}{:0width$}.{}", stem, part, index + 1, ext)
+            }
+            Naming::RarOld { ext } if index == 0 => format!("{}.{}", stem, ext),
+            Naming::RarOld { ext } => {
+                let number = index - 1;
+                let letter = char::from(b'r' + (number / 100) as u8);
+                // 拡張子の大文字・小文字は最初のボリュームに合わせる
+                let letter = if ext.starts_with('R') {
+                    letter.to_ascii_uppercase()
+                } else {
+                    letter
+                };
+                format!("{}.{}{:02}", stem, letter, number % 100)
+            }
+            Naming::Numbered { ext, width } => {
+                format!("{}.{}.{:0width$}", stem, ext, index + 1)
+            }
+            Naming::ZipSplit { ext } => {
+                let letter = if ext.starts_with('Z') { 'Z' } else { 'z' };
+                format!("{}.{}{:02}", stem, letter, index + 1)
+            }
+            Naming::Cabinet { names } => names.get(index).cloned().unwrap_or_default(),
+        }
+    }
+}
+
+/// 分割アーカイブのボリュームの組
+///
+/// どのボリュームを指定しても同じ組になり、解凍は最初のボリュームから行う。
+#[derive(Debug, Clone)]
+pub struct VolumeSet {
+    dir: PathBuf,
+    stem: String,
+    naming: Naming,
+    /// 最初のボリュームから順に並べたボリューム
+    volumes: Vec<PathBuf>,
+    /// 番号つきのボリュームのうち、最初から連続して見つかった数
+    numbered: usize,
+}
+
+impl VolumeSet {
+    /// ファイル名から分割アーカイブのボリュームかどうかを判定し、組全体を返す
+    ///
+    /// 指定されたボリュームより前のボリューム（WinZip 形式では最後の `.zip` も）が
+    /// 見つからない場合は、見つからないボリュームの名前を示すエラーを返す。
+    pub fn find(file_path: &Path) -> Result<Option<Self>> {
+        if is_cabinet_name(file_path) {
+            return Self::find_cabinets(file_path);
+        }
+
+        let Some((stem, naming, index)) = file_path
+            .file_name()
+            .and_then(|name| name.to_str())
+            .and_then(parse_volume_name)
+        else {
+            return Ok(None);
+        };
+
+        let mut set = Self {
+            dir: file_path.parent().unwrap_or(Path::new("")).to_path_buf(),
+            stem,
+            naming,
+            volumes: Vec::new(),
+            numbered: 0,
+        };
+        while set.volume_path(set.volumes.len()).is_file() {
+            set.volumes.push(set.volume_path(set.volumes.len()));
+        }
+        set.numbered = set.volumes.len();
+
+        match &set.naming {
+            Naming::ZipSplit { ext } => {
+                // `.zip` は `.z01` がなければ通常の ZIP
+                if index == ZIP_SPLIT_LAST && set.numbered == 0 {
+                    return Ok(None);
+                }
+                if index != ZIP_SPLIT_LAST && set.numbered <= index {
+                    return Err(missing_volume_error(&set.next_volume()));
+                }
+                let last = set.dir.join(format!("{}.{}", set.stem, ext));
+                if !last.is_file() {
+                    return Err(missing_volume_error(&last));
+                }
+                set.volumes.push(last);
+            }
+            naming => {
+                if set.numbered <= index {
+                    return Err(missing_volume_error(&set.next_volume()));
+                }
+                // 旧形式の `name.rar` は、2つ目のボリュームがなければ通常のアーカイブ
+                if matches!(naming, Naming::RarOld { .. }) && set.numbered == 1 {
+                    return Ok(None);
+                }
+            }
+        }
+        Ok(Some(set))
+    }
+
+    /// キャビネットのヘッダの前後のキャビネット名をたどり、組全体を返す
+    ///
+    /// 前後のキャビネットを持たない（分割されていない）場合は `None` を返す。
+    fn find_cabinets(file_path: &Path) -> Result<Option<Self>> {
+        let dir = file_path.parent().unwrap_or(Path::new("")).to_path_buf();
+        let Ok(Some(mut links)) = read_cabinet_links(file_path) else {
+            return Ok(None);
+        };
+        if links.prev.is_none() && links.next.is_none() {
+            return Ok(None);
+        }
+
+        // 前のキャビネットをたどって最初のキャビネットを探す
+        let mut first = file_path.to_path_buf();
+        for _ in 0..MAX_CABINETS {
+            let Some(prev) = links.prev.take() else {
+                break;
+            };
+            let path = find_file_ignore_case(&dir, &prev)
+                .ok_or_else(|| missing_volume_error(&dir.join(&prev)))?;
+            links = read_cabinet_links(&path)?.ok_or_else(|| missing_volume_error(&path))?;
+            first = path;
+        }
+
+        // 最初のキャビネットから順に次のキャビネットをたどる
+        let mut names = vec![file_name_of(&first)];
+        let mut volumes = vec![first];
+        while let Some(next) = links.next.take() {
+            if volumes.len() >= MAX_CABINETS {
+                break;
+            }
+            let Some(path) = find_file_ignore_case(&dir, &next) else {
+                // 見つからないキャビネットは `next_volume` で案内する
+                names.push(next);
+                break;
+            };
+            links = read_cabinet_links(&path)?.ok_or_else(|| missing_volume_error(&path))?;
+            names.push(file_name_of(&path));
+            volumes.push(path);
+        }
+
+        let stem = Path::new(&names[0])
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .unwrap_or_default()
+            .to_string();
+        Ok(Some(Self {
+            dir,
+            stem,
+            numbered: volumes.len(),
+            naming: Naming::Cabinet { names },
+            volumes,
+        }))
+    }
+
+    /// 解凍時に開くボリューム
+    ///
+    /// 通常は最初のボリュームだが、WinZip 形式の分割 ZIP では最後の `.zip` になる
+    /// （中央ディレクトリが最後のボリュームにあり、拡張子で ZIP と判定できるため）。
+    pub fn archive_path(&self) -> &Path {
+        match self.naming {
+            Naming::ZipSplit { .. } => &self.volumes[self.volumes.len() - 1],
+            _ => &self.volumes[0],
+        }
+    }
+
+    /// すべてのボリューム（連結する順）
+    pub fn volumes(&self) -> &[PathBuf] {
+        &self.volumes
+    }
+
+    /// すべてのボリュームの合計サイズ
+    pub fn total_size(&self) -> Result<u64> {
+        let mut total = 0;
+        for volume in &self.volumes {
+            total += volume.metadata()?.len();
+        }
+        Ok(total)
+    }
+
+    /// ボリューム番号を除いたアーカイブの名前（`name.part1.rar` なら `name`）
+    pub fn stem(&self) -> &str {
+        &self.stem
+    }
+
+    /// 最後に見つかった番号つきのボリュームの次のボリューム（途中で見つからなくなった場合の案内用）
+    pub fn next_volume(&self) -> PathBuf {
+        self.volume_path(self.numbered)
+    }
+
+    fn volume_path(&self, index: usize) -> PathBuf {
+        self.dir.join(self.naming.file_name(&self.stem, index))
+    }
+}
+
+/// ボリュームが見つからないことを示すエラー
+pub fn missing_volume_error(volume: &Path) -> anyhow::Error {
+    anyhow!(t!("ui.error_missing_volume", volume = volume.display()).to_string())
+}
+
+/// 分割アーカイブの最後に見つかったボリュームの次が欠けていることを示すエラー
+///
+/// 分割アーカイブでない場合は `None` を返す。
+pub fn missing_next_volume_error(file_path: &Path) -> Option<anyhow::Error> {
+    VolumeSet::find(file_path)
+        .ok()
+        .flatten()
+        .map(|volume_set| missing_volume_error(&volume_set.next_volume()))
+}
+
+/// 拡張子が `.cab` かどうか
+fn is_cabinet_name(file_path: &Path) -> bool {
+    file_path
+        .extension()
+        .and_then(|ext| ext.to_str())
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("cab"))
+}
+
+fn file_name_of(path: &Path) -> String {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .unwrap_or_default()
+        .to_string()
+}
+
+/// `dir` 内の `name` のファイルを探す
+///
+/// キャビネットのヘッダに記録された名前は大文字・小文字が実際のファイル名と異なることがあるため、
+/// 見つからなければ大文字・小文字を区別せずに探す。
+fn find_file_ignore_case(dir: &Path, name: &str) -> Option<PathBuf> {
+    // 別のディレクトリを指す名前は扱わない
+    if name.is_empty() || name.contains(['/', '\\']) {
+        return None;
+    }
+    let path = dir.join(name);
+    if path.is_file() {
+        return Some(path);
+    }
+    let read_dir_path = if dir.as_os_str().is_empty() {
+        Path::new(".")
+    } else {
+        dir
+    };
+    fs::read_dir(read_dir_path)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .find(|entry| {
+            entry
+                .file_name()
+                .to_str()
+                .is_some_and(|file_name| file_name.eq_ignore_ascii_case(name))
+                && entry.path().is_file()
+        })
+        .map(|entry| dir.join(entry.file_name()))
+}
+
+/// ファイル名をボリューム番号を除いた名前・命名規則・ボリューム番号（0 始まり）に分解する
+fn parse_volume_name(file_name: &str) -> Option<(String, Naming, usize)> {
+    let (base, ext) = file_name.rsplit_once('.')?;
+
+    if ext.eq_ignore_ascii_case("rar") {
+        // name.part1.rar 形式
+        if let Some((stem, part)) = base.rsplit_once('.') {
+            if let Some((prefix, digits)) = part.get(..4).zip(part.get(4..)) {
+                if prefix.eq_ignore_ascii_case("part")
+                    && !digits.is_empty()
+                    && digits.bytes().all(|b| b.is_ascii_digit())
+                {
+                    let number: usize = digits.parse().ok()?;
+                    let naming = Naming::RarParts {
+                        part: prefix.to_string(),
+                        width: digits.len(),
+                        ext: ext.to_string(),
+                    };
+                    return Some((stem.to_string(), naming, number.checked_sub(1)?));
+                }
+            }
+        }
+        return Some((
+            base.to_string(),
+            Naming::RarOld {
+                ext: ext.to_string(),
+            },
+            0,
+        ));
+    }
+
+    // name.7z.001 / name.zip.001 形式
+    if ext.len() >= 3 && ext.bytes().all(|b| b.is_ascii_digit()) {
+        let (stem, archive_ext) = base.rsplit_once('.')?;
+        if !archive_ext.eq_ignore_ascii_case("7z") && !archive_ext.eq_ignore_ascii_case("zip") {
+            return None;
+        }
+        let number: usize = ext.parse().ok()?;
+        let naming = Naming::Numbered {
+            ext: archive_ext.to_string(),
+            width: ext.len(),
+        };
+        return Some((stem.to_string(), naming, number.checked_sub(1)?));
+    }
+
+    // name.z01 ... name.zip 形式
+    if ext.eq_ignore_ascii_case("zip") {
+        let naming = Naming::ZipSplit {
+            ext: ext.to_string(),
+        };
+        return Some((base.to_string(), naming, ZIP_SPLIT_LAST));
+    }
+    if let Some(digits) = ext.strip_prefix(['z', 'Z']) {
+        if digits.len() >= 2 && digits.bytes().all(|b| b.is_ascii_digit()) {
+            let number: usize = digits.parse().ok()?;
+            let zip_ext = if ext.starts_with('Z') { "ZIP" } else { "zip" };
+            let naming = Naming::ZipSplit {
+                ext: zip_ext.to_string(),
+            };
+            return Some((base.to_string(), naming, number.checked_sub(1)?));
+        }
+    }
+
+    // name.r00 形式（r00〜r99 の次は s00〜s99）
+    let bytes = ext.as_bytes();
+    if bytes.len() == 3
+        && matches!(bytes[0].to_ascii_lowercase(), b'r' | b's')
+        && bytes[1..].iter().all(u8::is_ascii_digit)
+    {
+        let number = usize::from(bytes[0].to_ascii_lowercase() - b'r') * 100
+            + usize::from(bytes[1] - b'0') * 10
+            + usize::from(bytes[2] - b'0');
+        let rar_ext = if bytes[0].is_ascii_uppercase() {
+            "RAR"
+        } else {
+            "rar"
+        };
+        let naming = Naming::RarOld {
+            ext: rar_ext.to_string(),
+        };
+        return Some((base.to_string(), naming, number + 1));
+    }
+
+    None
+}
+
+/// 分割アーカイブの全ボリュームを連結し、1つのファイルとして読むリーダー
+///
+/// 分割されていないアーカイブもボリューム1つの組として扱えるため、
+/// 形式ごとの処理は分割の有無を区別せずに読める。
+/// `patch` で登録したバイト列は、読み込んだ内容の該当する位置を上書きして返す。
+pub struct VolumeReader {
+    files: Vec<File>,
+    /// 各ボリュームの連結後の開始位置
+    starts: Vec<u64>,
+    size: u64,
+    /// 先頭として扱う連結後の位置（`pos` などはここからの位置）
+    base: u64,
+    pos: u64,
+    patches: Vec<(u64, Vec<u8>)>,
+}
+
+impl VolumeReader {
+    /// `file_path` が分割アーカイブのボリュームなら組全体を、そうでなければそのファイルだけを開く
+    pub fn open(file_path: &Path) -> Result<Self> {
+        let volumes = match VolumeSet::find(file_path)? {
+            Some(volume_set) => volume_set.volumes().to_vec(),
+            None => vec![file_path.to_path_buf()],
+        };
+
+        let mut files = Vec::with_capacity(volumes.len());
+        let mut starts = Vec::with_capacity(volumes.len());
+        let mut size = 0;
+        for volume in volumes {
+            let file = File::open(volume)?;
+            starts.push(size);
+            size += file.metadata()?.len();
+            files.push(file);
+        }
+
+        Ok(Self {
+            files,
+            starts,
+            size,
+            base: 0,
+            pos: 0,
+            patches: Vec::new(),
+        })
+    }
+
+    /// 連結後の合計サイズ（`set_base` で読み飛ばした部分を除く）
+    pub fn size(&self) -> u64 {
+        self.size - self.base
+    }
+
+    /// 連結後の `offset` より前を読み飛ばし、そこを先頭として読ませる
+    ///
+    /// 自己解凍形式の実行ファイルのように、アーカイブの前に別のデータがある場合に使う。
+    pub fn set_base(&mut self, offset: u64) {
+        self.base = offset.min(self.size);
+        self.pos = 0;
+    }
+
+    pub fn volume_count(&self) -> usize {
+        self.files.len()
+    }
+
+    /// `index` 番目のボリュームの連結後の開始位置
+    pub fn volume_start(&self, index: usize) -> Option<u64> {
+        self.starts
+            .get(index)
+            .map(|start| start.saturating_sub(self.base))
+    }
+
+    /// 連結後の `offset` からの内容を `bytes` に置き換えて読ませる
+    pub fn patch(&mut self, offset: u64, bytes: Vec<u8>) {
+        self.patches.push((offset, bytes));
+    }
+}
+
+impl Read for VolumeReader {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let pos = self.base + self.pos;
+        if pos >= self.size || buf.is_empty() {
+            return Ok(0);
+        }
+
+        // 現在位置を含むボリュームから、そのボリュームの終わりまでを読む
+        let index = self.starts.partition_point(|&start| start <= pos) - 1;
+        let volume_end = self.starts.get(index + 1).copied().unwrap_or(self.size);
+        let len = buf.len().min((volume_end - pos) as usize);
+        let file = &mut self.files[index];
+        file.seek(SeekFrom::Start(pos - self.starts[index]))?;
+        let read = file.read(&mut buf[..len])?;
+
+        for (offset, bytes) in &self.patches {
+            let start = self.pos.max(*offset);
+            let end = (self.pos + read as u64).min(offset + bytes.len() as u64);
+            if start < end {
+                buf[(start - self.pos) as usize..(end - self.pos) as usize]
+                    .copy_from_slice(&bytes[(start - offset) as usize..(end - offset) as usize]);
+            }
+        }
+
+        self.pos += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for VolumeReader {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let new_pos = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::End(offset) => self.size().checked_add_signed(offset),
+            SeekFrom::Current(offset) => self.pos.checked_add_signed(offset),
+        };
+        self.pos = new_pos.ok_or_else(|| {
+            io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "invalid seek to a negative position",
+            )
+        })?;
+        Ok(self.pos)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `dir` に `(ファイル名, 内容)` のファイルを作る
+    fn create_files(dir: &Path, files: &[(&str, &[u8])]) -> Vec<PathBuf> {
+        files
+            .iter()
+            .map(|(name, contents)| {
+                let path = dir.join(name);
+                fs::write(&path, contents).unwrap();
+                path
+            })
+            .collect()
+    }
+
+    fn read_all(reader: &mut VolumeReader) -> Vec<u8> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data).unwrap();
+        data
+    }
+
+    #[test]
+    fn parses_volume_names() {
+        let rar_parts = |part: &str, width, ext: &str| Naming::RarParts {
+            part: part.to_string(),
+            width,
+            ext: ext.to_string(),
+        };
+        let rar_old = |ext: &str| Naming::RarOld {
+            ext: ext.to_string(),
+        };
+        let numbered = |ext: &str, width| Naming::Numbered {
+            ext: ext.to_string(),
+            width,
+        };
+        let zip_split = |ext: &str| Naming::ZipSplit {
+            ext: ext.to_string(),
+        };
+
+        let cases = [
+            ("a.part1.rar", "a", rar_parts("part", 1, "rar"), 0),
+            ("a.b.Part03.RAR", "a.b", rar_parts("Part", 2, "RAR"), 2),
+            ("a.rar", "a", rar_old("rar"), 0),
+            ("a.r00", "a", rar_old("rar"), 1),
+            ("a.r99", "a", rar_old("rar"), 100),
+            ("A.S01", "A", rar_old("RAR"), 102),
+            ("a.7z.001", "a", numbered("7z", 3), 0),
+            ("a.ZIP.0012", "a", numbered("ZIP", 4), 11),
+            ("a.z01", "a", zip_split("zip"), 0),
+            ("A.Z10", "A", zip_split("ZIP"), 9),
+            ("a.zip", "a", zip_split("zip"), ZIP_SPLIT_LAST),
+        ];
+        for (file_name, stem, naming, index) in cases {
+            assert_eq!(
+                parse_volume_name(file_name),
+                Some((stem.to_string(), naming.clone(), index)),
+                "{file_name}"
+            );
+            // 分解した名前から元のファイル名に戻せる
+            if index != ZIP_SPLIT_LAST {
+                assert_eq!(naming.file_name(stem, index), file_name);
+            }
+        }
+
+        for file_name in [
+            "a.txt",
+            "rar",
+            "a.part0.rar",
+            "a.tar.001",
+            "a.7z.000",
+            "a.7z.01",
+            "a.z1",
+            "a.t00",
+        ] {
+            assert_eq!(parse_volume_name(file_name), None, "{file_name}");
+        }
+    }
+
+    #[test]
+    fn finds_the_set_from_any_volume() {
+        let sets: [(&[&str], &str, usize); 4] = [
+            (&["x.part1.rar", "x.part2.rar", "x.part3.rar"], "x", 0),
+            (&["x.rar", "x.r00", "x.r01"], "x", 0),
+            (&["x.7z.001", "x.7z.002", "x.7z.003"], "x", 0),
+            // 最後の `.zip` から解凍する
+            (&["x.z01", "x.z02", "x.zip"], "x", 2),
+        ];
+        for (names, stem, archive) in sets {
+            let dir = tempfile::tempdir().unwrap();
+            let files: Vec<(&str, &[u8])> =
+                names.iter().map(|name| (*name, &b"data"[..])).collect();
+            let volumes = create_files(dir.path(), &files);
+            // 同じディレクトリにある別の組や無関係なファイルは含めない
+            create_files(dir.path(), &[("y.part1.rar", b""), ("x.txt", b"")]);
+
+            for volume in &volumes {
+                let set = VolumeSet::find(volume).unwrap().unwrap();
+                assert_eq!(set.volumes(), volumes, "{}", volume.display());
+                assert_eq!(set.archive_path(), volumes[archive]);
+                assert_eq!(set.stem(), stem);
+                assert_eq!(set.total_size().unwrap(), 4 * volumes.len() as u64);
+            }
+        }
+    }
+
+    #[test]
+    fn treats_single_archives_as_unsplit() {
+        let dir = tempfile::tempdir().unwrap();
+        let files = create_files(
+            dir.path(),
+            &[("a.rar", b""), ("b.zip", b""), ("c.txt", b"")],
+        );
+        for file in files {
+            assert!(
+                VolumeSet::find(&file).unwrap().is_none(),
+                "{}",
+                file.display()
+            );
+        }
+    }
+
+    #[test]
+    fn reports_missing_volumes() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = |name: &str| {
+            let error = VolumeSet::find(&dir.path().join(name)).unwrap_err();
+            error.to_string()
+        };
+
+        // 2つ目が欠けている
+        create_files(dir.path(), &[("x.part1.rar", b""), ("x.part3.rar", b"")]);
+        assert!(missing("x.part3.rar").contains("x.part2.rar"));
+        let set = VolumeSet::find(&dir.path().join("x.part1.rar"))
+            .unwrap()
+            .unwrap();
+        assert_eq!(set.volumes().len(), 1);
+        assert_eq!(set.next_volume(), dir.path().join("x.part2.rar"));
+        assert_eq!(
+            missing_next_volume_error(&dir.path().join("x.part1.rar"))
+                .unwrap()
+                .to_string(),
+            missing("x.part3.rar")
+        );
+
+        // 最初のボリュームが欠けている
+        create_files(dir.path(), &[("y.7z.002", b"")]);
+        assert!(missing("y.7z.002").contains("y.7z.001"));
+        create_files(dir.path(), &[("z.r00", b"")]);
+        assert!(missing("z.r00").contains("z.rar"));
+
+        // WinZip 形式では最後の `.zip` も必要
+        create_files(dir.path(), &[("w.z01", b""), ("w.z02", b"")]);
+        assert!(missing("w.z01").contains("w.zip"));
+        create_files(dir.path(), &[("v.z02", b""), ("v.zip", b"")]);
+        assert!(missing("v.z02").contains("v.z01"));
+    }
+
+    #[test]
+    fn reads_across_volume_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(
+            dir.path(),
+            &[
+                ("x.7z.001", b"abc"),
+                ("x.7z.002", b"defg"),
+                ("x.7z.003", b"hi"),
+            ],
+        );
+        let mut reader = VolumeReader::open(&dir.path().join("x.7z.002")).unwrap();
+        assert_eq!(reader.volume_count(), 3);
+        assert_eq!(reader.size(), 9);
+        assert_eq!(read_all(&mut reader), b"abcdefghi");
+
+        let mut buf = [0; 6];
+        reader.seek(SeekFrom::Start(2)).unwrap();
+        reader.read_exact(&mut buf).unwrap();
+        assert_eq!(&buf, b"cdefgh");
+        reader.seek(SeekFrom::End(-2)).unwrap();
+        assert_eq!(read_all(&mut reader), b"hi");
+        reader.seek(SeekFrom::Current(-3)).unwrap();
+        assert_eq!(read_all(&mut reader), b"ghi");
+        assert!(reader.seek(SeekFrom::Current(-10)).is_err());
+
+        // 先頭を読み飛ばした場合は、そこからの位置になる
+        reader.set_base(2);
+        assert_eq!(reader.size(), 7);
+        assert_eq!(reader.volume_start(0), Some(0));
+        assert_eq!(reader.volume_start(1), Some(1));
+        assert_eq!(reader.volume_start(2), Some(5));
+        assert_eq!(reader.volume_start(3), None);
+        assert_eq!(read_all(&mut reader), b"cdefghi");
+    }
+
+    #[test]
+    fn patches_bytes_across_volume_boundaries() {
+        let dir = tempfile::tempdir().unwrap();
+        create_files(
+            dir.path(),
+            &[("x.z01", b"abc"), ("x.z02", b"defg"), ("x.zip", b"hi")],
+        );
+        let mut reader = VolumeReader::open(&dir.path().join("x.zip")).unwrap();
+        reader.patch(2, b"XYZ".to_vec());
+        reader.patch(8, b"!".to_vec());
+        assert_eq!(read_all(&mut reader), b"abXYZfgh!");
+
+        // 1バイトずつ読んでも同じ
+        reader.seek(SeekFrom::Start(0)).unwrap();
+        let mut data = Vec::new();
+        let mut byte = [0];
+        while reader.read(&mut byte).unwrap() == 1 {
+            data.push(byte[0]);
+        }
+        assert_eq!(data, b"abXYZfgh!");
+
+        // 位置は `set_base` で読み飛ばした後の位置
+        reader.set_base(1);
+        assert_eq!(read_all(&mut reader), b"bcXYZghi");
+    }
+}
@@ -1,38 +1,66 @@
 use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
-use std::fs::{self, File};
-use std::io::BufReader;
+use std::fs::File;
+use std::io::{BufReader, Read};
 use std::path::Path;
 use xz2::read::XzDecoder;
 
 // Import the i18n macro
 use rust_i18n::t;
 
-pub fn extract_xz(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let mut decoder = XzDecoder::new(reader);
-
-    fs::create_dir_all(extract_dir)?;
-
-    // プログレスバーの設定
-    let pb = ProgressBar::new_spinner();
-    pb.set_style(
-        ProgressStyle::default_spinner()
-            .template("{spinner:.green} {elapsed_precise} {msg}")
-            .unwrap(),
-    );
-    pb.set_message(format!("{}", t!("progress.extracting_xz")));
-
-    // .xz ファイルの元のファイル名を取得
-    let output_name = file_path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .unwrap_or("extracted");
-
-    let output_path = extract_dir.join(output_name);
-    let mut output_file = File::create(output_path)?;
-
-    std::io::copy(&mut decoder, &mut output_file)?;
-    Ok(())
+use super::common::{decompress_single_file, list_single_file, new_spinner, test_single_file};
+use super::extractor::{ArchiveEntry, ExtractOptions, Extractor, TestReport};
+
+/// xz ストリームのマジックナンバー
+pub const XZ_MAGIC: &[u8] = &[0xfd, b'7', b'z', b'X', b'Z', 0x00];
+
+pub struct XzExtractor;
+
+impl XzExtractor {
+    fn open(file_path: &Path) -> Result<impl Read> {
+        let file = File::open(file_path)?;
+        Ok(XzDecoder::new(BufReader::new(file)))
+    }
+}
+
+impl Extractor for XzExtractor {
+    fn name(&self) -> &'static str {
+        "XZ"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["xz"]
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        header.starts_with(XZ_MAGIC)
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        list_single_file(Self::open(file_path)?, file_path)
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let decoder = Self::open(file_path)?;
+
+        // プログレスバーの設定
+        let _pb = new_spinner(format!("{}", t!("progress.extracting_xz")));
+
+        decompress_single_file(decoder, file_path, extract_dir, options)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        _password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        // 末尾まで読むとxz のチェックサムが検証される
+        test_single_file(Self::open(file_path)?, file_path, report);
+        Ok(())
+    }
 }
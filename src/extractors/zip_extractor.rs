@@ -1,46 +1,294 @@
-use anyhow::Result;
-use indicatif::{ProgressBar, ProgressStyle};
+use anyhow::{anyhow, Result};
+use indicatif::ProgressBar;
 use std::fs::{self, File};
-use std::io::BufReader;
+use std::io::{BufReader, Read, Seek, SeekFrom};
 use std::path::{Path, PathBuf};
+use zip::read::ZipFile;
+use zip::result::ZipError;
 use zip::ZipArchive;
 
 // Import the i18n macro
 use rust_i18n::t;
 
 // Import common decode function
-use super::common::decode_filename;
+use super::common::{decode_filename, dos_datetime, entry_output_path, new_progress_bar};
+use super::extractor::{ArchiveEntry, EntryKind, ExtractOptions, Extractor, TestReport};
+use super::limits::LimitTracker;
+use super::links::{is_symlink_mode, DeferredLinks, LinkKind};
+use super::password::PasswordError;
+use super::sfx_extractor::{sfx_offset, SfxFormat};
+use super::volumes::{missing_next_volume_error, VolumeReader};
 
-pub fn extract_zip(file_path: &Path, extract_dir: &Path) -> Result<()> {
-    let file = File::open(file_path)?;
-    let reader = BufReader::new(file);
-    let mut archive = ZipArchive::new(reader)?;
+/// ローカルファイルヘッダ / 空アーカイブの終端レコードのシグネチャ
+const ZIP_MAGICS: [&[u8]; 2] = [b"PK\x03\x04", b"PK\x05\x06"];
 
-    fs::create_dir_all(extract_dir)?;
+pub struct ZipExtractor;
 
-    // プログレスバーの設定
-    let pb = ProgressBar::new(archive.len() as u64);
-    pb.set_style(
-        ProgressStyle::default_bar()
-            .template("{spinner:.green} [{elapsed_precise}] [{bar:40.cyan/blue}] {pos}/{len} {msg}")
-            .unwrap()
-            .progress_chars("#>-"),
-    );
+impl ZipExtractor {
+    /// アーカイブを開く（分割 ZIP は全ボリュームを連結して読む）
+    fn open(file_path: &Path) -> Result<ZipArchive<BufReader<VolumeReader>>> {
+        let mut reader = VolumeReader::open(file_path)?;
+        // 自己解凍形式は、実行ファイルの中の ZIP の先頭から読む
+        if let Some(offset) = sfx_offset(file_path, SfxFormat::Zip)? {
+            reader.set_base(offset);
+        }
+        rebase_spanned_directory(&mut reader, file_path)?;
+        Ok(ZipArchive::new(BufReader::new(reader))?)
+    }
+}
+
+/// 終端レコード（EOCD）の固定長部分のサイズ
+const EOCD_LEN: usize = 22;
+/// 中央ディレクトリのファイルヘッダの固定長部分のサイズ
+const CENTRAL_HEADER_LEN: usize = 46;
+
+/// WinZip・Info-ZIP 形式の分割 ZIP を、連結した1つの ZIP として読めるようにする
+///
+/// 分割 ZIP の中央ディレクトリは、エントリの位置を「ボリューム番号とボリューム内の位置」で持つ。
+/// zip クレートはボリュームを区別しないため、連結後の位置に書き換えた中央ディレクトリと
+/// 終端レコードを読ませる。7-Zip 形式（`.zip.001`）は単純な分割なので書き換えは不要。
+fn rebase_spanned_directory(reader: &mut VolumeReader, file_path: &Path) -> Result<()> {
+    if reader.volume_count() < 2 {
+        return Ok(());
+    }
+
+    // 終端レコードはコメントを含めて末尾の 64KiB 以内にある
+    let tail_start = reader
+        .size()
+        .saturating_sub((EOCD_LEN + u16::MAX as usize) as u64);
+    let mut tail = Vec::new();
+    reader.seek(SeekFrom::Start(tail_start))?;
+    reader.read_to_end(&mut tail)?;
+    let Some(eocd_pos) = tail
+        .windows(4)
+        .rposition(|window| window == b"PK\x05\x06")
+        .filter(|&pos| pos + EOCD_LEN <= tail.len())
+    else {
+        // 分割 ZIP の終端レコードは最後のボリュームにあるため、見つからなければボリュームが欠けている
+        return Err(missing_next_volume_error(file_path).unwrap_or_else(|| {
+            ZipError::InvalidArchive("Could not find central directory end").into()
+        }));
+    };
+    let mut eocd = tail[eocd_pos..eocd_pos + EOCD_LEN].to_vec();
+
+    let disk_number = read_u16(&eocd, 4);
+    let directory_disk = read_u16(&eocd, 6);
+    let entries = read_u16(&eocd, 10);
+    let directory_size = read_u32(&eocd, 12);
+    let directory_offset = read_u32(&eocd, 16);
+    if disk_number == 0 {
+        return Ok(());
+    }
+    if usize::from(disk_number) + 1 != reader.volume_count() {
+        return Err(missing_next_volume_error(file_path)
+            .unwrap_or_else(|| ZipError::InvalidArchive("Invalid number of volumes").into()));
+    }
+    if entries == u16::MAX || directory_size == u32::MAX || directory_offset == u32::MAX {
+        return Err(anyhow!(t!("ui.error_spanned_zip64").to_string()));
+    }
+
+    let directory_start = reader
+        .volume_start(directory_disk.into())
+        .ok_or(ZipError::InvalidArchive("Invalid central directory disk"))?
+        + u64::from(directory_offset);
+    // 壊れた終端レコードの値で巨大なバッファを確保しないよう、連結後のサイズに収まるか確かめる
+    if directory_start + u64::from(directory_size) > reader.size() {
+        return Err(ZipError::InvalidArchive("Central directory exceeds archive size").into());
+    }
+    let mut directory = vec![0; directory_size as usize];
+    reader.seek(SeekFrom::Start(directory_start))?;
+    reader.read_exact(&mut directory)?;
+
+    // 各エントリのローカルヘッダの位置を連結後の位置に書き換える
+    let mut pos = 0;
+    while pos + CENTRAL_HEADER_LEN <= directory.len() && directory[pos..].starts_with(b"PK\x01\x02")
+    {
+        let header = &directory[pos..pos + CENTRAL_HEADER_LEN];
+        let header_len = CENTRAL_HEADER_LEN
+            + usize::from(read_u16(header, 28))
+            + usize::from(read_u16(header, 30))
+            + usize::from(read_u16(header, 32));
+        let disk = read_u16(header, 34);
+        let offset = read_u32(header, 42);
+        if disk == u16::MAX || offset == u32::MAX {
+            return Err(anyhow!(t!("ui.error_spanned_zip64").to_string()));
+        }
+
+        let absolute = reader
+            .volume_start(disk.into())
+            .ok_or(ZipError::InvalidArchive("Invalid local header disk"))?
+            + u64::from(offset);
+        let absolute = u32::try_from(absolute)
+            .map_err(|_| anyhow!(t!("ui.error_spanned_zip64").to_string()))?;
+        directory[pos + 34..pos + 36].copy_from_slice(&0u16.to_le_bytes());
+        directory[pos + 42..pos + 46].copy_from_slice(&absolute.to_le_bytes());
+        pos += header_len;
+    }
+
+    let directory_start_u32 = u32::try_from(directory_start)
+        .map_err(|_| anyhow!(t!("ui.error_spanned_zip64").to_string()))?;
+    eocd[4..6].copy_from_slice(&0u16.to_le_bytes());
+    eocd[6..8].copy_from_slice(&0u16.to_le_bytes());
+    eocd[8..10].copy_from_slice(&entries.to_le_bytes());
+    eocd[16..20].copy_from_slice(&directory_start_u32.to_le_bytes());
+
+    reader.patch(directory_start, directory);
+    reader.patch(tail_start + eocd_pos as u64, eocd);
+    reader.seek(SeekFrom::Start(0))?;
+    Ok(())
+}
+
+fn read_u16(bytes: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+}
+
+fn read_u32(bytes: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes([
+        bytes[offset],
+        bytes[offset + 1],
+        bytes[offset + 2],
+        bytes[offset + 3],
+    ])
+}
+
+/// エントリを開く（暗号化されている場合は ZipCrypto / AES として `password` で復号する）
+fn open_entry<'a, R: Read + Seek>(
+    archive: &'a mut ZipArchive<R>,
+    index: usize,
+    password: Option<&str>,
+) -> Result<ZipFile<'a>> {
+    match password {
+        Some(password) => archive
+            .by_index_decrypt(index, password.as_bytes())?
+            .map_err(|_| PasswordError::Wrong.into()),
+        None => match archive.by_index(index) {
+            Err(ZipError::UnsupportedArchive(ZipError::PASSWORD_REQUIRED)) => {
+                Err(PasswordError::Required.into())
+            }
+            result => Ok(result?),
+        },
+    }
+}
+
+/// すべてのエントリを開き、パスワードが必要か・正しいかを事前に確認する
+///
+/// 開くときは復号の検証用のヘッダを読むだけなので、展開はしない。
+/// 途中のエントリでパスワードの誤りに気づいて解凍をやり直すことがないようにする。
+fn check_password<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    password: Option<&str>,
+) -> Result<()> {
+    for i in 0..archive.len() {
+        open_entry(archive, i, password)?;
+    }
+    Ok(())
+}
+
+impl Extractor for ZipExtractor {
+    fn name(&self) -> &'static str {
+        "ZIP"
+    }
+
+    fn extensions(&self) -> &'static [&'static str] {
+        &["zip"]
+    }
+
+    fn detect(&self, _file_path: &Path, header: &[u8]) -> bool {
+        ZIP_MAGICS.iter().any(|magic| header.starts_with(magic))
+    }
+
+    fn list(&self, file_path: &Path, _password: Option<&str>) -> Result<Vec<ArchiveEntry>> {
+        let mut archive = Self::open(file_path)?;
+        let mut entries = Vec::with_capacity(archive.len());
 
-    pb.set_message(format!("{}", t!("progress.extracting_zip")));
+        for i in 0..archive.len() {
+            // 中身は読まないので復号や展開をしない raw で開く
+            let file = archive.by_index_raw(i)?;
+            let modified = file.last_modified();
+            entries.push(ArchiveEntry {
+                name: decode_filename(file.name_raw()),
+                size: file.size(),
+                compressed_size: Some(file.compressed_size()),
+                modified: dos_datetime(
+                    (modified.datepart() as u32) << 16 | modified.timepart() as u32,
+                ),
+                kind: if file.is_dir() {
+                    EntryKind::Directory
+                } else if file.unix_mode().is_some_and(is_symlink_mode) {
+                    EntryKind::Symlink
+                } else {
+                    EntryKind::File
+                },
+            });
+        }
+        Ok(entries)
+    }
+
+    fn extract(
+        &self,
+        file_path: &Path,
+        extract_dir: &Path,
+        options: &ExtractOptions,
+    ) -> Result<()> {
+        let mut archive = Self::open(file_path)?;
+        check_password(&mut archive, options.password.as_deref())?;
+
+        // プログレスバーの設定
+        let pb = new_progress_bar(
+            archive.len() as u64,
+            format!("{}", t!("progress.extracting_zip")),
+        );
+
+        let tracker = LimitTracker::new(options.limits, file_path)?;
+        let result = extract_zip_entries(&mut archive, extract_dir, options, &tracker, &pb);
+        tracker.finish(result)
+    }
+
+    fn test(
+        &self,
+        file_path: &Path,
+        password: Option<&str>,
+        report: &mut TestReport,
+    ) -> Result<()> {
+        let mut archive = Self::open(file_path)?;
+        check_password(&mut archive, password)?;
+
+        // 末尾まで読むと zip クレートが CRC32 を検証する
+        // エントリは個別に読めるため、破損したエントリがあっても残りを検査する
+        for i in 0..archive.len() {
+            let name = decode_filename(archive.by_index_raw(i)?.name_raw());
+            let result = open_entry(&mut archive, i, password)
+                .and_then(|mut file| Ok(std::io::copy(&mut file, &mut std::io::sink())?));
+            report(&name, result.map(|_| ()));
+        }
+        Ok(())
+    }
+}
+
+// ZIPエントリの解凍処理
+fn extract_zip_entries<R: Read + Seek>(
+    archive: &mut ZipArchive<R>,
+    extract_dir: &Path,
+    options: &ExtractOptions,
+    tracker: &LimitTracker,
+    pb: &ProgressBar,
+) -> Result<()> {
+    let mut links = DeferredLinks::new(extract_dir, options.link_policy);
 
     for i in 0..archive.len() {
-        let mut file = archive.by_index(i)?;
+        let mut file = open_entry(archive, i, options.password.as_deref())?;
+        tracker.start_entry()?;
 
         // ファイル名の文字エンコーディングを処理
-        let file_name = {
-            // 常に生のバイト列からファイル名を取得し、適切にデコード
-            let raw_name = file.name_raw();
-            let decoded_name = decode_filename(raw_name);
-            PathBuf::from(decoded_name)
-        };
+        // 常に生のバイト列からファイル名を取得し、適切にデコード
+        let decoded_name = decode_filename(file.name_raw());
+        let file_name = PathBuf::from(&decoded_name);
 
-        let outpath = extract_dir.join(&file_name);
+        // 解凍先の外を指すエントリはスキップ
+        let Some(outpath) = entry_output_path(extract_dir, &decoded_name, options) else {
+            pb.inc(1);
+            continue;
+        };
 
         // プログレスバーのメッセージを更新
         if let Some(file_name_str) = file_name.file_name().and_then(|s| s.to_str()) {
@@ -50,6 +298,20 @@ pub fn extract_zip(file_path: &Path, extract_dir: &Path) -> Result<()> {
             ));
         }
 
+        // シンボリックリンクはリンク先のパスが内容として格納されている
+        if file.unix_mode().is_some_and(is_symlink_mode) {
+            let mut target = Vec::new();
+            tracker.reader(&mut file).read_to_end(&mut target)?;
+            links.add(
+                LinkKind::Symbolic,
+                &decoded_name,
+                outpath,
+                &decode_filename(&target),
+            );
+            pb.inc(1);
+            continue;
+        }
+
         if file.name().ends_with('/') {
             // ディレクトリ
             fs::create_dir_all(&outpath)?;
@@ -59,7 +321,7 @@ pub fn extract_zip(file_path: &Path, extract_dir: &Path) -> Result<()> {
                 fs::create_dir_all(p)?;
             }
             let mut outfile = File::create(&outpath)?;
-            std::io::copy(&mut file, &mut outfile)?;
+            std::io::copy(&mut tracker.reader(&mut file), &mut outfile)?;
         }
 
         // ファイル権限を設定 (Unix系のみ)
@@ -73,5 +335,134 @@ pub fn extract_zip(file_path: &Path, extract_dir: &Path) -> Result<()> {
 
         pb.inc(1);
     }
-    Ok(())
+
+    links.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{read_output, zip_archive, Fixture};
+
+    /// 分割 ZIP の最後のボリュームに置く終端レコード
+    fn spanned_eocd(directory_size: u32, directory_offset: u32) -> Vec<u8> {
+        let mut eocd = b"PK\x05\x06".to_vec();
+        eocd.extend_from_slice(&1u16.to_le_bytes()); // このボリュームの番号
+        eocd.extend_from_slice(&0u16.to_le_bytes()); // 中央ディレクトリのあるボリューム
+        eocd.extend_from_slice(&1u16.to_le_bytes());
+        eocd.extend_from_slice(&1u16.to_le_bytes());
+        eocd.extend_from_slice(&directory_size.to_le_bytes());
+        eocd.extend_from_slice(&directory_offset.to_le_bytes());
+        eocd.extend_from_slice(&0u16.to_le_bytes());
+        eocd
+    }
+
+    #[test]
+    fn rejects_spanned_directory_larger_than_archive() {
+        for (size, offset) in [(0xffff_fff0, 0), (16, 0xffff_0000)] {
+            let fixture = Fixture::new("test.zip", &spanned_eocd(size, offset));
+            fs::write(
+                fixture.root.join("test.z01"),
+                b"PK\x07\x08 not a real volume",
+            )
+            .unwrap();
+
+            let error = ZipExtractor::open(&fixture.archive).err().unwrap();
+            assert!(
+                matches!(
+                    error.downcast_ref::<ZipError>(),
+                    Some(ZipError::InvalidArchive(_))
+                ),
+                "{error}"
+            );
+        }
+    }
+
+    /// WinZip 形式の分割 ZIP（`.z01` と `.zip`）を作る
+    ///
+    /// 先頭に分割の目印を置き、中央ディレクトリと終端レコードの位置をボリューム番号と
+    /// ボリューム内の位置で表す。`split_at` は連結後のどこで分けるか。
+    fn spanned_zip(zip: &[u8], split_at: usize) -> (Vec<u8>, Vec<u8>) {
+        let eocd_pos = zip.len() - EOCD_LEN;
+        let directory_offset = read_u32(zip, eocd_pos + 16) as usize;
+        let mut data = b"PK\x07\x08".to_vec();
+        data.extend_from_slice(&zip[..eocd_pos]);
+        let directory_start = directory_offset + 4;
+        assert!(split_at < directory_start);
+
+        let mut pos = directory_start;
+        while data[pos..].starts_with(b"PK\x01\x02") {
+            let header = &data[pos..pos + CENTRAL_HEADER_LEN];
+            let header_len = CENTRAL_HEADER_LEN
+                + usize::from(read_u16(header, 28))
+                + usize::from(read_u16(header, 30))
+                + usize::from(read_u16(header, 32));
+            let local = read_u32(header, 42) as usize + 4;
+            let (disk, offset) = if local < split_at {
+                (0u16, local)
+            } else {
+                (1, local - split_at)
+            };
+            data[pos + 34..pos + 36].copy_from_slice(&disk.to_le_bytes());
+            data[pos + 42..pos + 46].copy_from_slice(&(offset as u32).to_le_bytes());
+            pos += header_len;
+        }
+
+        let mut eocd = zip[eocd_pos..].to_vec();
+        eocd[4..6].copy_from_slice(&1u16.to_le_bytes());
+        eocd[6..8].copy_from_slice(&1u16.to_le_bytes());
+        eocd[16..20].copy_from_slice(&((directory_start - split_at) as u32).to_le_bytes());
+        data.extend_from_slice(&eocd);
+
+        let last = data.split_off(split_at);
+        (data, last)
+    }
+
+    #[test]
+    fn extracts_spanned_archives() {
+        let first: Vec<u8> = (0..500u32).map(|i| (i % 251) as u8).collect();
+        let zip = zip_archive(&[("a.bin", &first), ("b.txt", b"second entry")]);
+        // 1つ目のエントリの途中で分ける
+        let (volume1, volume2) = spanned_zip(&zip, 100);
+
+        let fixture = Fixture::new("x.zip", &volume2);
+        fs::write(fixture.root.join("x.z01"), &volume1).unwrap();
+
+        // `.z01` を指定しても最後の `.zip` から読む
+        let names: Vec<String> = ZipExtractor
+            .list(&fixture.root.join("x.z01"), None)
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, ["a.bin", "b.txt"]);
+
+        fixture
+            .extract(&ZipExtractor, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(read_output(&fixture.out, "a.bin"), Some(first));
+        assert_eq!(
+            read_output(&fixture.out, "b.txt"),
+            Some(b"second entry".to_vec())
+        );
+        assert!(fixture
+            .test(&ZipExtractor)
+            .unwrap()
+            .iter()
+            .all(|(_, ok)| *ok));
+    }
+
+    #[test]
+    fn reports_missing_spanned_volumes() {
+        let zip = zip_archive(&[("a.txt", b"hello")]);
+        let (volume1, mut volume2) = spanned_zip(&zip, 10);
+        // 3つのボリュームのうち、2つ目（`.z02`）が欠けている
+        let eocd_pos = volume2.len() - EOCD_LEN;
+        volume2[eocd_pos + 4..eocd_pos + 6].copy_from_slice(&2u16.to_le_bytes());
+        let fixture = Fixture::new("x.zip", &volume2);
+        fs::write(fixture.root.join("x.z01"), &volume1).unwrap();
+
+        let error = ZipExtractor::open(&fixture.archive).err().unwrap();
+        assert!(error.to_string().contains("x.z02"), "{error}");
+    }
 }
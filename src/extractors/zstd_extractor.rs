@@ -0,0 +1,69 @@
+use anyhow::Result;
+use std::io::BufRead;
+use zstd::stream::read::Decoder as ZstdDecoder;
+
+use super::common::SingleFileExtractor;
+
+/// zstd フレームのマジックナンバー
+pub const ZSTD_MAGIC: &[u8] = &[0x28, 0xb5, 0x2f, 0xfd];
+
+/// 展開時に許可するウィンドウサイズの上限（2^31 バイト、`zstd --long=31` 相当）
+const ZSTD_WINDOW_LOG_MAX: u32 = 31;
+
+/// zstd ストリームを展開するリーダーを作成する
+///
+/// 複数のフレームが連結されたファイルは続けて展開する。
+/// `--long` で圧縮されたファイルは既定の上限（128MiB）を超えるウィンドウを使うため、上限を引き上げておく。
+pub fn zstd_decoder<R: BufRead>(reader: R) -> Result<ZstdDecoder<'static, R>> {
+    let mut decoder = ZstdDecoder::with_buffer(reader)?;
+    decoder.window_log_max(ZSTD_WINDOW_LOG_MAX)?;
+    Ok(decoder)
+}
+
+/// Zstandard（.zst）
+///
+/// フレームにチェックサムがあれば、末尾まで読むと検証される。
+pub const ZSTD_EXTRACTOR: SingleFileExtractor = SingleFileExtractor {
+    name: "ZSTD",
+    extensions: &["zst"],
+    detect: |header| header.starts_with(ZSTD_MAGIC),
+    decoder: |reader| Ok(Box::new(zstd_decoder(reader)?)),
+    progress_key: "progress.extracting_zst",
+};
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::extractor::{ExtractOptions, Extractor};
+    use crate::extractors::test_support::{read_output, Fixture};
+
+    #[test]
+    fn extracts_concatenated_frames() {
+        let mut data = zstd::encode_all(&b"hello "[..], 3).unwrap();
+        data.extend(zstd::encode_all(&b"world"[..], 3).unwrap());
+        let fixture = Fixture::new("greeting.txt.zst", &data);
+
+        assert!(ZSTD_EXTRACTOR.detect(&fixture.archive, &data));
+        fixture
+            .extract(&ZSTD_EXTRACTOR, &ExtractOptions::default())
+            .unwrap();
+        assert_eq!(
+            read_output(&fixture.out, "greeting.txt").unwrap(),
+            b"hello world"
+        );
+
+        let entries = ZSTD_EXTRACTOR.list(&fixture.archive, None).unwrap();
+        assert_eq!(entries[0].size, 11);
+    }
+
+    #[test]
+    fn test_mode_reports_corrupt_input() {
+        let mut data = zstd::encode_all(&[b'x'; 4096][..], 3).unwrap();
+        data.truncate(data.len() - 4);
+        let fixture = Fixture::new("broken.zst", &data);
+        assert_eq!(
+            fixture.test(&ZSTD_EXTRACTOR).unwrap(),
+            vec![("broken".to_string(), false)]
+        );
+    }
+}
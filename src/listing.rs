@@ -0,0 +1,214 @@
+use anyhow::Result;
+use serde::Serialize;
+use std::fmt::Write;
+use std::path::{Path, PathBuf};
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use crate::extractors::{ArchiveEntry, Extractor};
+
+/// アーカイブ1つ分のエントリ一覧
+#[derive(Debug, Serialize)]
+pub struct ArchiveListing {
+    pub archive: PathBuf,
+    pub format: &'static str,
+    pub entries: Vec<ArchiveEntry>,
+}
+
+impl ArchiveListing {
+    pub fn new(
+        extractor: &dyn Extractor,
+        file_path: &Path,
+        password: Option<&str>,
+    ) -> Result<Self> {
+        Ok(Self {
+            archive: file_path.to_path_buf(),
+            format: extractor.name(),
+            entries: extractor.list(file_path, password)?,
+        })
+    }
+}
+
+/// 一覧を表形式で標準出力に表示する
+pub fn print_table(listing: &ArchiveListing) {
+    print!("{}", format_table(listing));
+}
+
+/// 一覧を表形式の文字列にする
+fn format_table(listing: &ArchiveListing) -> String {
+    let mut table = String::new();
+    // String への書き込みは失敗しない
+    let _ = writeln!(table, "{} ({})", listing.archive.display(), listing.format);
+
+    let columns = [
+        t!("list.size"),
+        t!("list.compressed_size"),
+        t!("list.modified"),
+        t!("list.kind"),
+        t!("list.name"),
+    ];
+    let _ = writeln!(
+        table,
+        "{} {}  {}  {}  {}",
+        pad_start(&columns[0], 12),
+        pad_start(&columns[1], 12),
+        pad_end(&columns[2], 19),
+        pad_end(&columns[3], 9),
+        columns[4]
+    );
+
+    for entry in &listing.entries {
+        let compressed_size = entry
+            .compressed_size
+            .map_or_else(|| "-".to_string(), |size| size.to_string());
+        let modified = entry.modified.map_or_else(
+            || "-".to_string(),
+            |modified| modified.format("%Y-%m-%d %H:%M:%S").to_string(),
+        );
+
+        let _ = writeln!(
+            table,
+            "{:>12} {:>12}  {:<19}  {:<9}  {}",
+            entry.size,
+            compressed_size,
+            modified,
+            entry.kind.as_str(),
+            entry.name
+        );
+    }
+
+    let total_size: u64 = listing.entries.iter().map(|entry| entry.size).sum();
+    let _ = writeln!(
+        table,
+        "{:>12}  {}",
+        total_size,
+        t!("list.summary", count = listing.entries.len())
+    );
+    table
+}
+
+/// すべての一覧を1つの JSON 配列として標準出力に表示する
+pub fn print_json(listings: &[ArchiveListing]) -> Result<()> {
+    println!("{}", serde_json::to_string_pretty(listings)?);
+    Ok(())
+}
+
+/// 全角文字を2桁として数えた表示幅
+fn display_width(s: &str) -> usize {
+    s.chars().map(|c| if c.is_ascii() { 1 } else { 2 }).sum()
+}
+
+fn pad_start(s: &str, width: usize) -> String {
+    format!(
+        "{}{}",
+        " ".repeat(width.saturating_sub(display_width(s))),
+        s
+    )
+}
+
+fn pad_end(s: &str, width: usize) -> String {
+    format!(
+        "{}{}",
+        s,
+        " ".repeat(width.saturating_sub(display_width(s)))
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::EntryKind;
+    use chrono::NaiveDate;
+    use serde_json::json;
+
+    fn listing() -> ArchiveListing {
+        let modified =
+            NaiveDate::from_ymd_opt(2024, 1, 2).and_then(|date| date.and_hms_opt(3, 4, 6));
+        ArchiveListing {
+            archive: PathBuf::from("docs.zip"),
+            format: "ZIP",
+            entries: vec![
+                ArchiveEntry {
+                    name: "docs/".to_string(),
+                    size: 0,
+                    compressed_size: Some(0),
+                    modified,
+                    kind: EntryKind::Directory,
+                },
+                ArchiveEntry {
+                    name: "docs/readme.txt".to_string(),
+                    size: 1234,
+                    compressed_size: None,
+                    modified: None,
+                    kind: EntryKind::File,
+                },
+            ],
+        }
+    }
+
+    #[test]
+    fn serializes_entries_as_json() {
+        let value = serde_json::to_value([listing()]).unwrap();
+        assert_eq!(
+            value,
+            json!([{
+                "archive": "docs.zip",
+                "format": "ZIP",
+                "entries": [
+                    {
+                        "name": "docs/",
+                        "size": 0,
+                        "compressed_size": 0,
+                        "modified": "2024-01-02T03:04:06",
+                        "kind": "directory"
+                    },
+                    {
+                        "name": "docs/readme.txt",
+                        "size": 1234,
+                        "compressed_size": null,
+                        "modified": null,
+                        "kind": "file"
+                    }
+                ]
+            }])
+        );
+    }
+
+    #[test]
+    fn serializes_every_entry_kind() {
+        for kind in [
+            EntryKind::File,
+            EntryKind::Directory,
+            EntryKind::Symlink,
+            EntryKind::Hardlink,
+        ] {
+            assert_eq!(serde_json::to_value(kind).unwrap(), json!(kind.as_str()));
+        }
+    }
+
+    #[test]
+    fn formats_table() {
+        let table = format_table(&listing());
+        let lines: Vec<&str> = table.lines().collect();
+        assert_eq!(lines[0], "docs.zip (ZIP)");
+        assert_eq!(
+            lines[2],
+            "           0            0  2024-01-02 03:04:06  directory  docs/"
+        );
+        assert_eq!(
+            lines[3],
+            "        1234            -  -                    file       docs/readme.txt"
+        );
+        assert!(lines[4].starts_with("        1234  "), "{}", lines[4]);
+        assert_eq!(lines.len(), 5);
+    }
+
+    #[test]
+    fn pads_wide_characters_by_display_width() {
+        assert_eq!(display_width("サイズ"), 6);
+        assert_eq!(pad_start("サイズ", 8), "  サイズ");
+        assert_eq!(pad_end("種類", 6), "種類  ");
+        assert_eq!(pad_end("too long", 3), "too long");
+    }
+}
@@ -2,7 +2,10 @@ use anyhow::{anyhow, Result};
 use clap::Parser;
 use log::{error, info};
 use rfd::MessageDialog;
+use std::collections::HashSet;
+use std::fs;
 use std::path::{Path, PathBuf};
+use std::process::ExitCode;
 
 // Initialize rust-i18n
 rust_i18n::i18n!("locales", fallback = "en");
@@ -11,15 +14,72 @@ use rust_i18n::t;
 mod extractors;
 use extractors::*;
 
+mod credentials;
+use credentials::PasswordSource;
+
+mod password_list;
+
+mod listing;
+use listing::ArchiveListing;
+
+mod staging;
+use staging::{install_interrupt_handler, StagingDir};
+
+mod verify;
+
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Args {
     /// Archive files to extract / 解凍する圧縮ファイルのパス
     files: Vec<PathBuf>,
+
+    /// Destination directory (default: next to each archive) / 解凍先のディレクトリ（デフォルト: 圧縮ファイルと同じ場所）
+    #[arg(short, long, value_name = "DIR")]
+    output: Option<PathBuf>,
+
+    /// List archive contents without extracting / 解凍せずに中身の一覧を表示する
+    #[arg(short, long, requires = "files")]
+    list: bool,
+
+    /// Print the list as JSON (with --list) / 一覧を JSON で出力する
+    #[arg(long, requires = "list")]
+    json: bool,
+
+    /// Verify archive integrity without extracting / 解凍せずに破損がないか検査する
+    #[arg(short, long, requires = "files", conflicts_with = "list")]
+    test: bool,
+
+    /// Password for encrypted archives / 暗号化されたアーカイブのパスワード
+    #[arg(short, long, env = "ARCHR_PASSWORD", hide_env_values = true)]
+    password: Option<String>,
+
+    /// How to handle symbolic/hard link entries / リンクのエントリの扱い
+    #[arg(long, value_enum, default_value_t = LinkPolicy::Recreate)]
+    links: LinkPolicy,
+
+    /// Maximum total extracted size, e.g. 500M, 20G (0 = unlimited) / 展開後の合計サイズの上限
+    #[arg(long, value_parser = parse_size, default_value_t = DEFAULT_MAX_TOTAL_SIZE)]
+    max_size: u64,
+
+    /// Maximum extracted size of a single entry (0 = unlimited) / 1エントリあたりの展開後のサイズの上限
+    #[arg(long, value_parser = parse_size, default_value_t = 0)]
+    max_entry_size: u64,
+
+    /// Maximum ratio of extracted size to archive size (0 = unlimited) / 圧縮率の上限
+    #[arg(long, default_value_t = DEFAULT_MAX_RATIO)]
+    max_ratio: u64,
+
+    /// Maximum number of entries (0 = unlimited) / エントリ数の上限
+    #[arg(long, default_value_t = DEFAULT_MAX_ENTRIES)]
+    max_entries: u64,
+
+    /// Also extract __MACOSX, .DS_Store, Thumbs.db and ._* files / OS が作成する不要なファイルも解凍する
+    #[arg(long)]
+    keep_junk: bool,
 }
 
 #[tokio::main]
-async fn main() {
+async fn main() -> ExitCode {
     env_logger::init();
 
     // Set locale based on system language, default to English
@@ -34,23 +94,61 @@ async fn main() {
     rust_i18n::set_locale(locale);
 
     let args = Args::parse();
+    install_interrupt_handler();
+    let registry = Registry::with_builtin();
+    let passwords = PasswordSource::new(args.password);
+
+    if args.list {
+        return list_archives(&registry, &passwords, &group_volumes(args.files), args.json);
+    }
+    if args.test {
+        return test_archives(&registry, &passwords, &group_volumes(args.files));
+    }
+
+    let options = ExtractOptions {
+        link_policy: args.links,
+        limits: ExtractLimits {
+            max_total_size: args.max_size,
+            max_entry_size: args.max_entry_size,
+            max_ratio: args.max_ratio,
+            max_entries: args.max_entries,
+        },
+        keep_junk: args.keep_junk,
+        password: None,
+    };
 
     // 引数が空の場合、ファイルダイアログを表示
-    let files_to_extract = if args.files.is_empty() {
-        match select_files().await {
+    let (files_to_extract, output_dir) = if args.files.is_empty() {
+        let files = match select_files(&registry).await {
             Some(files) => files,
             None => {
-                info!("{}", t!("ui.no_files_selected").to_string());
-                return;
+                info!("{}", t!("ui.no_files_selected"));
+                return ExitCode::SUCCESS;
             }
-        }
+        };
+
+        // 解凍先もダイアログで選択（キャンセルした場合は圧縮ファイルと同じ場所）
+        let output_dir = match args.output {
+            Some(output_dir) => Some(output_dir),
+            None => select_output_dir(&files).await,
+        };
+        (files, output_dir)
     } else {
-        args.files
+        (args.files, args.output)
     };
 
     // 複数ファイルの処理
-    for file_path in files_to_extract {
-        if let Err(e) = extract_archive(&file_path).await {
+    let mut exit_code = ExitCode::SUCCESS;
+    for file_path in group_volumes(files_to_extract) {
+        if let Err(e) = extract_archive(
+            &registry,
+            &file_path,
+            output_dir.as_deref(),
+            &options,
+            &passwords,
+        )
+        .await
+        {
             let error_msg = t!(
                 "ui.extraction_failed",
                 file = file_path.display(),
@@ -58,106 +156,232 @@ async fn main() {
             );
             error!("{}", error_msg);
             show_error_dialog(&error_msg);
+            exit_code = ExitCode::FAILURE;
         } else {
             let success_msg = t!("ui.extraction_complete", file = file_path.display());
             info!("{}", success_msg);
         }
     }
+    exit_code
 }
 
-async fn select_files() -> Option<Vec<PathBuf>> {
+/// 解凍せずにエントリの一覧を表示する
+fn list_archives(
+    registry: &Registry,
+    passwords: &PasswordSource,
+    files: &[PathBuf],
+    json: bool,
+) -> ExitCode {
+    let mut exit_code = ExitCode::SUCCESS;
+    let mut listings = Vec::new();
+
+    for file_path in files {
+        let listing = find_extractor(registry, file_path).and_then(|extractor| {
+            passwords.run(file_path, |password| {
+                ArchiveListing::new(extractor, file_path, password)
+            })
+        });
+
+        match listing {
+            Ok(listing) if json => listings.push(listing),
+            Ok(listing) => {
+                listing::print_table(&listing);
+                println!();
+            }
+            Err(e) => {
+                error!(
+                    "{}",
+                    t!("ui.list_failed", file = file_path.display(), error = e)
+                );
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+    }
+
+    if json {
+        if let Err(e) = listing::print_json(&listings) {
+            error!("{}", e);
+            exit_code = ExitCode::FAILURE;
+        }
+    }
+    exit_code
+}
+
+/// 解凍せずにすべてのエントリをデコードし、破損がないか検査する
+fn test_archives(registry: &Registry, passwords: &PasswordSource, files: &[PathBuf]) -> ExitCode {
+    let mut exit_code = ExitCode::SUCCESS;
+
+    for file_path in files {
+        let result = find_extractor(registry, file_path)
+            .and_then(|extractor| verify::test_archive(extractor, passwords, file_path));
+
+        match result {
+            Ok(summary) if summary.is_ok() => {}
+            Ok(_) => exit_code = ExitCode::FAILURE,
+            Err(e) => {
+                error!(
+                    "{}",
+                    t!("ui.test_failed", file = file_path.display(), error = e)
+                );
+                exit_code = ExitCode::FAILURE;
+            }
+        }
+        println!();
+    }
+    exit_code
+}
+
+async fn select_files(registry: &Registry) -> Option<Vec<PathBuf>> {
+    // 分割アーカイブの最初のボリューム（`.7z.001` など）も選択できるようにする
+    let mut extensions = registry.extensions();
+    extensions.push("001");
+
     let files = rfd::FileDialog::new()
-        .add_filter(
-            &t!("app.description").to_string(),
-            &[
-                "zip", "7z", "rar", "tar", "gz", "xz", "bz2", "tgz", "tar.gz", "tar.xz", "tar.bz2",
-                "lha", "lzh",
-            ],
-        )
-        .set_title(&t!("ui.select_files_title").to_string())
+        .add_filter(t!("app.description").to_string(), &extensions)
+        .set_title(t!("ui.select_files_title").to_string())
         .pick_files()?;
 
     Some(files)
 }
 
-async fn extract_archive(file_path: &Path) -> Result<()> {
-    if !file_path.exists() {
-        return Err(anyhow!(t!(
-            "ui.error_file_not_found",
-            file = file_path.display()
-        )
-        .to_string()));
+async fn select_output_dir(files: &[PathBuf]) -> Option<PathBuf> {
+    let mut dialog = rfd::FileDialog::new().set_title(t!("ui.select_output_title").to_string());
+    if let Some(parent_dir) = files.first().and_then(|file| file.parent()) {
+        dialog = dialog.set_directory(parent_dir);
     }
 
-    // 解凍先ディレクトリを決定（ファイルと同じディレクトリ）
-    let parent_dir = file_path
-        .parent()
-        .ok_or_else(|| anyhow!(t!("ui.error_no_parent_dir").to_string()))?;
+    dialog.pick_folder()
+}
 
-    let file_stem = file_path
-        .file_stem()
-        .and_then(|s| s.to_str())
-        .ok_or_else(|| anyhow!(t!("ui.error_no_filename").to_string()))?;
+async fn extract_archive(
+    registry: &Registry,
+    file_path: &Path,
+    output_dir: Option<&Path>,
+    options: &ExtractOptions,
+    passwords: &PasswordSource,
+) -> Result<()> {
+    // ファイルの内容と拡張子から対応する形式を選択
+    let extractor = find_extractor(registry, file_path)?;
 
-    // .tar.gz のような複合拡張子も考慮
-    let extract_dir_name = if file_stem.ends_with(".tar") {
-        &file_stem[..file_stem.len() - 4]
-    } else {
-        file_stem
+    // 解凍先ディレクトリを決定（指定がなければファイルと同じディレクトリ）
+    let parent_dir = match output_dir {
+        Some(output_dir) => {
+            fs::create_dir_all(output_dir)?;
+            output_dir
+        }
+        None => file_path
+            .parent()
+            .ok_or_else(|| anyhow!(t!("ui.error_no_parent_dir").to_string()))?,
     };
 
-    let mut extract_dir = parent_dir.join(extract_dir_name);
-
-    // 同名ディレクトリが存在する場合、連番をつける
-    extract_dir = get_unique_path(extract_dir);
+    // 分割アーカイブはボリューム番号を除いた名前にする
+    // それ以外は .tar.gz や .tbz2 のような複合拡張子・別名も含め、選ばれた形式の拡張子を除く
+    let volume_set = VolumeSet::find(file_path)?;
+    let extract_dir_name = match &volume_set {
+        Some(volume_set) => volume_set.stem(),
+        None => strip_format_extension(file_path, extractor)
+            .or_else(|| file_path.file_stem().and_then(|s| s.to_str()))
+            .ok_or_else(|| anyhow!(t!("ui.error_no_filename").to_string()))?,
+    };
 
     info!(
         "{}",
         t!(
             "status.extraction_start",
+            format = extractor.name(),
             source = file_path.display(),
-            dest = extract_dir.display()
+            dest = parent_dir.join(extract_dir_name).display()
         )
-        .to_string()
     );
 
-    // ファイル拡張子に基づいて適切な解凍関数を呼び出し
-    let extension = get_full_extension(file_path);
-    match extension.as_str() {
-        "zip" => extract_zip(file_path, &extract_dir)?,
-        "7z" => extract_7z(file_path, &extract_dir)?,
-        "rar" => extract_rar(file_path, &extract_dir)?,
-        "tar" => extract_tar(file_path, &extract_dir)?,
-        "tar.gz" | "tgz" => extract_tar_gz(file_path, &extract_dir)?,
-        "tar.xz" => extract_tar_xz(file_path, &extract_dir)?,
-        "tar.bz2" => extract_tar_bz2(file_path, &extract_dir)?,
-        "gz" => extract_gz(file_path, &extract_dir)?,
-        "xz" => extract_xz(file_path, &extract_dir)?,
-        "bz2" => extract_bz2(file_path, &extract_dir)?,
-        "lha" => extract_lha(file_path, &extract_dir)?,
-        "lzh" => extract_lzh(file_path, &extract_dir)?,
-        _ => {
-            return Err(anyhow!(t!(
-                "ui.error_unsupported_format",
-                format = extension
-            )
-            .to_string()))
-        }
+    // 隠しディレクトリに解凍し、成功した場合のみ最終的な名前に変更する
+    // （失敗した場合はステージングディレクトリの破棄時に途中までの結果も削除される）
+    // 暗号化されている場合はパスワードを尋ね、新しいステージングディレクトリでやり直す
+    let staging = passwords.run(file_path, |password| {
+        let staging = StagingDir::create(parent_dir, extract_dir_name.as_ref())?;
+        let options = ExtractOptions {
+            password: password.map(str::to_string),
+            ..options.clone()
+        };
+        extractor.extract(file_path, staging.path(), &options)?;
+        Ok(staging)
+    })?;
+
+    // 同名のファイル・ディレクトリが存在する場合は連番をつける
+    let output = staging.commit(parent_dir, extract_dir_name.as_ref())?;
+    if output.collapsed {
+        info!(
+            "{}",
+            t!("status.extraction_collapsed", dest = output.path.display())
+        );
     }
 
     Ok(())
 }
 
-fn get_full_extension(path: &Path) -> String {
+/// 分割アーカイブのボリュームを最初のボリュームに置き換え、同じ組が複数回処理されないようにする
+///
+/// ボリュームが欠けている場合はそのまま残し、処理時にエラーとして報告する。
+fn group_volumes(files: Vec<PathBuf>) -> Vec<PathBuf> {
+    let mut seen = HashSet::new();
+    files
+        .into_iter()
+        .map(|file_path| match VolumeSet::find(&file_path) {
+            Ok(Some(volume_set)) => volume_set.archive_path().to_path_buf(),
+            _ => file_path,
+        })
+        .filter(|file_path| seen.insert(file_path.clone()))
+        .collect()
+}
+
+/// ファイルの内容と拡張子から対応する形式を選択する
+fn find_extractor<'a>(registry: &'a Registry, file_path: &Path) -> Result<&'a dyn Extractor> {
+    if !file_path.exists() {
+        return Err(anyhow!(t!(
+            "ui.error_file_not_found",
+            file = file_path.display()
+        )
+        .to_string()));
+    }
+
+    // 分割アーカイブの途中のボリュームが欠けている場合は、その名前を示す
+    VolumeSet::find(file_path)?;
+
+    registry.find(file_path).ok_or_else(|| {
+        anyhow!(t!(
+            "ui.error_unsupported_format",
+            format = get_full_extension(registry, file_path)
+        )
+        .to_string())
+    })
+}
+
+/// エラーメッセージに示すファイルの拡張子
+fn get_full_extension(registry: &Registry, path: &Path) -> String {
     let file_name = path.file_name().and_then(|s| s.to_str()).unwrap_or("");
 
-    // .tar.gz, .tar.xz, .tar.bz2 のような複合拡張子をチェック
-    if file_name.ends_with(".tar.gz") || file_name.ends_with(".tgz") {
-        "tar.gz".to_string()
-    } else if file_name.ends_with(".tar.xz") {
-        "tar.xz".to_string()
-    } else if file_name.ends_with(".tar.bz2") {
-        "tar.bz2".to_string()
+    // .tar.gz のような複合拡張子は、対応する形式の拡張子と一致するものを示す
+    if let Some(ext) = registry
+        .find_by_extension(path)
+        .and_then(|extractor| extractor.matched_extension(path))
+    {
+        return ext.to_string();
+    }
+    // .tbz2 や .epub のような別名は、対応する形式の拡張子で示す
+    if let Some(ext) = resolve_extension_alias(path) {
+        return ext.to_string();
+    }
+
+    if let Some((base, number)) = file_name
+        .rsplit_once('.')
+        .filter(|(_, ext)| !ext.is_empty() && ext.bytes().all(|b| b.is_ascii_digit()))
+    {
+        // 分割アーカイブのボリューム番号（.001 など）だけでは形式が分からないため、元の拡張子も示す
+        let ext = Path::new(base)
+            .extension()
+            .and_then(|s| s.to_str())
+            .unwrap_or("");
+        format!("{}.{}", ext.to_lowercase(), number)
     } else {
         path.extension()
             .and_then(|s| s.to_str())
@@ -166,6 +390,30 @@ fn get_full_extension(path: &Path) -> String {
     }
 }
 
+/// "500M" や "20G" のようなサイズ指定をバイト数に変換する（単位は 1024 倍）
+fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let (number, unit) = match s.find(|c: char| !c.is_ascii_digit()) {
+        Some(i) => s.split_at(i),
+        None => (s, ""),
+    };
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1 << 10,
+        "M" | "MB" | "MIB" => 1 << 20,
+        "G" | "GB" | "GIB" => 1 << 30,
+        "T" | "TB" | "TIB" => 1 << 40,
+        _ => return Err(t!("ui.error_invalid_size", size = s).to_string()),
+    };
+
+    number
+        .parse::<u64>()
+        .ok()
+        .and_then(|n| n.checked_mul(multiplier))
+        .ok_or_else(|| t!("ui.error_invalid_size", size = s).to_string())
+}
+
 fn get_unique_path(mut path: PathBuf) -> PathBuf {
     let original_path = path.clone();
     let mut counter = 1;
@@ -186,8 +434,133 @@ fn get_unique_path(mut path: PathBuf) -> PathBuf {
 
 fn show_error_dialog(message: &str) {
     MessageDialog::new()
-        .set_title(&t!("ui.error_dialog_title").to_string())
+        .set_title(t!("ui.error_dialog_title").to_string())
         .set_description(message)
         .set_level(rfd::MessageLevel::Error)
         .show();
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use extractors::test_support::{
+        corrupt_zip, rar4_volumes, read_output, zip_archive, Fixture, TarBuilder,
+    };
+    use lz4_flex::frame::FrameEncoder;
+    use std::io::Write;
+
+    #[test]
+    fn test_mode_fails_on_corrupt_entries() {
+        let registry = Registry::with_builtin();
+        let passwords = PasswordSource::new(None);
+
+        let fixture = Fixture::new("good.zip", &zip_archive(&[("a.txt", b"a")]));
+        let good = fixture.archive.clone();
+        assert_eq!(
+            test_archives(&registry, &passwords, std::slice::from_ref(&good)),
+            ExitCode::SUCCESS
+        );
+
+        let bad = fixture.root.join("bad.zip");
+        fs::write(&bad, corrupt_zip()).unwrap();
+        assert_eq!(
+            test_archives(&registry, &passwords, &[good.clone(), bad]),
+            ExitCode::FAILURE
+        );
+        assert_eq!(
+            test_archives(&registry, &passwords, &[fixture.root.join("missing.zip")]),
+            ExitCode::FAILURE
+        );
+    }
+
+    #[tokio::test]
+    async fn extracts_each_volume_set_once() {
+        let contents: Vec<u8> = (0..3000u32).map(|i| (i % 251) as u8).collect();
+        let fixture = Fixture::new("other.zip", &zip_archive(&[("a.txt", b"a")]));
+        let volumes: Vec<PathBuf> = rar4_volumes("big.bin", &contents, 3)
+            .iter()
+            .enumerate()
+            .map(|(i, volume)| {
+                let path = fixture.root.join(format!("x.part{}.rar", i + 1));
+                fs::write(&path, volume).unwrap();
+                path
+            })
+            .collect();
+
+        // どのボリュームを何個渡しても、最初のボリュームとして1回だけ処理する
+        let files = group_volumes(vec![
+            volumes[1].clone(),
+            fixture.archive.clone(),
+            volumes[0].clone(),
+            volumes[2].clone(),
+        ]);
+        assert_eq!(files, [volumes[0].clone(), fixture.archive.clone()]);
+
+        let registry = Registry::with_builtin();
+        let passwords = PasswordSource::new(None);
+        for file_path in &files {
+            extract_archive(
+                &registry,
+                file_path,
+                Some(&fixture.out),
+                &ExtractOptions::default(),
+                &passwords,
+            )
+            .await
+            .unwrap();
+        }
+        let mut names: Vec<String> = fs::read_dir(&fixture.out)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        assert_eq!(names, ["a.txt", "big.bin"]);
+        assert_eq!(read_output(&fixture.out, "big.bin"), Some(contents));
+    }
+
+    #[tokio::test]
+    async fn names_output_folder_after_the_format_extension() {
+        let tar = TarBuilder::new()
+            .file("a.txt", b"a")
+            .file("b.txt", b"b")
+            .build();
+        let mut encoder = FrameEncoder::new(Vec::new());
+        encoder.write_all(&tar).unwrap();
+        let fixture = Fixture::new("data.tar.lz4", &encoder.finish().unwrap());
+
+        extract_archive(
+            &Registry::with_builtin(),
+            &fixture.archive,
+            Some(&fixture.out),
+            &ExtractOptions::default(),
+            &PasswordSource::new(None),
+        )
+        .await
+        .unwrap();
+        assert_eq!(read_output(&fixture.out, "data/b.txt"), Some(b"b".to_vec()));
+    }
+
+    #[test]
+    fn shows_full_extension_of_unsupported_files() {
+        let registry = Registry::with_builtin();
+        for (file_name, expected) in [
+            ("a.tar.gz", "tar.gz"),
+            ("a.TGZ", "tgz"),
+            ("a.tar.lz4", "tar.lz4"),
+            ("a.tar.lzma", "tar.lzma"),
+            ("a.cpio.xz", "cpio.xz"),
+            ("a.tbz2", "tar.bz2"),
+            ("a.cbr", "rar"),
+            ("a.7z.001", "7z.001"),
+            ("a.Foo.002", "foo.002"),
+            ("a.DAT", "dat"),
+            ("a", ""),
+        ] {
+            assert_eq!(
+                get_full_extension(&registry, Path::new(file_name)),
+                expected,
+                "{file_name}"
+            );
+        }
+    }
+}
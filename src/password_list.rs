@@ -0,0 +1,211 @@
+use anyhow::Result;
+use log::warn;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+// Import the i18n macro
+use rust_i18n::t;
+
+/// パスワード一覧ファイルの名前（1行に1つ、`#` で始まる行と空行は無視する）
+const LIST_FILE_NAME: &str = "passwords.txt";
+/// どのアーカイブでどのパスワードが使えたかを記録するファイルの名前
+const KNOWN_FILE_NAME: &str = "known-passwords.json";
+/// アーカイブの指紋に使う先頭・末尾の大きさ
+const FINGERPRINT_CHUNK: u64 = 1024 * 1024;
+
+/// 設定ディレクトリに置かれたパスワード一覧
+///
+/// 取引先ごとに決まったパスワードを毎回入力しなくて済むよう、入力を求める前に順に試す。
+/// 使えたパスワードはアーカイブのハッシュと対応づけて記録し、次回はそれを最初に試す。
+/// 記録にはパスワードそのものではなく、そのハッシュだけを保存する。
+#[derive(Default)]
+pub struct PasswordList {
+    passwords: Vec<String>,
+    /// アーカイブの指紋 → 使えたパスワードの SHA-256
+    known: HashMap<String, String>,
+    known_path: Option<PathBuf>,
+}
+
+impl PasswordList {
+    /// 設定ディレクトリから一覧を読み込む（ファイルがなければ空の一覧）
+    pub fn load() -> Self {
+        let Some(dir) = config_dir() else {
+            return Self::default();
+        };
+
+        let list_path = dir.join(LIST_FILE_NAME);
+        let passwords = match fs::read_to_string(&list_path) {
+            Ok(content) => parse_list(&content),
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => {
+                warn!(
+                    "{}",
+                    t!(
+                        "ui.warning_password_list_unreadable",
+                        file = list_path.display(),
+                        error = e
+                    )
+                );
+                Vec::new()
+            }
+        };
+
+        // 記録が壊れていても一覧を先頭から試すだけなので、読めなければ無視する
+        let known_path = dir.join(KNOWN_FILE_NAME);
+        let known = fs::read_to_string(&known_path)
+            .ok()
+            .and_then(|content| serde_json::from_str(&content).ok())
+            .unwrap_or_default();
+
+        Self {
+            passwords,
+            known,
+            known_path: Some(known_path),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.passwords.is_empty()
+    }
+
+    /// 試す順に並べたパスワード（以前このアーカイブで使えたものを先頭にする）
+    pub fn candidates(&self, archive_hash: &str) -> Vec<&str> {
+        let mut candidates: Vec<&str> = self.passwords.iter().map(String::as_str).collect();
+        if let Some(known) = self.known.get(archive_hash) {
+            if let Some(index) = candidates
+                .iter()
+                .position(|password| &sha256_hex(password.as_bytes()) == known)
+            {
+                let password = candidates.remove(index);
+                candidates.insert(0, password);
+            }
+        }
+        candidates
+    }
+
+    /// `password` がこのアーカイブで使えたことを記録する
+    pub fn remember(&mut self, archive_hash: &str, password: &str) {
+        let Some(known_path) = &self.known_path else {
+            return;
+        };
+        let digest = sha256_hex(password.as_bytes());
+        if self.known.get(archive_hash) == Some(&digest) {
+            return;
+        }
+        self.known.insert(archive_hash.to_string(), digest);
+
+        // 記録できなくても解凍自体は成功しているため、警告に留める
+        let result = serde_json::to_string_pretty(&self.known)
+            .map_err(io::Error::from)
+            .and_then(|content| fs::write(known_path, content));
+        if let Err(e) = result {
+            warn!(
+                "{}",
+                t!(
+                    "ui.warning_password_known_unsaved",
+                    file = known_path.display(),
+                    error = e
+                )
+            );
+        }
+    }
+}
+
+/// アーカイブを見分けるための指紋（SHA-256 の16進数）
+///
+/// 巨大なアーカイブを丸ごと読まないよう、サイズと先頭・末尾の 1 MiB だけから求める。
+pub fn archive_hash(file_path: &Path) -> Result<String> {
+    let mut file = File::open(file_path)?;
+    let size = file.metadata()?.len();
+
+    let mut hasher = Sha256::new();
+    hasher.update(size.to_le_bytes());
+    io::copy(&mut (&mut file).take(FINGERPRINT_CHUNK), &mut hasher)?;
+    // 2 MiB 以下なら残りをすべて読む（先頭と重ならないようにする）
+    if size > FINGERPRINT_CHUNK {
+        let tail = size
+            .saturating_sub(FINGERPRINT_CHUNK)
+            .max(FINGERPRINT_CHUNK);
+        file.seek(SeekFrom::Start(tail))?;
+        io::copy(&mut file, &mut hasher)?;
+    }
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// パスワード一覧などを置く archr の設定ディレクトリ
+fn config_dir() -> Option<PathBuf> {
+    dirs::config_dir().map(|dir| dir.join("archr"))
+}
+
+fn parse_list(content: &str) -> Vec<String> {
+    content
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+        .map(str::to_string)
+        .collect()
+}
+
+fn sha256_hex(data: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(data))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: usize = 1024 * 1024;
+
+    #[test]
+    fn fingerprints_size_head_and_tail() {
+        let dir = tempfile::tempdir().unwrap();
+        let hash = |name: &str, data: &[u8]| {
+            let path = dir.path().join(name);
+            fs::write(&path, data).unwrap();
+            archive_hash(&path).unwrap()
+        };
+
+        let data: Vec<u8> = (0..3 * MIB).map(|i| (i % 251) as u8).collect();
+        let original = hash("original", &data);
+        assert_eq!(hash("copy", &data), original);
+
+        let changed = |index: usize| {
+            let mut data = data.clone();
+            data[index] ^= 0xff;
+            data
+        };
+        assert_ne!(hash("head", &changed(10)), original);
+        assert_ne!(hash("tail", &changed(data.len() - 10)), original);
+        assert_ne!(hash("size", &data[..data.len() - 1]), original);
+        // 先頭・末尾の 1 MiB 以外は読まない
+        assert_eq!(hash("middle", &changed(MIB + MIB / 2)), original);
+
+        // 小さいファイルは全体を使う
+        let small = b"small archive".to_vec();
+        assert_ne!(hash("small1", &small), hash("small2", b"small archivf"));
+    }
+
+    #[test]
+    fn tries_remembered_password_first() {
+        let dir = tempfile::tempdir().unwrap();
+        let known_path = dir.path().join(KNOWN_FILE_NAME);
+        let mut list = PasswordList {
+            passwords: parse_list("# comment\none\n\ntwo\nthree\n"),
+            known: HashMap::new(),
+            known_path: Some(known_path.clone()),
+        };
+        assert_eq!(list.candidates("archive"), ["one", "two", "three"]);
+
+        list.remember("archive", "three");
+        assert_eq!(list.candidates("archive"), ["three", "one", "two"]);
+        assert_eq!(list.candidates("other"), ["one", "two", "three"]);
+
+        // 記録はファイルに保存され、パスワードそのものは含まない
+        let saved = fs::read_to_string(&known_path).unwrap();
+        assert!(!saved.contains("three"));
+        let known: HashMap<String, String> = serde_json::from_str(&saved).unwrap();
+        assert_eq!(known["archive"], sha256_hex(b"three"));
+    }
+}
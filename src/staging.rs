@@ -0,0 +1,278 @@
+use anyhow::Result;
+use std::ffi::{OsStr, OsString};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::get_unique_path;
+
+/// 作成中のステージングディレクトリ（Ctrl-C で中断されたときに削除する）
+static ACTIVE_STAGING_DIRS: Mutex<Vec<PathBuf>> = Mutex::new(Vec::new());
+
+/// 解凍結果の移動先
+pub struct CommitResult {
+    /// 最終的な解凍先のパス
+    pub path: PathBuf,
+    /// 中身が1つだけだったため、フォルダで包まずに置いたかどうか
+    pub collapsed: bool,
+}
+
+/// 解凍先の隣に作成する隠しディレクトリ
+///
+/// 解凍はまずこのディレクトリに対して行い、成功した場合のみ `commit` で
+/// 最終的な名前に変更する。`commit` せずに破棄された場合（エラー・パニック）や
+/// Ctrl-C で中断された場合はディレクトリごと削除するため、途中までの解凍結果は残らない。
+pub struct StagingDir {
+    path: PathBuf,
+}
+
+impl StagingDir {
+    /// `parent_dir` に `name` 用のステージングディレクトリを作成する
+    pub fn create(parent_dir: &Path, name: &OsStr) -> Result<Self> {
+        let mut staging_name = OsString::from(".");
+        staging_name.push(name);
+        staging_name.push(".archr-tmp");
+
+        let path = get_unique_path(parent_dir.join(staging_name));
+        fs::create_dir_all(&path)?;
+
+        lock_active().push(path.clone());
+        Ok(Self { path })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// 解凍した内容を `parent_dir` 内の最終的な場所へ移動する
+    ///
+    /// macOS のアーカイブユーティリティと同様に、直下がディレクトリ1つ、
+    /// またはファイル1つだけの場合はそれをそのまま置き、それ以外は `name` のフォルダにする。
+    /// 同名のファイル・ディレクトリがある場合は連番をつける。
+    pub fn commit(self, parent_dir: &Path, name: &OsStr) -> Result<CommitResult> {
+        if let Some(item) = single_item(&self.path)? {
+            let path = get_unique_path(parent_dir.join(item.file_name().unwrap_or(name)));
+            fs::rename(&item, &path)?;
+            return Ok(CommitResult {
+                path,
+                collapsed: true,
+            });
+        }
+
+        let path = get_unique_path(parent_dir.join(name));
+        fs::rename(&self.path, &path)?;
+        Ok(CommitResult {
+            path,
+            collapsed: false,
+        })
+    }
+}
+
+impl Drop for StagingDir {
+    fn drop(&mut self) {
+        // commit 済みの場合は空になっているか、すでに存在しない
+        let _ = fs::remove_dir_all(&self.path);
+        lock_active().retain(|path| path != &self.path);
+    }
+}
+
+/// Ctrl-C で中断されたときに、作成中のステージングディレクトリを削除して終了する
+pub fn install_interrupt_handler() {
+    tokio::spawn(async {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            // 解凍処理が並行して書き込んでいるため、削除に失敗した場合は数回やり直す
+            for path in lock_active().iter() {
+                for _ in 0..3 {
+                    if fs::remove_dir_all(path).is_ok() || !path.exists() {
+                        break;
+                    }
+                }
+            }
+            std::process::exit(130);
+        }
+    });
+}
+
+/// 直下がディレクトリ1つ、またはファイル1つだけの場合にそのパスを返す
+/// （シンボリックリンクはリンク先がずれるため対象外）
+fn single_item(dir: &Path) -> Result<Option<PathBuf>> {
+    let mut entries = fs::read_dir(dir)?;
+    let (Some(entry), None) = (entries.next().transpose()?, entries.next()) else {
+        return Ok(None);
+    };
+
+    let file_type = entry.file_type()?;
+    if file_type.is_dir() || file_type.is_file() {
+        Ok(Some(entry.path()))
+    } else {
+        Ok(None)
+    }
+}
+
+fn lock_active() -> std::sync::MutexGuard<'static, Vec<PathBuf>> {
+    // パニックで汚染されていても一覧自体は有効なので、そのまま使う
+    ACTIVE_STAGING_DIRS
+        .lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::TarBuilder;
+    use crate::extractors::{ExtractOptions, Extractor, TarCompression, TarExtractor};
+    use tempfile::TempDir;
+
+    /// `files` を書き込んだステージングディレクトリを `parent_dir` の中に作る
+    fn staged(parent_dir: &Path, files: &[&str]) -> StagingDir {
+        let staging = StagingDir::create(parent_dir, OsStr::new("archive")).unwrap();
+        for name in files {
+            let path = staging.path().join(name);
+            fs::create_dir_all(path.parent().unwrap()).unwrap();
+            fs::write(&path, name.as_bytes()).unwrap();
+        }
+        staging
+    }
+
+    /// `dir` の直下の名前（並べ替え済み）
+    fn names(dir: &Path) -> Vec<String> {
+        let mut names: Vec<String> = fs::read_dir(dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().file_name().to_string_lossy().into_owned())
+            .collect();
+        names.sort();
+        names
+    }
+
+    #[test]
+    fn collapses_single_folder() {
+        let dir = TempDir::new().unwrap();
+        let staging = staged(dir.path(), &["project/src/main.rs", "project/README"]);
+
+        let output = staging.commit(dir.path(), OsStr::new("archive")).unwrap();
+        assert!(output.collapsed);
+        assert_eq!(output.path, dir.path().join("project"));
+        assert_eq!(names(dir.path()), ["project"]);
+        assert_eq!(names(&output.path), ["README", "src"]);
+    }
+
+    #[test]
+    fn collapses_single_file() {
+        let dir = TempDir::new().unwrap();
+        let staging = staged(dir.path(), &["report.pdf"]);
+
+        let output = staging.commit(dir.path(), OsStr::new("archive")).unwrap();
+        assert!(output.collapsed);
+        assert_eq!(output.path, dir.path().join("report.pdf"));
+        assert_eq!(fs::read(&output.path).unwrap(), b"report.pdf");
+        assert_eq!(names(dir.path()), ["report.pdf"]);
+    }
+
+    #[test]
+    fn wraps_several_entries_in_a_folder() {
+        let dir = TempDir::new().unwrap();
+        let staging = staged(dir.path(), &["a.txt", "b/c.txt"]);
+
+        let output = staging.commit(dir.path(), OsStr::new("archive")).unwrap();
+        assert!(!output.collapsed);
+        assert_eq!(output.path, dir.path().join("archive"));
+        assert_eq!(names(&output.path), ["a.txt", "b"]);
+        assert_eq!(names(dir.path()), ["archive"]);
+    }
+
+    #[test]
+    fn numbers_collapsed_folder_that_already_exists() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("project")).unwrap();
+        fs::write(dir.path().join("project/old.txt"), b"old").unwrap();
+        let staging = staged(dir.path(), &["project/new.txt"]);
+
+        let output = staging.commit(dir.path(), OsStr::new("archive")).unwrap();
+        assert!(output.collapsed);
+        assert_eq!(output.path, dir.path().join("project (1)"));
+        assert_eq!(names(&output.path), ["new.txt"]);
+        assert_eq!(names(&dir.path().join("project")), ["old.txt"]);
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn keeps_single_symlink_in_a_folder() {
+        let dir = TempDir::new().unwrap();
+        let staging = staged(dir.path(), &[]);
+        std::os::unix::fs::symlink("target", staging.path().join("link")).unwrap();
+
+        let output = staging.commit(dir.path(), OsStr::new("archive")).unwrap();
+        assert!(!output.collapsed);
+        assert_eq!(names(&output.path), ["link"]);
+    }
+
+    #[test]
+    fn commits_under_the_archive_name() {
+        let dir = TempDir::new().unwrap();
+        let staging = staged(dir.path(), &["a.txt", "b.txt"]);
+        let staging_path = staging.path().to_path_buf();
+        assert!(staging_path
+            .file_name()
+            .unwrap()
+            .to_string_lossy()
+            .ends_with(".archr-tmp"));
+        assert!(lock_active().contains(&staging_path));
+
+        let output = staging.commit(dir.path(), OsStr::new("archive")).unwrap();
+        assert_eq!(output.path, dir.path().join("archive"));
+        assert!(!staging_path.exists());
+        assert!(!lock_active().contains(&staging_path));
+    }
+
+    #[test]
+    fn numbers_destination_that_already_exists() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join("archive")).unwrap();
+        fs::write(dir.path().join("archive (1)"), b"file").unwrap();
+        let staging = staged(dir.path(), &["a.txt", "b.txt"]);
+
+        let output = staging.commit(dir.path(), OsStr::new("archive")).unwrap();
+        assert_eq!(output.path, dir.path().join("archive (2)"));
+        assert_eq!(names(&output.path), ["a.txt", "b.txt"]);
+        assert_eq!(fs::read(dir.path().join("archive (1)")).unwrap(), b"file");
+    }
+
+    #[test]
+    fn uses_another_staging_name_when_taken() {
+        let dir = TempDir::new().unwrap();
+        fs::create_dir(dir.path().join(".archive.archr-tmp")).unwrap();
+        let staging = staged(dir.path(), &["a.txt"]);
+        assert_eq!(
+            staging.path(),
+            dir.path().join(".archive.archr-tmp (1)").as_path()
+        );
+        drop(staging);
+        assert_eq!(names(dir.path()), [".archive.archr-tmp"]);
+    }
+
+    #[test]
+    fn removes_partial_output_when_extraction_fails() {
+        let dir = TempDir::new().unwrap();
+        // 2つ目のエントリの途中で終わる TAR
+        let mut data = TarBuilder::new()
+            .file("a.txt", b"complete")
+            .file("b.txt", &[b'b'; 2000])
+            .build();
+        data.truncate(512 * 3);
+        let archive = dir.path().join("archive.tar");
+        fs::write(&archive, data).unwrap();
+
+        let staging = StagingDir::create(dir.path(), OsStr::new("archive")).unwrap();
+        let result = TarExtractor::new(TarCompression::None).extract(
+            &archive,
+            staging.path(),
+            &ExtractOptions::default(),
+        );
+        assert!(result.is_err());
+        assert!(staging.path().join("a.txt").exists());
+
+        // commit せずに破棄すると、途中までの結果ごと削除される
+        drop(staging);
+        assert_eq!(names(dir.path()), ["archive.tar"]);
+    }
+}
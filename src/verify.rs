@@ -0,0 +1,105 @@
+use anyhow::Result;
+use std::path::Path;
+
+// Import the i18n macro
+use rust_i18n::t;
+
+use crate::credentials::PasswordSource;
+use crate::extractors::Extractor;
+
+/// 検査したエントリの数と、そのうち破損していたエントリの数
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TestSummary {
+    pub count: usize,
+    pub failed: usize,
+}
+
+impl TestSummary {
+    /// 破損したエントリがなかったかどうか
+    pub fn is_ok(&self) -> bool {
+        self.failed == 0
+    }
+}
+
+/// アーカイブを検査し、エントリごとの結果を標準出力に表示する
+///
+/// アーカイブ自体が読めない場合は、それまでの結果を表示したうえで `Err` を返す。
+pub fn test_archive(
+    extractor: &dyn Extractor,
+    passwords: &PasswordSource,
+    file_path: &Path,
+) -> Result<TestSummary> {
+    println!("{} ({})", file_path.display(), extractor.name());
+
+    let mut count = 0;
+    let mut failed = 0;
+    passwords.run(file_path, |password| {
+        // パスワードを尋ね直した場合は最初から数え直す
+        count = 0;
+        failed = 0;
+        extractor.test(file_path, password, &mut |name, result| {
+            count += 1;
+            match result {
+                Ok(()) => println!("  {:<8} {}", t!("test.ok"), name),
+                Err(e) => {
+                    failed += 1;
+                    println!("  {:<8} {}: {}", t!("test.failed"), name, e);
+                }
+            }
+        })
+    })?;
+
+    if failed == 0 {
+        println!("{}", t!("test.summary_ok", count = count));
+    } else {
+        println!(
+            "{}",
+            t!("test.summary_failed", failed = failed, count = count)
+        );
+    }
+    Ok(TestSummary { count, failed })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::extractors::test_support::{corrupt_zip, zip_archive, Fixture};
+    use crate::extractors::ZipExtractor;
+
+    #[test]
+    fn counts_corrupt_entries() {
+        let fixture = Fixture::new("test.zip", &corrupt_zip());
+        let summary =
+            test_archive(&ZipExtractor, &PasswordSource::new(None), &fixture.archive).unwrap();
+        assert_eq!(
+            summary,
+            TestSummary {
+                count: 3,
+                failed: 1
+            }
+        );
+        assert!(!summary.is_ok());
+    }
+
+    #[test]
+    fn passes_intact_archives() {
+        let data = zip_archive(&[("a.txt", b"a"), ("b.txt", b"b")]);
+        let fixture = Fixture::new("test.zip", &data);
+        let summary =
+            test_archive(&ZipExtractor, &PasswordSource::new(None), &fixture.archive).unwrap();
+        assert_eq!(
+            summary,
+            TestSummary {
+                count: 2,
+                failed: 0
+            }
+        );
+        assert!(summary.is_ok());
+    }
+
+    #[test]
+    fn fails_on_unreadable_archives() {
+        let fixture = Fixture::new("test.zip", b"PK\x03\x04 truncated");
+        assert!(test_archive(&ZipExtractor, &PasswordSource::new(None), &fixture.archive).is_err());
+    }
+}